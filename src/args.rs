@@ -1,5 +1,9 @@
+use std::str::FromStr;
+
 use clap::Parser;
 
+use crate::server::{Bind, UnixBind, WorkerMode};
+
 const DEFAULT_HOST: &str = "0.0.0.0";
 
 #[derive(Parser, Debug)]
@@ -11,8 +15,303 @@ pub struct Args {
     pub host: String,
     #[arg(short, long, default_value_t = 4)]
     pub worker: u32,
+    /// write timeout to clients
     #[arg(short, long, default_value_t = 500)]
     pub timeout_ms: u64,
+    /// each read of a request header
+    #[arg(long, default_value_t = 5000)]
+    pub header_timeout_ms: u64,
+    /// whole request header, slow clients get 408
+    #[arg(long, default_value_t = 20000)]
+    pub header_deadline_ms: u64,
+    /// each read of a request body
+    #[arg(long, default_value_t = 10000)]
+    pub body_timeout_ms: u64,
+    /// minimum request body bytes per second after --body-timeout-ms, 0 disables
+    #[arg(long, default_value_t = 500)]
+    pub min_body_rate: u64,
     #[arg(long, default_value_t = 8196)]
     pub max_header_size: usize,
+    /// request line including the URI, 414 beyond
+    #[arg(long, default_value_t = 8192)]
+    pub max_request_line: usize,
+    /// header fields of a request, 431 beyond
+    #[arg(long, default_value_t = 100)]
+    pub max_header_count: usize,
+    /// one header field, 431 beyond
+    #[arg(long, default_value_t = 8192)]
+    pub max_header_field: usize,
+    /// request body bytes, 413 beyond, 0 disables
+    #[arg(long, default_value_t = 1024 * 1024)]
+    pub max_body_size: u64,
+    #[arg(long, default_value_t = false)]
+    pub h2c: bool,
+    /// certificate chain PEM, repeat with --tls-key for SNI
+    #[arg(long)]
+    pub tls_cert: Vec<String>,
+    /// private key PEM of --tls-cert in the same position
+    #[arg(long)]
+    pub tls_key: Vec<String>,
+    #[arg(long, value_delimiter = ',')]
+    pub tls_alpn: Vec<String>,
+    /// 1.2, 1.3
+    #[arg(long, value_delimiter = ',')]
+    pub tls_version: Vec<String>,
+    #[arg(long, value_delimiter = ',')]
+    pub tls_cipher: Vec<String>,
+    /// CA bundle PEM verifying client certificates
+    #[arg(long)]
+    pub tls_client_ca: Option<String>,
+    /// optional, required
+    #[arg(long, default_value = "required")]
+    pub tls_client_auth: String,
+    /// path prefixes requiring client certificate, others are served without it
+    #[arg(long, value_delimiter = ',')]
+    pub tls_client_auth_path: Vec<String>,
+    /// serve plaintext next to TLS on the same port
+    #[arg(long, default_value_t = false)]
+    pub sniff: bool,
+    /// expect PROXY protocol v1/v2 header from load balancer
+    #[arg(long, default_value_t = false)]
+    pub proxy_protocol: bool,
+    /// CIDR of proxies whose Forwarded / X-Forwarded-* headers are trusted
+    #[arg(long, value_delimiter = ',')]
+    pub trusted_proxy: Vec<String>,
+    /// HTML template of error responses, repeatable: CODE=PATH for a status, PATH for the others.
+    /// {status}, {reason} and {message} are replaced
+    #[arg(long)]
+    pub error_page: Vec<String>,
+    /// text, html or json, format of error responses to clients taking any
+    #[arg(long, default_value = "text")]
+    pub error_format: String,
+    /// JSON error responses as application/problem+json (RFC 9457)
+    #[arg(long, default_value_t = false)]
+    pub problem_json: bool,
+    /// handler panics answered 500 before a worker is replaced, 0 never replaces it
+    #[arg(long, default_value_t = 10)]
+    pub panic_budget: u32,
+    /// worker group of its own, repeatable: ADDR[,ADDR..][,OPTION..]
+    /// ADDR is host:port, [ipv6]:port, :port (IPv4 and IPv6), unix:PATH or udp:host:port,
+    /// OPTION is process=http|echo, tls, proxy-protocol, v6only, dual-stack, workers=N, threads=N or
+    /// mode=blocking|event|threaded.
+    /// Replaces --host, --port and --unix
+    #[arg(long)]
+    pub listen: Vec<Listen>,
+    /// blocking: a connection per worker at a time, event: many connections per worker with epoll,
+    /// threaded: --threads blocking threads per worker
+    #[arg(long, default_value_t = String::from("blocking"))]
+    pub worker_mode: String,
+    /// threads of each worker in threaded mode
+    #[arg(long, default_value_t = 8)]
+    pub threads: u32,
+    /// how workers share a listener: shared (all poll), mutex (fcntl lock), exclusive (EPOLLEXCLUSIVE),
+    /// reuseport (SO_REUSEPORT socket per worker)
+    #[arg(long, default_value_t = String::from("shared"))]
+    pub accept: String,
+    /// idle keep-alive connection timeout of event workers
+    #[arg(long, default_value_t = 5000)]
+    pub keep_alive_ms: u64,
+    /// also listen on Unix stream socket of this path
+    #[arg(long)]
+    pub unix: Option<String>,
+    /// permission of --unix socket in octal, e.g. 660
+    #[arg(long)]
+    pub unix_mode: Option<String>,
+    /// owner of --unix socket, user[:group] by name or id
+    #[arg(long)]
+    pub unix_owner: Option<String>,
+}
+
+/**
+ * One `--listen`: binds served by the same workers and process.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listen {
+    pub binds: Vec<Bind>,
+    pub process: String,
+    pub tls: bool,
+    pub proxy_protocol: bool,
+    pub workers: Option<u32>,
+    pub threads: Option<u32>,
+    pub mode: Option<WorkerMode>,
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut listen = Listen {
+            binds: vec![],
+            process: "http".to_string(),
+            tls: false,
+            proxy_protocol: false,
+            workers: None,
+            threads: None,
+            mode: None,
+        };
+        let mut v6_only = None;
+
+        for item in s.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            match item.split_once('=') {
+                Some(("process", process)) => listen.process = process.to_string(),
+                Some(("workers", workers)) => {
+                    listen.workers = Some(
+                        workers
+                            .parse()
+                            .map_err(|_| format!("invalid workers {workers}"))?,
+                    )
+                }
+                Some(("threads", threads)) => {
+                    listen.threads = Some(
+                        threads
+                            .parse()
+                            .ok()
+                            .filter(|t| *t > 0)
+                            .ok_or_else(|| format!("invalid threads {threads}"))?,
+                    )
+                }
+                Some(("mode", mode)) => {
+                    listen.mode = Some(
+                        WorkerMode::parse(mode).ok_or_else(|| format!("unknown mode {mode}"))?,
+                    )
+                }
+                Some(_) => return Err(format!("unknown option {item}")),
+                None => match item {
+                    "tls" => listen.tls = true,
+                    "proxy-protocol" => listen.proxy_protocol = true,
+                    "v6only" => v6_only = Some(true),
+                    "dual-stack" => v6_only = Some(false),
+                    addr => listen.binds.push(parse_bind(addr)?),
+                },
+            }
+        }
+
+        if listen.binds.is_empty() {
+            return Err(format!("no address in {s}"));
+        }
+
+        if let Some(v6_only) = v6_only {
+            for bind in &mut listen.binds {
+                if let Bind::Tcp {
+                    host, v6_only: v, ..
+                }
+                | Bind::Udp {
+                    host, v6_only: v, ..
+                } = bind
+                    && host.contains(':')
+                {
+                    *v = Some(v6_only);
+                }
+            }
+        }
+        return Ok(listen);
+    }
+}
+
+fn parse_bind(addr: &str) -> Result<Bind, String> {
+    if let Some(path) = addr.strip_prefix("unix:") {
+        return Ok(Bind::Unix(UnixBind::new(path)));
+    }
+    if let Some(addr) = addr.strip_prefix("udp:") {
+        return match parse_bind(addr)? {
+            Bind::Tcp {
+                host,
+                port,
+                v6_only,
+            } => Ok(Bind::Udp {
+                host,
+                port,
+                v6_only,
+            }),
+            _ => Err(format!("invalid UDP address {addr}")),
+        };
+    }
+
+    let (host, port) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| format!("{addr} has no port"))?;
+    let port = port
+        .parse()
+        .map_err(|_| format!("invalid port in {addr}"))?;
+
+    return Ok(match host {
+        // every address of both families
+        "" => Bind::Tcp {
+            host: "::".to_string(),
+            port,
+            v6_only: Some(false),
+        },
+        host => Bind::tcp(host.trim_start_matches('[').trim_end_matches(']'), port),
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        args::Listen,
+        server::{Bind, UnixBind, WorkerMode},
+    };
+
+    #[test]
+    fn test_parse_listen() {
+        let listen: Listen = "0.0.0.0:8080,[::]:8080,v6only,workers=2,mode=event"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            listen.binds,
+            vec![
+                Bind::tcp("0.0.0.0", 8080),
+                Bind::Tcp {
+                    host: "::".to_string(),
+                    port: 8080,
+                    v6_only: Some(true),
+                },
+            ]
+        );
+        assert_eq!(listen.process, "http");
+        assert_eq!(listen.workers, Some(2));
+        assert_eq!(listen.mode, Some(WorkerMode::Event));
+
+        let listen: Listen = ":9000,unix:/tmp/echo.sock,process=echo,tls,proxy-protocol"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            listen.binds,
+            vec![
+                Bind::Tcp {
+                    host: "::".to_string(),
+                    port: 9000,
+                    v6_only: Some(false),
+                },
+                Bind::Unix(UnixBind::new("/tmp/echo.sock")),
+            ]
+        );
+        assert_eq!(listen.process, "echo");
+        assert!(listen.tls && listen.proxy_protocol);
+
+        let listen: Listen = "127.0.0.1:8080,mode=threaded,threads=16".parse().unwrap();
+        assert_eq!(listen.mode, Some(WorkerMode::Threaded));
+        assert_eq!(listen.threads, Some(16));
+
+        let listen: Listen = "udp::5353,udp:127.0.0.1:9000,v6only".parse().unwrap();
+        assert_eq!(
+            listen.binds,
+            vec![
+                Bind::Udp {
+                    host: "::".to_string(),
+                    port: 5353,
+                    v6_only: Some(true),
+                },
+                Bind::udp("127.0.0.1", 9000),
+            ]
+        );
+
+        assert!("tls".parse::<Listen>().is_err());
+        assert!("udp:unix:/tmp/a".parse::<Listen>().is_err());
+        assert!("localhost".parse::<Listen>().is_err());
+        assert!("localhost:http".parse::<Listen>().is_err());
+        assert!("localhost:80,color=red".parse::<Listen>().is_err());
+        assert!("localhost:80,mode=async".parse::<Listen>().is_err());
+        assert!("localhost:80,threads=0".parse::<Listen>().is_err());
+    }
 }
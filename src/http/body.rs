@@ -0,0 +1,364 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+use crate::process::stream::Stream;
+
+const COPY_BUFFER: usize = 16 * 1024;
+
+/**
+ * Response body a handler sets with `HttpResponse::set_body`, instead of writing it.
+ * A body of known length is sent with Content-Length. Any other one is chunked to an HTTP/1.1 client
+ * and ends with the connection for HTTP/1.0.
+ */
+#[allow(dead_code)]
+pub enum Body {
+    Empty,
+    Bytes(Cow<'static, [u8]>),
+    Reader {
+        reader: Box<dyn Read>,
+        // exactly this many bytes are sent, the rest of the reader is left
+        length: Option<u64>,
+    },
+    // sent with sendfile(2) when the stream is a plain socket
+    File {
+        file: File,
+        // where the body starts in the file
+        offset: u64,
+        length: u64,
+    },
+    Chunks(Box<dyn Iterator<Item = Vec<u8>>>),
+}
+
+#[allow(dead_code)]
+impl Body {
+    pub fn reader(reader: impl Read + 'static, length: Option<u64>) -> Self {
+        return Body::Reader {
+            reader: Box::new(reader),
+            length,
+        };
+    }
+
+    /**
+     * Rest of `file` from its current position to its current length.
+     */
+    pub fn file(mut file: File) -> std::io::Result<Self> {
+        let offset = file.stream_position()?;
+        let length = file.metadata()?.len().saturating_sub(offset);
+        return Ok(Body::File {
+            file,
+            offset,
+            length,
+        });
+    }
+
+    pub fn chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: 'static,
+    {
+        return Body::Chunks(Box::new(chunks.into_iter()));
+    }
+
+    /**
+     * Length known before sending.
+     */
+    pub fn length(&self) -> Option<u64> {
+        return match self {
+            Body::Empty => Some(0),
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::Reader { length, .. } => *length,
+            Body::File { length, .. } => Some(*length),
+            Body::Chunks(_) => None,
+        };
+    }
+
+    /**
+     * Whole body in memory, for protocols sending it as one, like HTTP/2.
+     */
+    pub fn into_bytes(self) -> std::io::Result<Vec<u8>> {
+        return match self {
+            Body::Empty => Ok(vec![]),
+            Body::Bytes(bytes) => Ok(bytes.into_owned()),
+            Body::Reader {
+                reader,
+                length: Some(length),
+            } => read_exact_length(reader, length),
+            Body::Reader {
+                mut reader,
+                length: None,
+            } => {
+                let mut res = vec![];
+                reader.read_to_end(&mut res)?;
+                Ok(res)
+            }
+            Body::File {
+                mut file,
+                offset,
+                length,
+            } => {
+                file.seek(SeekFrom::Start(offset))?;
+                read_exact_length(Box::new(file), length)
+            }
+            Body::Chunks(chunks) => Ok(chunks.flatten().collect()),
+        };
+    }
+
+    /**
+     * Send the body after the header, chunked when `chunked`. Returns the bytes written.
+     * `sendfile` is the socket under `writer` to send a file from, when there is one.
+     */
+    pub(crate) fn write_to(
+        self,
+        writer: &mut dyn Write,
+        sendfile: Option<&dyn Stream>,
+        chunked: bool,
+    ) -> std::io::Result<usize> {
+        let mut written = 0;
+        match self {
+            Body::Empty => {}
+            Body::Bytes(bytes) => {
+                written += write_data(writer, &bytes, chunked)?;
+            }
+            Body::Reader { reader, length } => {
+                written += copy(reader, length, writer, chunked)?;
+            }
+            Body::File {
+                mut file,
+                offset,
+                length,
+            } => {
+                if let Some(stream) = sendfile {
+                    // header must be on the socket before the file
+                    writer.flush()?;
+                    if let Some(sent) = send_file(stream, &file, offset, length) {
+                        return sent;
+                    }
+                }
+                file.seek(SeekFrom::Start(offset))?;
+                written += copy(Box::new(file), Some(length), writer, chunked)?;
+            }
+            Body::Chunks(chunks) => {
+                for chunk in chunks {
+                    written += write_data(writer, &chunk, chunked)?;
+                }
+            }
+        }
+
+        if chunked {
+            writer.write_all(b"0\r\n\r\n")?;
+            written += 5;
+        }
+        writer.flush()?;
+        return Ok(written);
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        return Body::Bytes(Cow::Owned(value));
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        return Body::Bytes(Cow::Owned(value.into_bytes()));
+    }
+}
+
+impl From<&'static [u8]> for Body {
+    fn from(value: &'static [u8]) -> Self {
+        return Body::Bytes(Cow::Borrowed(value));
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(value: &'static str) -> Self {
+        return Body::Bytes(Cow::Borrowed(value.as_bytes()));
+    }
+}
+
+// data as is, or as one chunk, an empty chunk would end the body
+fn write_data(writer: &mut dyn Write, data: &[u8], chunked: bool) -> std::io::Result<usize> {
+    if data.is_empty() {
+        return Ok(0);
+    }
+    if !chunked {
+        writer.write_all(data)?;
+        return Ok(data.len());
+    }
+    let size = format!("{:x}\r\n", data.len());
+    writer.write_all(size.as_bytes())?;
+    writer.write_all(data)?;
+    writer.write_all(b"\r\n")?;
+    return Ok(size.len() + data.len() + 2);
+}
+
+// `length` bytes of `reader` or all of it, a reader ending early is an error as the length was promised
+fn copy(
+    mut reader: Box<dyn Read>,
+    length: Option<u64>,
+    writer: &mut dyn Write,
+    chunked: bool,
+) -> std::io::Result<usize> {
+    let mut buf = vec![0; COPY_BUFFER];
+    let mut left = length.unwrap_or(u64::MAX);
+    let mut written = 0;
+    while left > 0 {
+        let max = buf.len().min(left.try_into().unwrap_or(usize::MAX));
+        let n = match reader.read(&mut buf[..max]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        written += write_data(writer, &buf[..n], chunked)?;
+        left -= n as u64;
+    }
+    if length.is_some() && left > 0 {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "body shorter than its length",
+        ));
+    }
+    return Ok(written);
+}
+
+fn read_exact_length(reader: Box<dyn Read>, length: u64) -> std::io::Result<Vec<u8>> {
+    let mut res = vec![];
+    copy(reader, Some(length), &mut res, false)?;
+    return Ok(res);
+}
+
+// `None` when the stream can not send a file before anything was sent
+fn send_file(
+    stream: &dyn Stream,
+    file: &File,
+    offset: u64,
+    length: u64,
+) -> Option<std::io::Result<usize>> {
+    let mut sent = 0;
+    while sent < length {
+        let count = (length - sent).min(1 << 30) as usize;
+        match stream.send_file(file, offset + sent, count) {
+            None if sent == 0 => return None,
+            None => return Some(Err(Error::other("stream stopped sending file"))),
+            Some(Ok(0)) => {
+                return Some(Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "file shorter than its length",
+                )));
+            }
+            Some(Ok(n)) => sent += n as u64,
+            Some(Err(e)) if e.kind() == ErrorKind::Interrupted => {}
+            Some(Err(e)) => return Some(Err(e)),
+        }
+    }
+    return Some(Ok(sent as usize));
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::File,
+        io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
+        os::unix::net::UnixStream,
+        thread,
+    };
+
+    use crate::http::body::Body;
+
+    #[test]
+    fn test_write_to() {
+        let mut out = vec![];
+        let body = Body::chunks(vec![b"ab".to_vec(), vec![], b"cde".to_vec()]);
+        assert_eq!(body.length(), None);
+        let written = body.write_to(&mut out, None, true).unwrap();
+        assert_eq!(out, b"2\r\nab\r\n3\r\ncde\r\n0\r\n\r\n");
+        assert_eq!(written, out.len());
+
+        let mut out = vec![];
+        let body = Body::reader(Cursor::new(b"0123456789".to_vec()), Some(4));
+        assert_eq!(body.length(), Some(4));
+        body.write_to(&mut out, None, false).unwrap();
+        assert_eq!(out, b"0123");
+
+        let mut out = vec![];
+        let body = Body::reader(Cursor::new(b"01".to_vec()), Some(4));
+        let err = body.write_to(&mut out, None, false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let body = Body::from("static");
+        assert_eq!(body.into_bytes().unwrap(), b"static");
+    }
+
+    #[test]
+    fn test_file() {
+        let path = std::env::temp_dir().join(format!("server_rs.body.{}", rand::random::<u32>()));
+        File::create(&path).unwrap().write_all(&[7; 40000]).unwrap();
+        let body = Body::file(File::open(&path).unwrap()).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(body.length(), Some(40000));
+        let mut out = vec![];
+        body.write_to(&mut out, None, true).unwrap();
+        assert!(out.starts_with(b"4000\r\n"));
+        assert!(out.ends_with(b"\r\n0\r\n\r\n"));
+        assert_eq!(out.len(), 40000 + 3 * "4000\r\n\r\n".len() + 5);
+
+        // sendfile straight to a socket, nothing goes through the writer
+        let (server, mut client) = UnixStream::pair().unwrap();
+        let reader = thread::spawn(move || {
+            let mut received = vec![];
+            client.read_to_end(&mut received).unwrap();
+            return received;
+        });
+        let body = Body::file(file).unwrap();
+        let mut out = vec![];
+        let sent = body.write_to(&mut out, Some(&server), false).unwrap();
+        drop(server);
+        assert_eq!(sent, 40000);
+        assert!(out.is_empty());
+        assert_eq!(reader.join().unwrap(), vec![7; 40000]);
+    }
+
+    #[test]
+    fn test_seeked_file() {
+        let path = std::env::temp_dir().join(format!("server_rs.body.{}", rand::random::<u32>()));
+        File::create(&path)
+            .unwrap()
+            .write_all(b"0123456789")
+            .unwrap();
+        let open = || {
+            let mut file = File::open(&path).unwrap();
+            file.seek(SeekFrom::Start(4)).unwrap();
+            return file;
+        };
+        let (copied, sent, whole) = (open(), open(), open());
+        std::fs::remove_file(&path).unwrap();
+
+        let body = Body::file(copied).unwrap();
+        assert_eq!(body.length(), Some(6));
+        let mut out = vec![];
+        body.write_to(&mut out, None, false).unwrap();
+        assert_eq!(out, b"456789");
+
+        // sendfile starts at the same place as the copy
+        let (server, mut client) = UnixStream::pair().unwrap();
+        let reader = thread::spawn(move || {
+            let mut received = vec![];
+            client.read_to_end(&mut received).unwrap();
+            return received;
+        });
+        let body = Body::file(sent).unwrap();
+        let sent = body.write_to(&mut vec![], Some(&server), false).unwrap();
+        drop(server);
+        assert_eq!(sent, 6);
+        assert_eq!(reader.join().unwrap(), b"456789");
+
+        assert_eq!(Body::file(whole).unwrap().into_bytes().unwrap(), b"456789");
+    }
+}
@@ -0,0 +1,341 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::http::{
+    typed::{Accept, ContentType, MediaType},
+    value::HttpResponseCode,
+};
+
+// page of a status without a template
+const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>\n\
+<html>\n\
+<head><title>{status} {reason}</title></head>\n\
+<body>\n\
+<h1>{status} {reason}</h1>\n\
+<p>{message}</p>\n\
+</body>\n\
+</html>\n";
+
+/**
+ * Kind of document an error response is sent as.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    Html,
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn parse(str: &str) -> Option<Self> {
+        return match str.to_ascii_lowercase().as_str() {
+            "text" => Some(ErrorFormat::Text),
+            "html" => Some(ErrorFormat::Html),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        };
+    }
+
+    fn media_types(&self) -> &'static [&'static str] {
+        return match self {
+            ErrorFormat::Text => &["text/plain"],
+            ErrorFormat::Html => &["text/html"],
+            ErrorFormat::Json => &["application/json", "application/problem+json"],
+        };
+    }
+}
+
+/**
+ * Documents of error responses, both the ones the server makes, like 400 of a broken request or 413 of a body
+ * over the limit, and the ones handlers send with `HttpResponse::send_error`.
+ * The format is picked from the request `Accept`: HTML from a template of the status, JSON, or plain text.
+ * Templates replace `{status}`, `{reason}` and `{message}` with HTML escaped values.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPages {
+    // HTML templates by status code
+    templates: HashMap<i32, String>,
+    // template of a status without one of its own
+    fallback: Option<String>,
+    // format of a client taking anything, or none of the formats
+    default_format: ErrorFormat,
+    // JSON as application/problem+json of RFC 9457
+    problem_json: bool,
+}
+
+/**
+ * Rendered error document.
+ */
+pub struct ErrorPage {
+    pub content_type: ContentType,
+    pub body: String,
+}
+
+#[allow(dead_code)]
+impl ErrorPages {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /**
+     * HTML template of status `code`, or of every status without one of its own when `code` is `None`.
+     */
+    pub fn with_template(mut self, code: Option<i32>, template: &str) -> Self {
+        match code {
+            Some(code) => {
+                self.templates.insert(code, template.to_string());
+            }
+            None => self.fallback = Some(template.to_string()),
+        }
+        return self;
+    }
+
+    /**
+     * Template read from `path` now, like `with_template`.
+     */
+    pub fn with_file(self, code: Option<i32>, path: &Path) -> std::io::Result<Self> {
+        let template = std::fs::read_to_string(path)?;
+        return Ok(self.with_template(code, &template));
+    }
+
+    pub fn with_default_format(mut self, format: ErrorFormat) -> Self {
+        self.default_format = format;
+        return self;
+    }
+
+    /**
+     * Send JSON errors as `application/problem+json` problem details instead of `application/json`.
+     */
+    pub fn with_problem_json(mut self, problem_json: bool) -> Self {
+        self.problem_json = problem_json;
+        return self;
+    }
+
+    /**
+     * Format the client wants most among the ones there are. The default one for any media type, no `Accept`
+     * or only formats the server does not make.
+     */
+    pub fn negotiate(&self, accept: Option<&Accept>) -> ErrorFormat {
+        let Some(accept) = accept else {
+            return self.default_format;
+        };
+        let formats = [
+            self.default_format,
+            ErrorFormat::Html,
+            ErrorFormat::Json,
+            ErrorFormat::Text,
+        ];
+        for range in accept.preferred() {
+            let Some(range) = MediaType::parse(range.value()) else {
+                continue;
+            };
+            let format = formats
+                .iter()
+                .find(|f| f.media_types().iter().any(|m| range.is(m)));
+            if let Some(format) = format {
+                return *format;
+            }
+        }
+        return self.default_format;
+    }
+
+    /**
+     * Document of `code` in the format `accept` wants. `message` tells more than the reason phrase, it may be empty.
+     */
+    pub fn render(
+        &self,
+        code: &HttpResponseCode,
+        message: &str,
+        accept: Option<&Accept>,
+    ) -> ErrorPage {
+        return match self.negotiate(accept) {
+            ErrorFormat::Text => ErrorPage {
+                content_type: ContentType::new("text", "plain").with_charset("utf-8"),
+                body: if message.is_empty() {
+                    code.reason().to_string()
+                } else {
+                    message.to_string()
+                },
+            },
+            ErrorFormat::Html => {
+                let template = self
+                    .templates
+                    .get(&code.code())
+                    .or(self.fallback.as_ref())
+                    .map(|t| t.as_str())
+                    .unwrap_or(DEFAULT_TEMPLATE);
+                ErrorPage {
+                    content_type: ContentType::new("text", "html").with_charset("utf-8"),
+                    body: fill(template, code, message),
+                }
+            }
+            ErrorFormat::Json if self.problem_json => {
+                let mut body = format!(
+                    "{{\"type\":\"about:blank\",\"title\":{},\"status\":{}",
+                    json_string(code.reason()),
+                    code.code()
+                );
+                if !message.is_empty() {
+                    body.push_str(&format!(",\"detail\":{}", json_string(message)));
+                }
+                body.push('}');
+                ErrorPage {
+                    content_type: ContentType::new("application", "problem+json"),
+                    body,
+                }
+            }
+            ErrorFormat::Json => ErrorPage {
+                content_type: ContentType::new("application", "json"),
+                body: format!(
+                    "{{\"status\":{},\"error\":{},\"message\":{}}}",
+                    code.code(),
+                    json_string(code.reason()),
+                    json_string(message)
+                ),
+            },
+        };
+    }
+}
+
+// placeholders of `template` replaced in one pass, so a message can not bring in another one
+fn fill(template: &str, code: &HttpResponseCode, message: &str) -> String {
+    let status = code.code().to_string();
+    let values = [
+        ("{status}", status.as_str()),
+        ("{reason}", code.reason()),
+        ("{message}", message),
+    ];
+
+    let mut res = String::with_capacity(template.len() + message.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match values.iter().find(|(key, _)| rest.starts_with(key)) {
+            Some((key, value)) => {
+                res.push_str(&html_escape(value));
+                rest = &rest[key.len()..];
+            }
+            None => {
+                res.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    return res;
+}
+
+fn html_escape(str: &str) -> String {
+    let mut res = String::with_capacity(str.len());
+    for c in str.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    return res;
+}
+
+fn json_string(str: &str) -> String {
+    let mut res = String::with_capacity(str.len() + 2);
+    res.push('"');
+    for c in str.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    return res;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::http::{
+        error_page::{ErrorFormat, ErrorPages},
+        typed::{Accept, TypedHeader},
+        value::HttpResponseCode,
+    };
+
+    #[test]
+    fn test_negotiate() {
+        let pages = ErrorPages::new();
+        let accept = |value: &str| Accept::parse(value).unwrap();
+
+        assert_eq!(pages.negotiate(None), ErrorFormat::Text);
+        assert_eq!(pages.negotiate(Some(&accept("*/*"))), ErrorFormat::Text);
+        assert_eq!(
+            pages.negotiate(Some(&accept("text/html,application/xhtml+xml,*/*;q=0.8"))),
+            ErrorFormat::Html
+        );
+        assert_eq!(
+            pages.negotiate(Some(&accept("text/html;q=0.5, application/json"))),
+            ErrorFormat::Json
+        );
+        assert_eq!(
+            pages.negotiate(Some(&accept("application/problem+json"))),
+            ErrorFormat::Json
+        );
+        assert_eq!(pages.negotiate(Some(&accept("text/*"))), ErrorFormat::Text);
+        assert_eq!(
+            pages.negotiate(Some(&accept("image/png"))),
+            ErrorFormat::Text
+        );
+
+        let pages = pages.with_default_format(ErrorFormat::Html);
+        assert_eq!(pages.negotiate(Some(&accept("*/*"))), ErrorFormat::Html);
+        assert_eq!(
+            pages.negotiate(Some(&accept("application/*"))),
+            ErrorFormat::Json
+        );
+        assert_eq!(ErrorFormat::parse("JSON"), Some(ErrorFormat::Json));
+    }
+
+    #[test]
+    fn test_render() {
+        let html = Accept::parse("text/html").unwrap();
+        let json = Accept::parse("application/json").unwrap();
+        let pages = ErrorPages::new()
+            .with_template(Some(404), "<p>{status} {reason}: {message} {other}</p>")
+            .with_template(None, "<b>{status}</b>");
+
+        let page = pages.render(&HttpResponseCode::NotFound, "<{reason}>", Some(&html));
+        assert_eq!(page.content_type.0.essence(), "text/html");
+        assert_eq!(page.body, "<p>404 Not Found: &lt;{reason}&gt; {other}</p>");
+
+        let page = pages.render(&HttpResponseCode::BadRequest, "", Some(&html));
+        assert_eq!(page.body, "<b>400</b>");
+
+        let page = ErrorPages::new().render(&HttpResponseCode::BadRequest, "", Some(&html));
+        assert!(page.body.contains("<h1>400 Bad Request</h1>"));
+
+        let page = pages.render(&HttpResponseCode::PayloadTooLarge, "", None);
+        assert_eq!(page.content_type.0.essence(), "text/plain");
+        assert_eq!(page.body, "Payload Too Large");
+
+        let page = pages.render(&HttpResponseCode::BadRequest, "bad \"x\"\n", Some(&json));
+        assert_eq!(page.content_type.0.essence(), "application/json");
+        assert_eq!(
+            page.body,
+            r#"{"status":400,"error":"Bad Request","message":"bad \"x\"\n"}"#
+        );
+
+        let pages = pages.with_problem_json(true);
+        let page = pages.render(&HttpResponseCode::MethodNotAllowed, "", Some(&json));
+        assert_eq!(page.content_type.0.essence(), "application/problem+json");
+        assert_eq!(
+            page.body,
+            r#"{"type":"about:blank","title":"Method Not Allowed","status":405}"#
+        );
+    }
+}
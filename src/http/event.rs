@@ -0,0 +1,550 @@
+use std::{
+    cell::RefCell,
+    io::{self, Cursor, ErrorKind, Read, Write},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    http::{
+        handler::Handler,
+        http::Http1,
+        limit::{BodyLimitReader, Limit, Limits},
+        parser::{self, BodyLength, ChunkedReader, HeadError, RequestHead},
+        value::HttpResponseCode,
+    },
+    process::{
+        self, Process,
+        address::Address,
+        event::{EventProcess, Session},
+        stream::{SharedStream, Stream},
+    },
+};
+
+const HEAD_END: &[u8] = b"\r\n\r\n";
+// a body is buffered whole before the handler runs, so it is bound even when the body limit is disabled
+const MAX_BUFFERED_BODY: u64 = 64 * 1024 * 1024;
+
+/**
+ * Http1 over the event worker. Each complete request in the input is answered by the blocking `process`
+ * on an in-memory stream, so both modes share parsing and handler calls.
+ * The handler runs inside the event loop, a handler which sleeps or streams holds every connection of the worker.
+ * A header still incomplete after `header_deadline` is answered 408, on its next bytes or the next sweep of the worker.
+ */
+impl<T> EventProcess for Http1<T>
+where
+    T: Handler,
+{
+    fn receive(&self, session: &mut Session) -> Result<(), process::Error> {
+        while !session.close {
+            // empty lines between requests
+            let blank = session
+                .input
+                .chunks(2)
+                .take_while(|line| *line == b"\r\n")
+                .count();
+            session.input.drain(..blank * 2);
+
+            let frame = parser::with_request_head(|head| {
+                return frame_request(
+                    &session.input,
+                    head,
+                    self.limits(),
+                    self.max_header_length(),
+                );
+            });
+            let frame = match frame {
+                Ok(Some(frame)) => frame,
+                Ok(None) if session.input.is_empty() => return Ok(()),
+                Ok(None) => {
+                    session.started.get_or_insert_with(Instant::now);
+                    self.expire(session);
+                    return Ok(());
+                }
+                Err(()) => {
+                    // let process answer 400, or the status of the exceeded limit
+                    Frame {
+                        length: session.input.len(),
+                        close: true,
+                        too_large: false,
+                    }
+                }
+            };
+            if frame.too_large {
+                self.respond_too_large(session);
+                return Ok(());
+            }
+
+            session.started = None;
+            let request: Vec<u8> = session.input.drain(..frame.length).collect();
+            let (stream, output) = BufferStream::new(request, session);
+
+            let result = self.process(Box::new(stream), &session.client_addr);
+            let output = output.take();
+            // without length the end of response is the end of connection
+            session.close = frame.close || result.is_err() || !has_length(&output);
+            session.output.extend(output);
+        }
+        return Ok(());
+    }
+
+    fn expire(&self, session: &mut Session) {
+        let Some(started) = session.started else {
+            return;
+        };
+        // a head is never longer, the rest may be a large body
+        let head = &session.input[..session
+            .input
+            .len()
+            .min(self.max_header_length() + HEAD_END.len())];
+        if !session.close && started.elapsed() > self.timeouts().header_deadline && !has_head(head)
+        {
+            self.respond_and_close(session, |stream| {
+                self.error_response(
+                    stream,
+                    HttpResponseCode::RequestTimeout,
+                    "Request timeout",
+                    None,
+                );
+            });
+        }
+    }
+}
+
+impl<T> Http1<T>
+where
+    T: Handler,
+{
+    // body over the buffer of event mode, which process would not refuse by itself
+    fn respond_too_large(&self, session: &mut Session) {
+        let client_addr = session.client_addr.clone();
+        self.respond_and_close(session, |stream| {
+            self.error_response_for_limit(stream, &client_addr, Limit::Body, None);
+        });
+    }
+
+    fn respond_and_close(&self, session: &mut Session, respond: impl FnOnce(&SharedStream)) {
+        let (stream, output) = BufferStream::new(vec![], session);
+        respond(&SharedStream::new(Box::new(stream)));
+        session.input.clear();
+        session.started = None;
+        session.output.extend(output.take());
+        session.close = true;
+    }
+}
+
+struct Frame {
+    length: usize,
+    // no further request on the connection
+    close: bool,
+    // body over `MAX_BUFFERED_BODY`, answered 413 without process
+    too_large: bool,
+}
+
+/**
+ * Length of the first request in `input`, `None` until all of it arrived.
+ * The head is read by the same `RequestHead` and a chunked body decoded by the same `ChunkedReader` as process,
+ * so both agree where the request ends.
+ * A body over the limit is not waited for, what arrived is framed for process to refuse it.
+ * A body over `MAX_BUFFERED_BODY` is refused the same way when the limit would not refuse it.
+ */
+fn frame_request(
+    input: &[u8],
+    head: &mut RequestHead,
+    limits: &Limits,
+    max_header_length: usize,
+) -> Result<Option<Frame>, ()> {
+    match head.read(&mut &input[..], limits, max_header_length) {
+        Ok(()) => {}
+        Err(HeadError::Eof | HeadError::Incomplete) => return Ok(None),
+        Err(_) => return Err(()),
+    }
+
+    let mut close = head.version() != "HTTP/1.1";
+    for (name, value) in head.fields() {
+        if name.eq_ignore_ascii_case("connection") {
+            close |= value
+                .split(',')
+                .any(|v| v.trim().eq_ignore_ascii_case("close"));
+        }
+    }
+
+    let content_length = match parser::body_length(head.fields()).map_err(|_| ())? {
+        BodyLength::None => 0,
+        BodyLength::Length(length) => length,
+        BodyLength::Chunked => {
+            return Ok(frame_chunked(input, head.len(), close, limits));
+        }
+    };
+
+    let limited = limits.body > 0 && limits.body <= MAX_BUFFERED_BODY;
+    if content_length > limits.body && limited {
+        return Ok(Some(Frame {
+            length: head.len(),
+            close: true,
+            too_large: false,
+        }));
+    }
+    if content_length > MAX_BUFFERED_BODY {
+        return Ok(Some(Frame {
+            length: head.len(),
+            close: true,
+            too_large: true,
+        }));
+    }
+
+    let length = head.len() + content_length as usize;
+    if input.len() < length {
+        return Ok(None);
+    }
+    return Ok(Some(Frame {
+        length,
+        close,
+        too_large: false,
+    }));
+}
+
+// request with the chunked body after its head of `head_length`, `None` until the last chunk arrived
+fn frame_chunked(input: &[u8], head_length: usize, close: bool, limits: &Limits) -> Option<Frame> {
+    let limited = limits.body > 0 && limits.body <= MAX_BUFFERED_BODY;
+    let max = if limited {
+        limits.body
+    } else {
+        MAX_BUFFERED_BODY
+    };
+    let mut rest = &input[head_length..];
+    let mut reader = BodyLimitReader::new(ChunkedReader::new(&mut rest), max);
+    let exceeded = reader.exceeded();
+    return match io::copy(&mut reader, &mut io::sink()) {
+        Ok(_) => Some(Frame {
+            length: input.len() - rest.len(),
+            close,
+            too_large: false,
+        }),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+        Err(_) if exceeded.get() && !limited => Some(Frame {
+            length: head_length,
+            close: true,
+            too_large: true,
+        }),
+        // let process answer 400, or 413 of the limit
+        Err(_) => Some(Frame {
+            length: input.len(),
+            close: true,
+            too_large: false,
+        }),
+    };
+}
+
+fn has_head(input: &[u8]) -> bool {
+    return input.windows(HEAD_END.len()).any(|w| w == HEAD_END);
+}
+
+// Content-Length or chunked, else the response ends with the connection
+fn has_length(response: &[u8]) -> bool {
+    let head_end = response
+        .windows(HEAD_END.len())
+        .position(|w| w == HEAD_END)
+        .unwrap_or(response.len());
+    return String::from_utf8_lossy(&response[..head_end])
+        .split("\r\n")
+        .skip(1)
+        .any(|line| {
+            line.split_once(':')
+                .map(|(name, value)| {
+                    let name = name.trim();
+                    name.eq_ignore_ascii_case("content-length")
+                        || (name.eq_ignore_ascii_case("transfer-encoding")
+                            && value.trim().eq_ignore_ascii_case("chunked"))
+                })
+                .unwrap_or(false)
+        });
+}
+
+// one request in, its response out
+struct BufferStream {
+    input: Cursor<Vec<u8>>,
+    output: Rc<RefCell<Vec<u8>>>,
+    peer_addr: Address,
+    local_addr: Address,
+}
+
+impl BufferStream {
+    fn new(input: Vec<u8>, session: &Session) -> (Self, Rc<RefCell<Vec<u8>>>) {
+        let output = Rc::new(RefCell::new(vec![]));
+        let stream = BufferStream {
+            input: Cursor::new(input),
+            output: output.clone(),
+            peer_addr: session.client_addr.clone(),
+            local_addr: session.local_addr.clone(),
+        };
+        return (stream, output);
+    }
+}
+
+impl Read for BufferStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        return self.input.read(buf);
+    }
+}
+
+impl Write for BufferStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.borrow_mut().extend_from_slice(buf);
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl Stream for BufferStream {
+    fn set_read_timeout(&self, _: Option<Duration>) -> std::io::Result<()> {
+        return Ok(());
+    }
+
+    fn set_write_timeout(&self, _: Option<Duration>) -> std::io::Result<()> {
+        return Ok(());
+    }
+
+    fn peer_addr(&self) -> Address {
+        return self.peer_addr.clone();
+    }
+
+    fn local_addr(&self) -> Address {
+        return self.local_addr.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        http::{
+            handler::Handler,
+            http::Http1,
+            limit::Limits,
+            request::HttpRequest,
+            response::HttpResponse,
+            test_client::{TestRequest, TestResponse},
+            timeout::Timeouts,
+        },
+        process::{
+            address::Address,
+            event::{EventProcess, Session},
+        },
+    };
+    use std::{io::Write, thread, time::Duration};
+
+    struct PathHandler;
+
+    impl Handler for PathHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            let mut body = String::new();
+            let _ = req.reader().read_to_string(&mut body);
+            let _ = write!(res, "{} {}", req.path(), body);
+        }
+    }
+
+    fn new_session() -> Session {
+        return Session::new(
+            Address::Inet("127.0.0.1:50000".parse().unwrap()),
+            Address::Unnamed,
+        );
+    }
+
+    fn responses(output: &[u8]) -> Vec<String> {
+        let mut res = vec![];
+        let mut rest = output;
+        while !rest.is_empty() {
+            let response = TestResponse::parse(rest);
+            let head_end = rest.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+            rest = &rest[head_end + response.body.len()..];
+            res.push(response.text());
+        }
+        return res;
+    }
+
+    #[test]
+    fn test_partial_and_pipelined() {
+        let http = Http1::new(8192, PathHandler);
+        let mut session = new_session();
+        let request = TestRequest::post("/a").body("body").encode();
+
+        // split in the middle of the body
+        session.input.extend(&request[..request.len() - 2]);
+        http.receive(&mut session).unwrap();
+        assert!(session.output.is_empty());
+
+        session.input.extend(&request[request.len() - 2..]);
+        session.input.extend(TestRequest::get("/b").encode());
+        http.receive(&mut session).unwrap();
+        assert!(session.input.is_empty());
+        assert!(!session.close);
+        assert_eq!(responses(&session.output), vec!["/a body", "/b "]);
+    }
+
+    #[test]
+    fn test_close() {
+        let http = Http1::new(8192, PathHandler);
+
+        let mut session = new_session();
+        session.input.extend(
+            TestRequest::get("/a")
+                .header("Connection", "close")
+                .encode(),
+        );
+        session.input.extend(TestRequest::get("/b").encode());
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(responses(&session.output), vec!["/a "]);
+
+        let mut session = new_session();
+        session
+            .input
+            .extend(TestRequest::get("/a").version("HTTP/1.0").encode());
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+
+        // body can not be framed, answer it and stop
+        let mut session = new_session();
+        session
+            .input
+            .extend(b"GET / HTTP/1.1\r\nContent-Length: x\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(responses(&session.output).len(), 1);
+
+        let mut session = new_session();
+        session.input.extend(vec![b'a'; 9000]);
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 414);
+
+        // second request hidden in the body of a conflicting length is never served
+        let mut session = new_session();
+        session.input.extend(
+            b"POST / HTTP/1.1\r\nContent-Length: 0\r\nContent-Length: 19\r\n\r\nGET /b HTTP/1.1\r\n\r\n",
+        );
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 400);
+        assert_eq!(responses(&session.output).len(), 1);
+
+        // Content-Length with an empty Transfer-Encoding
+        let mut session = new_session();
+        session.input.extend(
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding:\r\n\r\n0\r\n\r\nGET /b HTTP/1.1\r\n\r\n",
+        );
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 400);
+        assert_eq!(responses(&session.output).len(), 1);
+
+        // buffered body is bound without a body limit
+        let unlimited = Http1::new(8192, PathHandler).with_limits(Limits {
+            body: 0,
+            ..Limits::default()
+        });
+        let mut session = new_session();
+        session
+            .input
+            .extend(b"POST / HTTP/1.1\r\nContent-Length: 100000000\r\n\r\nabc");
+        unlimited.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert!(session.input.is_empty());
+        assert_eq!(TestResponse::parse(&session.output).status, 413);
+
+        // refused without waiting for the body
+        let mut session = new_session();
+        session
+            .input
+            .extend(b"POST / HTTP/1.1\r\nContent-Length: 2000000\r\n\r\nabc");
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 413);
+    }
+
+    #[test]
+    fn test_chunked() {
+        let http = Http1::new(8192, PathHandler);
+        let mut session = new_session();
+        let request =
+            b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nbody\r\n0\r\n\r\n";
+
+        // waits for the last chunk, then serves the next request
+        session.input.extend(&request[..request.len() - 5]);
+        http.receive(&mut session).unwrap();
+        assert!(session.output.is_empty());
+
+        session.input.extend(&request[request.len() - 5..]);
+        session.input.extend(TestRequest::get("/b").encode());
+        http.receive(&mut session).unwrap();
+        assert!(session.input.is_empty());
+        assert!(!session.close);
+        assert_eq!(responses(&session.output), vec!["/a body", "/b "]);
+
+        let mut session = new_session();
+        session.input.extend(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nbody\r\n0\r\n\r\nGET /b HTTP/1.1\r\n\r\n",
+        );
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 400);
+        assert_eq!(responses(&session.output).len(), 1);
+
+        // refused without waiting for the rest
+        let http = Http1::new(8192, PathHandler).with_limits(Limits {
+            body: 3,
+            ..Limits::default()
+        });
+        let mut session = new_session();
+        session
+            .input
+            .extend(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nbody\r\n");
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 413);
+    }
+
+    #[test]
+    fn test_header_deadline() {
+        let http = Http1::new(8192, PathHandler).with_timeouts(Timeouts {
+            header_deadline: Duration::from_millis(50),
+            ..Timeouts::default()
+        });
+        let mut session = new_session();
+
+        session.input.extend(b"GET / HTTP/1.1\r\n");
+        http.receive(&mut session).unwrap();
+        assert!(session.output.is_empty());
+
+        thread::sleep(Duration::from_millis(60));
+        session.input.extend(b"Host: a\r\n");
+        http.receive(&mut session).unwrap();
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 408);
+
+        // silent client, answered by the sweep of the worker
+        let mut session = new_session();
+        session.input.extend(b"GET / HTTP/1.1\r\n");
+        http.receive(&mut session).unwrap();
+        http.expire(&mut session);
+        assert!(session.output.is_empty());
+
+        thread::sleep(Duration::from_millis(60));
+        http.expire(&mut session);
+        assert!(session.close);
+        assert_eq!(TestResponse::parse(&session.output).status, 408);
+
+        // a body is not bound by the deadline of the header
+        let mut session = new_session();
+        session
+            .input
+            .extend(b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nab");
+        http.receive(&mut session).unwrap();
+        thread::sleep(Duration::from_millis(60));
+        http.expire(&mut session);
+        assert!(!session.close);
+    }
+}
@@ -0,0 +1,435 @@
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{http::header_map::HeaderMap, process::address::Address};
+
+/**
+ * IP network like `10.0.0.0/8`, a bare address is a network of itself.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(str: &str) -> Option<Self> {
+        let (addr, prefix) = match str.trim().split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+            None => (str.trim().parse::<IpAddr>().ok()?, None),
+        };
+
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return None;
+        }
+        return Some(Self { addr, prefix });
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        // IPv4 peer of dual stack socket comes as ::ffff:a.b.c.d
+        let ip = match ip {
+            IpAddr::V6(v6) => v6
+                .to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(*v6)),
+            ip => *ip,
+        };
+
+        return match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_match(&net.octets(), &ip.octets(), self.prefix)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_match(&net.octets(), &ip.octets(), self.prefix)
+            }
+            _ => false,
+        };
+    }
+}
+
+fn prefix_match(net: &[u8], ip: &[u8], prefix: u8) -> bool {
+    let full = (prefix / 8) as usize;
+    if net[..full] != ip[..full] {
+        return false;
+    }
+
+    let rest = prefix % 8;
+    if rest == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rest);
+    return net[full] & mask == ip[full] & mask;
+}
+
+/**
+ * Proxies whose `Forwarded` / `X-Forwarded-*` headers are believed.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<Cidr>,
+}
+
+#[allow(dead_code)]
+impl TrustedProxies {
+    pub fn new(networks: Vec<Cidr>) -> Self {
+        return Self { networks };
+    }
+
+    /**
+     * Parse list of CIDR, the first invalid one is the error.
+     */
+    pub fn parse(networks: &[String]) -> Result<Self, String> {
+        let mut res = vec![];
+        for network in networks {
+            res.push(Cidr::parse(network).ok_or_else(|| network.clone())?);
+        }
+        return Ok(Self { networks: res });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.networks.is_empty();
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        return self.networks.iter().any(|n| n.contains(ip));
+    }
+}
+
+/**
+ * Client as seen by the first trusted proxy.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Forwarded {
+    pub client: Option<Address>,
+    pub proto: Option<String>,
+    pub host: Option<String>,
+}
+
+// one hop of forwarding
+#[derive(Debug, Default)]
+struct Hop {
+    // None for "unknown" and obfuscated identifier
+    client: Option<SocketAddr>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+impl Forwarded {
+    /**
+     * Read forwarding headers when `peer` is a trusted proxy.
+     * `Forwarded` (RFC 7239) wins over `X-Forwarded-*`. The hop list is walked from the nearest one
+     * while the address is trusted, so a client cannot pretend to be someone else by sending the header.
+     * `X-Forwarded-Proto` and `X-Forwarded-Host` are not kept per hop, the value the trusted peer appended counts,
+     * earlier ones may come from the client.
+     */
+    pub fn resolve(peer: &Address, headers: &HeaderMap, trusted: &TrustedProxies) -> Option<Self> {
+        let peer_ip = peer.ip()?;
+        if !trusted.contains(&peer_ip) {
+            return None;
+        }
+
+        let values = |name| -> Vec<&str> {
+            return headers.get_all(name).collect();
+        };
+
+        let forwarded = values("forwarded");
+        if !forwarded.is_empty() {
+            let hops = parse_forwarded(&forwarded.join(","));
+            let hop = select(&hops, trusted)?;
+            return Some(Self {
+                client: hop.client.map(Address::Inet),
+                proto: hop.proto.clone(),
+                host: hop.host.clone(),
+            });
+        }
+
+        let hops: Vec<Hop> = list(&values("x-forwarded-for"))
+            .iter()
+            .map(|v| Hop {
+                client: parse_node(v),
+                ..Default::default()
+            })
+            .collect();
+        let res = Self {
+            client: select(&hops, trusted)
+                .and_then(|hop| hop.client)
+                .map(Address::Inet),
+            proto: list(&values("x-forwarded-proto"))
+                .pop()
+                .map(|p| p.to_ascii_lowercase()),
+            host: list(&values("x-forwarded-host")).pop(),
+        };
+        if res == Self::default() {
+            return None;
+        }
+        return Some(res);
+    }
+}
+
+// nearest hop whose address is not trusted, or the farthest one when all are
+fn select<'h>(hops: &'h [Hop], trusted: &TrustedProxies) -> Option<&'h Hop> {
+    let mut selected = None;
+    for hop in hops.iter().rev() {
+        selected = Some(hop);
+        match hop.client {
+            Some(addr) if trusted.contains(&addr.ip()) => continue,
+            _ => break,
+        }
+    }
+    return selected;
+}
+
+// comma separated values of every field
+fn list(values: &[&str]) -> Vec<String> {
+    return values
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+}
+
+fn parse_forwarded(value: &str) -> Vec<Hop> {
+    return split_unquoted(value, ',')
+        .iter()
+        .map(|element| {
+            let mut hop = Hop::default();
+            for pair in split_unquoted(element, ';') {
+                let (key, value) = match pair.split_once('=') {
+                    Some((k, v)) => (k.trim(), unquote(v.trim())),
+                    None => continue,
+                };
+                if key.eq_ignore_ascii_case("for") {
+                    hop.client = parse_node(&value);
+                } else if key.eq_ignore_ascii_case("proto") {
+                    hop.proto = Some(value.to_ascii_lowercase());
+                } else if key.eq_ignore_ascii_case("host") {
+                    hop.host = Some(value);
+                }
+            }
+            return hop;
+        })
+        .collect();
+}
+
+// split by separator out of quoted string
+fn split_unquoted(value: &str, separator: char) -> Vec<String> {
+    let mut res = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            res.push(current.trim().to_string());
+            current.clear();
+            continue;
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        res.push(current.trim().to_string());
+    }
+    return res;
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_string();
+    }
+
+    let mut res = String::new();
+    let mut escaped = false;
+    for c in value[1..value.len() - 1].chars() {
+        if !escaped && c == '\\' {
+            escaped = true;
+            continue;
+        }
+        escaped = false;
+        res.push(c);
+    }
+    return res;
+}
+
+// "192.0.2.1", "192.0.2.1:80", "[2001:db8::1]:80", "2001:db8::1"
+fn parse_node(node: &str) -> Option<SocketAddr> {
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+
+    let ip = node.trim_start_matches('[').trim_end_matches(']');
+    return ip.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 0));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        http::{
+            forwarded::{Cidr, Forwarded, TrustedProxies},
+            header_map::HeaderMap,
+        },
+        process::address::Address,
+    };
+
+    fn trusted() -> TrustedProxies {
+        return TrustedProxies::parse(&["10.0.0.0/8".to_string(), "::1".to_string()]).unwrap();
+    }
+
+    fn addr(str: &str) -> Address {
+        return Address::Inet(str.parse().unwrap());
+    }
+
+    #[test]
+    fn test_cidr() {
+        let net = Cidr::parse("192.168.0.0/23").unwrap();
+        assert!(net.contains(&"192.168.1.255".parse().unwrap()));
+        assert!(!net.contains(&"192.168.2.0".parse().unwrap()));
+        assert!(net.contains(&"::ffff:192.168.0.1".parse().unwrap()));
+
+        let net = Cidr::parse("2001:db8::/32").unwrap();
+        assert!(net.contains(&"2001:db8:1::1".parse().unwrap()));
+        assert!(!net.contains(&"2001:db9::1".parse().unwrap()));
+
+        assert!(
+            Cidr::parse("0.0.0.0/0")
+                .unwrap()
+                .contains(&"8.8.8.8".parse().unwrap())
+        );
+        assert_eq!(Cidr::parse("10.0.0.0/33"), None);
+        assert_eq!(Cidr::parse("example.com"), None);
+    }
+
+    #[test]
+    fn test_untrusted_peer() {
+        let headers = HeaderMap::from_iter([("X-Forwarded-For", "1.1.1.1")]);
+        assert_eq!(
+            Forwarded::resolve(&addr("8.8.8.8:1000"), &headers, &trusted()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_x_forwarded() {
+        let headers = HeaderMap::from_iter([
+            ("X-Forwarded-For", "6.6.6.6, 1.1.1.1"),
+            ("X-Forwarded-For", "10.0.0.2"),
+            ("X-Forwarded-Proto", "HTTPS"),
+            ("X-Forwarded-Host", "example.com"),
+        ]);
+
+        // spoofed 6.6.6.6 is ignored, 1.1.1.1 is added by trusted 10.0.0.2
+        assert_eq!(
+            Forwarded::resolve(&addr("10.0.0.1:1000"), &headers, &trusted()),
+            Some(Forwarded {
+                client: Some(addr("1.1.1.1:0")),
+                proto: Some("https".to_string()),
+                host: Some("example.com".to_string()),
+            })
+        );
+
+        let headers = HeaderMap::from_iter([
+            ("x-forwarded-for", "1.1.1.1"),
+            ("x-forwarded-proto", "HTTPS"),
+            ("x-forwarded-host", "example.com"),
+        ]);
+        assert_eq!(
+            Forwarded::resolve(&addr("[::1]:1000"), &headers, &trusted()),
+            Some(Forwarded {
+                client: Some(addr("1.1.1.1:0")),
+                proto: Some("https".to_string()),
+                host: Some("example.com".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_x_forwarded_multi_hop() {
+        let headers = HeaderMap::from_iter([
+            ("X-Forwarded-For", "1.1.1.1, 10.0.0.2"),
+            ("X-Forwarded-Proto", "https"),
+            ("X-Forwarded-Host", "example.com"),
+        ]);
+        assert_eq!(
+            Forwarded::resolve(&addr("10.0.0.3:1000"), &headers, &trusted()),
+            Some(Forwarded {
+                client: Some(addr("1.1.1.1:0")),
+                proto: Some("https".to_string()),
+                host: Some("example.com".to_string()),
+            })
+        );
+
+        // the client sent its own before the one of the proxy
+        let headers = HeaderMap::from_iter([
+            ("X-Forwarded-For", "1.1.1.1"),
+            ("X-Forwarded-Proto", "https"),
+            ("X-Forwarded-Host", "evil.example"),
+            ("X-Forwarded-Proto", "http"),
+            ("X-Forwarded-Host", "example.com"),
+        ]);
+        assert_eq!(
+            Forwarded::resolve(&addr("10.0.0.3:1000"), &headers, &trusted()),
+            Some(Forwarded {
+                client: Some(addr("1.1.1.1:0")),
+                proto: Some("http".to_string()),
+                host: Some("example.com".to_string()),
+            })
+        );
+        let headers = HeaderMap::from_iter([("X-Forwarded-Proto", "https, http")]);
+        assert_eq!(
+            Forwarded::resolve(&addr("10.0.0.3:1000"), &headers, &trusted()).and_then(|f| f.proto),
+            Some("http".to_string())
+        );
+
+        // scheme and host without X-Forwarded-For
+        let headers = HeaderMap::from_iter([("X-Forwarded-Proto", "https")]);
+        assert_eq!(
+            Forwarded::resolve(&addr("10.0.0.3:1000"), &headers, &trusted()),
+            Some(Forwarded {
+                client: None,
+                proto: Some("https".to_string()),
+                host: None,
+            })
+        );
+        assert_eq!(
+            Forwarded::resolve(&addr("10.0.0.3:1000"), &HeaderMap::new(), &trusted()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let headers = HeaderMap::from_iter([
+            (
+                "Forwarded",
+                r#"for=6.6.6.6, for="[2001:db8:cafe::17]:4711";proto=https;host="a.example.com""#,
+            ),
+            ("X-Forwarded-For", "9.9.9.9"),
+        ]);
+
+        assert_eq!(
+            Forwarded::resolve(&addr("10.1.1.1:1000"), &headers, &trusted()),
+            Some(Forwarded {
+                client: Some(addr("[2001:db8:cafe::17]:4711")),
+                proto: Some("https".to_string()),
+                host: Some("a.example.com".to_string()),
+            })
+        );
+
+        let headers = HeaderMap::from_iter([("Forwarded", "for=_hidden;proto=http")]);
+        assert_eq!(
+            Forwarded::resolve(&addr("10.1.1.1:1000"), &headers, &trusted()),
+            Some(Forwarded {
+                client: None,
+                proto: Some("http".to_string()),
+                host: None,
+            })
+        );
+    }
+}
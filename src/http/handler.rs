@@ -1,5 +1,5 @@
 use crate::http::{request::HttpRequest, response::HttpResponse};
 
-pub trait Handler {
+pub trait Handler: Send + Sync {
     fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse);
 }
@@ -1,218 +1,63 @@
-use std::fmt::Write;
-use std::rc::Rc;
-use std::time::SystemTime;
+use std::borrow::Cow;
 
-use crate::http::value::WeightedValue;
-use crate::util::date::Date;
+use crate::http::typed::{self, TypedHeader};
 
-pub trait ToString: std::fmt::Debug {
-    fn to_string(&self) -> Rc<String>;
-}
-
-#[allow(dead_code)]
-pub enum HttpHeaderValue {
-    String(String),
-    Str(&'static str),
-}
-
-impl HttpHeaderValue {
-    pub fn to_value(&self) -> Rc<dyn ToString> {
-        match self {
-            HttpHeaderValue::String(string) => Rc::new(HeaderValueString {
-                string: Rc::new(string.clone()),
-            }),
-            HttpHeaderValue::Str(str) => Rc::new(HeaderValueStr { str: str }),
-        }
-    }
-}
-
-#[derive(Debug)]
-struct HeaderValueStr {
-    str: &'static str,
-}
-
-impl ToString for HeaderValueStr {
-    fn to_string(&self) -> Rc<String> {
-        Rc::new(self.str.to_string())
-    }
-}
-
-#[derive(Debug)]
-struct HeaderValueString {
-    string: Rc<String>,
-}
-
-impl ToString for HeaderValueString {
-    fn to_string(&self) -> Rc<String> {
-        self.string.clone()
-    }
-}
-
-#[allow(dead_code)]
-#[derive(Debug)]
-struct HeaderValueWeighted {
-    weighted: Vec<WeightedValue>,
-}
-
-impl ToString for HeaderValueWeighted {
-    fn to_string(&self) -> Rc<String> {
-        let mut val = self.weighted.iter().fold(String::new(), |mut s, w| {
-            s.push_str(&w.value());
-            if let Some(w) = w.weight() {
-                let _ = write!(s, ";q={:.2}", w).map_err(|e| e.to_string());
-            }
-            s
-        });
-        val.remove(val.len() - 1);
-        return Rc::new(val);
-    }
-}
-
-#[derive(Debug)]
-struct HeaderValueTime {
-    time: Date,
-}
-
-impl HeaderValueTime {
-    fn time_to_header_string(&self) -> String {
-        self.time.to_rfc1123()
-    }
-
-    pub fn from_system_time(time: SystemTime) -> Self {
-        Self {
-            time: Date::from(time),
-        }
-    }
-}
-
-impl ToString for HeaderValueTime {
-    fn to_string(&self) -> Rc<String> {
-        Rc::new(self.time_to_header_string())
-    }
-}
-
-#[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HttpHeader {
-    key_str: Option<&'static str>,
-    key_string: Option<Rc<String>>,
-    value: Rc<dyn ToString>,
+    name: Cow<'static, str>,
+    value: Cow<'static, str>,
 }
 
 impl HttpHeader {
-    pub fn key_str(&self) -> Option<&'static str> {
-        self.key_str
+    /**
+     * Field of a typed header with its encoded value.
+     */
+    pub fn from_typed<H: TypedHeader>(header: &H) -> Self {
+        return Self {
+            name: Cow::Borrowed(H::NAME),
+            value: Cow::Owned(header.encode()),
+        };
     }
 
-    pub fn key_string(&self) -> Option<Rc<String>> {
-        self.key_string.clone()
+    pub fn name(&self) -> &Cow<'static, str> {
+        &self.name
     }
 
-    pub fn value(&self) -> &Rc<dyn ToString> {
+    pub fn value(&self) -> &Cow<'static, str> {
         &self.value
     }
 }
 
-fn from_str_key(key: &'static str, value: Rc<dyn ToString>) -> HttpHeader {
-    HttpHeader {
-        key_str: Some(key),
-        key_string: None,
-        value: value,
-    }
-}
-
-#[allow(dead_code)]
-fn from_string_key(key: String, value: Rc<dyn ToString>) -> HttpHeader {
-    HttpHeader {
-        key_str: None,
-        key_string: Some(Rc::new(key)),
-        value: value,
-    }
-}
-
 // common
 pub fn date(time: std::time::SystemTime) -> HttpHeader {
-    return from_str_key("Date", Rc::new(HeaderValueTime::from_system_time(time)));
-}
-
-// entity
-#[allow(dead_code)]
-pub fn allow(values: Vec<WeightedValue>) -> HttpHeader {
-    from_str_key("Allow", Rc::new(HeaderValueWeighted { weighted: values }))
-}
-
-#[allow(dead_code)]
-pub fn content_encoding(value: HttpHeaderValue) -> HttpHeader {
-    from_str_key("Content-Encoding", value.to_value())
+    return HttpHeader::from_typed(&typed::Date(time));
 }
 
-#[allow(dead_code)]
-pub fn content_length(value: usize) -> HttpHeader {
-    from_str_key(
-        "Content-Length",
-        Rc::new(HeaderValueString {
-            string: Rc::new(value.to_string()),
-        }),
-    )
+pub fn server(value: &'static str) -> HttpHeader {
+    return HttpHeader {
+        name: Cow::Borrowed("Server"),
+        value: Cow::Borrowed(value),
+    };
 }
 
 // entity
-#[allow(dead_code)]
-pub fn content_type(value: HttpHeaderValue) -> HttpHeader {
-    from_str_key("Content-Type", value.to_value())
-}
-
-#[allow(dead_code)]
-pub fn expires(time: std::time::SystemTime) -> HttpHeader {
-    from_str_key("Expires", Rc::new(HeaderValueTime::from_system_time(time)))
-}
-
-#[allow(dead_code)]
-pub fn last_modified(time: std::time::SystemTime) -> HttpHeader {
-    from_str_key(
-        "Last-Modified",
-        Rc::new(HeaderValueTime::from_system_time(time)),
-    )
-}
-
-#[allow(dead_code)]
-pub fn header(key: &'static str, value: HttpHeaderValue) -> HttpHeader {
-    from_str_key(key, value.to_value())
-}
-
-#[allow(dead_code)]
-pub fn location(value: HttpHeaderValue) -> HttpHeader {
-    from_str_key("Location", value.to_value())
-}
-
-#[allow(dead_code)]
-pub fn server(value: HttpHeaderValue) -> HttpHeader {
-    from_str_key("Server", value.to_value())
-}
-
-#[allow(dead_code)]
-pub fn www_authenticate(value: HttpHeaderValue) -> HttpHeader {
-    from_str_key("WWW-Authenticate", value.to_value())
+pub fn content_length(value: usize) -> HttpHeader {
+    return HttpHeader::from_typed(&typed::ContentLength(value as u64));
 }
 
 #[cfg(test)]
 mod test {
     use std::time::SystemTime;
 
-    use crate::http::header::{HeaderValueTime, ToString};
+    use crate::http::header::date;
 
     #[test]
     fn test_time_to_header_string() {
         assert_eq!(
-            HeaderValueTime::from_system_time(SystemTime::UNIX_EPOCH)
-                .to_string()
-                .as_ref(),
+            date(SystemTime::UNIX_EPOCH).value(),
             "Thu, 01 Jan 1970 00:00:00 GMT"
         );
 
-        println!(
-            "{}",
-            HeaderValueTime::from_system_time(SystemTime::now()).to_string()
-        )
+        println!("{}", date(SystemTime::now()).value())
     }
 }
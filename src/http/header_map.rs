@@ -0,0 +1,213 @@
+use std::{borrow::Cow, str::FromStr};
+
+use crate::http::typed::{ContentLength, TypedHeader};
+
+/**
+ * Header fields of a request or response, in the order they were added.
+ * Names compare case-insensitively and keep the casing they were added with, a name may repeat.
+ * A request borrows names and values from its head, a response owns what handlers set.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap<'a> {
+    fields: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+#[allow(dead_code)]
+impl<'a> HeaderMap<'a> {
+    pub fn new() -> Self {
+        return Self { fields: vec![] };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.fields.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.fields.is_empty();
+    }
+
+    /**
+     * First value of `name`.
+     */
+    pub fn get(&self, name: &str) -> Option<&str> {
+        return self.get_all(name).next();
+    }
+
+    /**
+     * Every value of `name`, in order.
+     */
+    pub fn get_all<'b>(&'b self, name: &str) -> impl Iterator<Item = &'b str> {
+        return self
+            .fields
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_ref());
+    }
+
+    /**
+     * Elements of comma separated list `name` over all of its values, trimmed, empty ones skipped.
+     */
+    pub fn get_list<'b>(&'b self, name: &str) -> impl Iterator<Item = &'b str> {
+        return self
+            .get_all(name)
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty());
+    }
+
+    /**
+     * First value of `name` parsed as `T`, `None` when missing or invalid.
+     */
+    pub fn get_parsed<T: FromStr>(&self, name: &str) -> Option<T> {
+        return self.get(name)?.trim().parse().ok();
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        return self.get(name).is_some();
+    }
+
+    /**
+     * Typed value of `H`, from every field of the name when it is a list. `None` when missing or invalid.
+     */
+    pub fn typed<H: TypedHeader>(&self) -> Option<H> {
+        if H::LIST {
+            let values: Vec<&str> = self.get_all(H::NAME).collect();
+            if values.is_empty() {
+                return None;
+            }
+            return H::parse(&values.join(", "));
+        }
+        return H::parse(self.get(H::NAME)?);
+    }
+
+    /**
+     * Replace the fields of `H` with its encoded value.
+     */
+    pub fn set_typed<H: TypedHeader>(&mut self, header: &H) {
+        self.insert(H::NAME, header.encode());
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        return self.typed::<ContentLength>().map(|c| c.0);
+    }
+
+    /**
+     * Whether list `name` has `token`, e.g. `close` in `Connection`.
+     */
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        return self.get_list(name).any(|v| v.eq_ignore_ascii_case(token));
+    }
+
+    /**
+     * Add a field after the others, keeping values `name` already has.
+     */
+    pub fn append(&mut self, name: impl Into<Cow<'a, str>>, value: impl Into<Cow<'a, str>>) {
+        self.fields.push((name.into(), value.into()));
+    }
+
+    /**
+     * Set the only value of `name`. The first field of the name keeps its place, the rest are removed.
+     */
+    pub fn insert(&mut self, name: impl Into<Cow<'a, str>>, value: impl Into<Cow<'a, str>>) {
+        let name = name.into();
+        let mut value = Some(value.into());
+        self.fields.retain_mut(|(n, v)| {
+            if !n.eq_ignore_ascii_case(&name) {
+                return true;
+            }
+            return match value.take() {
+                Some(value) => {
+                    *v = value;
+                    true
+                }
+                None => false,
+            };
+        });
+        if let Some(value) = value {
+            self.fields.push((name, value));
+        }
+    }
+
+    /**
+     * Remove every field of `name`, returning whether there was one.
+     */
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.fields.len();
+        self.fields.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        return self.fields.len() != len;
+    }
+
+    /**
+     * Fields in order, a repeated name once for each value.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        return self.fields.iter().map(|(n, v)| (n.as_ref(), v.as_ref()));
+    }
+}
+
+impl<'a, N, V> FromIterator<(N, V)> for HeaderMap<'a>
+where
+    N: Into<Cow<'a, str>>,
+    V: Into<Cow<'a, str>>,
+{
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        let mut map = HeaderMap::new();
+        for (name, value) in iter {
+            map.append(name, value);
+        }
+        return map;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::http::header_map::HeaderMap;
+
+    #[test]
+    fn test_lookup() {
+        let map = HeaderMap::from_iter([
+            ("Content-Type", "text/plain"),
+            ("accept", "a/b"),
+            ("ACCEPT", "c/d, e/f"),
+            ("Content-Length", " 12"),
+        ]);
+
+        assert_eq!(map.get("content-type"), Some("text/plain"));
+        assert_eq!(map.get("Accept"), Some("a/b"));
+        assert_eq!(
+            map.get_all("accept").collect::<Vec<_>>(),
+            vec!["a/b", "c/d, e/f"]
+        );
+        assert_eq!(
+            map.get_list("accept").collect::<Vec<_>>(),
+            vec!["a/b", "c/d", "e/f"]
+        );
+        assert!(map.has_token("Accept", "E/F"));
+        assert_eq!(map.content_length(), Some(12));
+        assert_eq!(map.get_parsed::<u32>("content-type"), None);
+        assert_eq!(map.get("Host"), None);
+    }
+
+    #[test]
+    fn test_insert_and_append() {
+        let mut map = HeaderMap::new();
+        map.append("Set-Cookie", "a=1");
+        map.append("Server", "server_rs");
+        map.append("set-cookie", "b=2");
+        assert_eq!(map.len(), 3);
+
+        // replaced in the place of the first field
+        map.insert("SET-COOKIE", "c=3".to_string());
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![("Set-Cookie", "c=3"), ("Server", "server_rs")]
+        );
+
+        map.insert("Date", "now");
+        assert_eq!(map.iter().last(), Some(("Date", "now")));
+
+        assert!(map.remove("server"));
+        assert!(!map.remove("server"));
+        assert_eq!(map.len(), 2);
+    }
+}
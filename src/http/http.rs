@@ -1,23 +1,45 @@
 use std::{
+    cell::Cell,
     collections::HashMap,
     io::{BufRead, BufReader, Read, Write},
-    net::{SocketAddr, TcpStream},
-    time::{Duration, SystemTime},
+    sync::atomic::{AtomicU32, Ordering},
+    time::SystemTime,
 };
 
 use crate::{
     http::{
+        error_page::ErrorPages,
+        forwarded::TrustedProxies,
         handler::Handler,
-        header::{HttpHeaderValue, content_type, date, server},
+        header::{date, server},
+        header_map::HeaderMap,
+        limit::{BodyLimitReader, Limit, Limits},
+        parser::{self, BodyLength, ChunkedReader, HeadError, RequestHead},
         request::HttpRequest,
         response::{HeaderSetter, HttpResponse},
+        timeout::{TimedReader, Timeouts},
+        typed::{Accept, TypedHeader},
         value::{Error, HttpMethod, HttpResponseCode, HttpVersion},
     },
-    process::{self, Process},
+    process::{
+        self, Process,
+        address::Address,
+        event::EventProcess,
+        stream::{SharedStream, Stream},
+    },
+    util::panic::catch,
 };
 
 pub struct Http1<T: Handler> {
     max_header_length: usize,
+    limits: Limits,
+    timeouts: Timeouts,
+    trusted_proxies: TrustedProxies,
+    error_pages: ErrorPages,
+    // handler panics before the worker is recycled, 0 never recycles
+    panic_budget: u32,
+    // handler panics of this worker
+    panics: AtomicU32,
     handler: T,
 }
 
@@ -27,44 +49,34 @@ where
 {
     fn process(
         &self,
-        stream: TcpStream,
-        client_addr: &std::net::SocketAddr,
+        stream: Box<dyn Stream>,
+        client_addr: &Address,
     ) -> Result<(usize, usize), process::Error> {
-        let _ = stream.set_read_timeout(Some(Duration::from_millis(100)));
-        let _ = stream.set_write_timeout(Some(Duration::from_millis(100)));
-
-        log::trace!("Read timeout: {:?}", stream.read_timeout());
-        log::trace!("Write timeout: {:?}", stream.write_timeout());
-
-        let mut reader: BufReader<Box<dyn Read>> = BufReader::new(Box::new(&stream));
-
-        let (header_readed, headers) =
-            self.read_header(client_addr, &mut reader).map_err(|err| {
-                self.error_response_for_invalid_request(&stream);
-                process::Error::IoFail(format!("Read header failed: ({})", err))
-            })?;
-
-        let mut request = self
-            .init_request(client_addr, &headers, Box::new(&stream))
-            .map_err(|e| {
-                self.error_response_for_invalid_request(&stream);
-                process::Error::ParseFail(e.to_string())
-            })?;
-        let mut response = HttpResponse::from_request(&request, Box::new(&stream));
-        response.set_header(&server(HttpHeaderValue::Str("server_rs")));
-
-        self.handler.handle(&mut request, &mut response);
-
-        response
-            .flush()
-            .map_err(|e| process::Error::IoFail(e.to_string()))?;
-
-        Ok((header_readed, response.written()))
+        let stream = SharedStream::new(stream);
+        self.init_stream(&stream);
+
+        let mut reader = BufReader::new(TimedReader::new(stream.clone(), &self.timeouts));
+        let timed_out = reader.get_ref().timed_out();
+
+        return parser::with_request_head(|head| {
+            self.read_header(client_addr, &mut reader, head)
+                .map_err(|err| {
+                    self.error_response_for_header(&stream, &err, timed_out.get());
+                    process::Error::IoFail(format!("Read header failed: ({})", err))
+                })?;
+            reader.get_mut().start_body();
+
+            return self.respond(&stream, client_addr, head, Box::new(reader), &timed_out);
+        });
     }
 
     fn name(&self) -> String {
         return "http".to_string();
     }
+
+    fn event(&self) -> Option<&dyn EventProcess> {
+        return Some(self);
+    }
 }
 
 impl<T> Http1<T>
@@ -74,63 +86,237 @@ where
     pub fn new(max_header_length: usize, handler: T) -> Self {
         return Http1 {
             max_header_length,
+            limits: Limits::default(),
+            timeouts: Timeouts::default(),
+            trusted_proxies: TrustedProxies::default(),
+            error_pages: ErrorPages::default(),
+            panic_budget: 0,
+            panics: AtomicU32::new(0),
             handler,
         };
     }
 
-    fn read_header<'a>(
-        &self,
-        client_addr: &SocketAddr,
-        reader: &mut BufReader<Box<dyn Read + 'a>>,
-    ) -> Result<(usize, Vec<String>), Error> {
-        let mut res = vec![];
-        let mut readed = 0;
-        loop {
-            let mut buf = String::new();
-            let result = reader.read_line(&mut buf);
-            if let Err(err) = result {
-                return Err(Error::ReadFail(format!("{}", err)));
-            }
-            readed += result.unwrap();
+    /**
+     * Read and write limits of a request, a client too slow for them gets 408.
+     * Streaming responses rely on the write timeout to drop a client that stopped reading.
+     */
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        return self;
+    }
 
-            if readed > self.max_header_length {
-                return Err(Error::BadRequest(
-                    client_addr.clone(),
-                    "header size limit exceed",
-                ));
-            }
+    pub(crate) fn timeouts(&self) -> &Timeouts {
+        return &self.timeouts;
+    }
 
-            while buf
-                .chars()
-                .nth(0)
-                .map(|v| v.is_whitespace())
-                .unwrap_or(false)
-            {
-                buf.remove(0);
-            }
+    /**
+     * Request line, header field and body size limits. The whole header stays `max_header_length`.
+     */
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        return self;
+    }
 
-            // head end
-            if buf.is_empty() {
-                break;
-            }
+    pub(crate) fn limits(&self) -> &Limits {
+        return &self.limits;
+    }
 
-            if !buf.ends_with("\r\n") {
-                return Err(Error::ParseFail(format!("Invalid heaader {}", buf)));
-            }
+    /**
+     * Peers allowed to tell the client address, scheme and host by `Forwarded` or `X-Forwarded-*`.
+     */
+    pub fn with_trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        return self;
+    }
 
-            buf.remove(buf.len() - 1); // delete \n
-            buf.remove(buf.len() - 1); // delete \r
+    pub(crate) fn trusted_proxies(&self) -> &TrustedProxies {
+        return &self.trusted_proxies;
+    }
 
-            log::trace!("<< {}", buf);
+    /**
+     * Documents of error responses, of the server and of handlers calling `HttpResponse::send_error`.
+     */
+    pub fn with_error_pages(mut self, error_pages: ErrorPages) -> Self {
+        self.error_pages = error_pages;
+        return self;
+    }
 
-            res.push(buf);
+    pub(crate) fn error_pages(&self) -> &ErrorPages {
+        return &self.error_pages;
+    }
+
+    /**
+     * Handler panics a worker survives. The one reaching `panic_budget` recycles the worker, 0 never does.
+     */
+    pub fn with_panic_budget(mut self, panic_budget: u32) -> Self {
+        self.panic_budget = panic_budget;
+        return self;
+    }
+
+    /**
+     * Run the handler, or answer 403 when the listener requires a client certificate the request lacks.
+     * A handler panic is logged with the request and answered 500, unless the response is streaming already,
+     * then `false` is returned as the response can not be finished.
+     */
+    pub(crate) fn handle(&self, request: &mut HttpRequest, response: &mut HttpResponse) -> bool {
+        if !request.permitted() {
+            forbidden(response);
+            return true;
+        }
+        let Err(panic) = catch(|| self.handler.handle(request, response)) else {
+            return true;
+        };
+
+        log::error!(
+            target: "Http1",
+            "handler panicked at {}: {}, request: {} {} {}\n{}",
+            panic.location,
+            panic.message,
+            request.method(),
+            request.path(),
+            request.version(),
+            panic.backtrace
+        );
+        let panics = self.panics.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.panic_budget > 0 && panics >= self.panic_budget {
+            log::error!(target: "Http1", "{panics} handler panics, recycling the worker");
+            process::recycle();
         }
 
-        if readed == 0 {
-            return Err(Error::ReadFail(format!("EOF")));
+        if response.is_streaming() {
+            return false;
         }
+        // nothing the handler set is sent
+        *response.header_mut() = HeaderMap::new();
+        response.set_header(&server("server_rs"));
+        response.send_error(HttpResponseCode::InternalServerError, "");
+        return true;
+    }
+
+    pub(crate) fn max_header_length(&self) -> usize {
+        return self.max_header_length;
+    }
 
-        return Ok((readed, res));
+    pub(crate) fn init_stream(&self, stream: &dyn Stream) {
+        let _ = stream.set_read_timeout(Some(self.timeouts.header_read));
+        let _ = stream.set_write_timeout(Some(self.timeouts.write));
+
+        log::trace!("Timeouts: {:?}", self.timeouts);
+    }
+
+    /**
+     * Handle request of already read header.
+     * `reader` continues right after the header, so request body can be read from it.
+     * `timed_out` is set by the reader when the body did not arrive in time, the response becomes 408 then.
+     * A chunked body is decoded for the handler, a malformed one becomes 400.
+     * A body over the limit is refused by its Content-Length before the handler, or fails the reader and becomes 413.
+     */
+    pub(crate) fn respond<'a>(
+        &self,
+        stream: &SharedStream,
+        client_addr: &'a Address,
+        head: &'a RequestHead,
+        reader: Box<dyn BufRead + 'a>,
+        timed_out: &Cell<bool>,
+    ) -> Result<(usize, usize), process::Error> {
+        let mut body_invalid = None;
+        let reader: Box<dyn Read + 'a> =
+            if let Ok(BodyLength::Chunked) = parser::body_length(head.fields()) {
+                let reader = ChunkedReader::new(reader);
+                body_invalid = Some(reader.invalid());
+                Box::new(reader)
+            } else {
+                Box::new(reader)
+            };
+
+        let mut body_exceeded = None;
+        let reader: Box<dyn Read + 'a> = if self.limits.body > 0 {
+            if let Ok(BodyLength::Length(length)) = parser::body_length(head.fields())
+                && length > self.limits.body
+            {
+                self.error_response_for_limit(stream, client_addr, Limit::Body, accept(head));
+                return Err(process::Error::ParseFail(Limit::Body.to_string()));
+            }
+            let reader = BodyLimitReader::new(reader, self.limits.body);
+            body_exceeded = Some(reader.exceeded());
+            Box::new(reader)
+        } else {
+            reader
+        };
+
+        let mut request = self
+            .init_request(client_addr, head, reader)
+            .map_err(|e| {
+                self.error_response_for_invalid_request(stream, accept(head));
+                process::Error::ParseFail(e.to_string())
+            })?
+            .with_local_addr(stream.local_addr())
+            .with_tls(stream.tls_info())
+            .with_proxy_header(stream.proxy_header())
+            .with_trusted_proxies(&self.trusted_proxies);
+        let mut response = HttpResponse::from_request(&request, Box::new(stream.clone()))
+            .with_sendfile(stream.clone())
+            .with_error_pages(&self.error_pages);
+        response.set_header(&server("server_rs"));
+
+        if !self.handle(&mut request, &mut response) {
+            return Err(process::Error::IoFail("handler panicked".to_string()));
+        }
+
+        if timed_out.get() && !response.is_streaming() {
+            drop(response);
+            self.error_response(
+                stream,
+                HttpResponseCode::RequestTimeout,
+                "Request timeout",
+                request.header().typed(),
+            );
+            return Err(process::Error::IoFail("Read body timed out".to_string()));
+        }
+        if body_exceeded.is_some_and(|exceeded| exceeded.get()) && !response.is_streaming() {
+            drop(response);
+            self.error_response_for_limit(
+                stream,
+                client_addr,
+                Limit::Body,
+                request.header().typed(),
+            );
+            return Err(process::Error::ParseFail(Limit::Body.to_string()));
+        }
+        if body_invalid.is_some_and(|invalid| invalid.get()) && !response.is_streaming() {
+            drop(response);
+            self.error_response_for_invalid_request(stream, request.header().typed());
+            return Err(process::Error::ParseFail(
+                "invalid chunked body".to_string(),
+            ));
+        }
+
+        response
+            .flush()
+            .map_err(|e| process::Error::IoFail(e.to_string()))?;
+
+        Ok((head.len(), response.written()))
+    }
+
+    /**
+     * Read the request head into `head`, the body stays in `reader`.
+     */
+    pub(crate) fn read_header(
+        &self,
+        client_addr: &Address,
+        reader: &mut impl BufRead,
+        head: &mut RequestHead,
+    ) -> Result<(), Error> {
+        return head
+            .read(reader, &self.limits, self.max_header_length)
+            .map_err(|err| match err {
+                HeadError::Read(e) => Error::ReadFail(e.to_string()),
+                HeadError::Eof => Error::ReadFail("EOF".to_string()),
+                HeadError::Incomplete => Error::BadRequest(client_addr.clone(), "incomplete head"),
+                HeadError::TooLarge(limit) => Error::TooLarge(client_addr.clone(), limit),
+                HeadError::Invalid(msg) => Error::BadRequest(client_addr.clone(), msg),
+                HeadError::Unsupported(msg) => Error::NotImplemented(client_addr.clone(), msg),
+            });
     }
 
     /**
@@ -138,68 +324,116 @@ where
      */
     fn init_request<'a>(
         &self,
-        client_addr: &'a std::net::SocketAddr,
-        header: &'a Vec<String>,
+        client_addr: &'a Address,
+        head: &'a RequestHead,
         reader: Box<dyn Read + 'a>,
     ) -> Result<HttpRequest<'a>, Error> {
-        let buf = &header[0];
-
-        let mut req_line = buf.split(" ");
-
-        let method = req_line
-            .next()
-            .ok_or_else(|| Error::ParseFail(format!("invalid request line: {}", buf)))?;
-        let path_query = req_line
-            .next()
-            .ok_or_else(|| Error::ParseFail(format!("invalid request line: {}", buf)))?;
+        if head.is_empty() {
+            return Err(Error::ParseFail("no request line".to_string()));
+        }
 
-        // 1.1 구현 후 반영
-        let version = HttpVersion::default();
+        let version = HttpVersion::parse(head.version()).unwrap_or_default();
 
-        let (path, param) = parse_url(path_query);
+        let (path, param) = parse_url(head.target());
 
         return Ok(HttpRequest::new(
             client_addr,
-            HttpMethod::parse(method),
+            HttpMethod::parse(head.method()),
             version,
             path,
-            self.init_header(&header),
+            self.init_header(head),
             param,
             reader,
         ));
     }
 
-    fn init_header<'a>(&self, reader: &'a Vec<String>) -> HashMap<&'a str, Vec<&'a str>> {
-        let mut header_map: HashMap<&str, Vec<&str>> = HashMap::new();
-        for i in 1..reader.len() {
-            let buf = &reader[i];
-
-            let div_idx = match buf.find(':') {
-                Some(idx) => idx,
-                None => continue,
-            };
+    fn init_header<'a>(&self, head: &'a RequestHead) -> HeaderMap<'a> {
+        return head.fields().collect();
+    }
 
-            let (key, value) = buf.split_at(div_idx);
+    pub(crate) fn error_response_for_invalid_request(
+        &self,
+        stream: &SharedStream,
+        accept: Option<Accept>,
+    ) {
+        self.error_response(
+            stream,
+            HttpResponseCode::BadRequest,
+            "Invalid request",
+            accept,
+        );
+    }
 
-            put_data_to_hashmap(&mut header_map, key.trim(), value[1..].trim());
+    /**
+     * 408 when the header did not arrive in time, the status of the limit when it was too large,
+     * 400 for any other failure.
+     */
+    pub(crate) fn error_response_for_header(
+        &self,
+        stream: &SharedStream,
+        err: &Error,
+        timed_out: bool,
+    ) {
+        if timed_out {
+            self.error_response(
+                stream,
+                HttpResponseCode::RequestTimeout,
+                "Request timeout",
+                None,
+            );
+        } else if let Error::TooLarge(client_addr, limit) = err {
+            self.error_response_for_limit(stream, client_addr, *limit, None);
+        } else if let Error::NotImplemented(client_addr, msg) = err {
+            log::warn!(target: "Http1", "{client_addr} rejected with 501: {msg}");
+            self.error_response(stream, HttpResponseCode::NotImplemented, msg, None);
+        } else {
+            self.error_response_for_invalid_request(stream, None);
         }
-
-        return header_map;
     }
 
-    fn error_response_for_invalid_request(&self, stream: &TcpStream) {
-        let mut response = HttpResponse::new(HttpVersion::default(), Box::new(stream));
+    pub(crate) fn error_response_for_limit(
+        &self,
+        stream: &SharedStream,
+        client_addr: &Address,
+        limit: Limit,
+        accept: Option<Accept>,
+    ) {
+        let code = limit.response_code();
+        log::warn!(target: "Http1", "{client_addr} rejected with {}: {limit}", code.code());
+        self.error_response(stream, code, &limit.to_string(), accept);
+    }
 
-        response.set_response_code(HttpResponseCode::BadRequest);
-        response.set_header(&server(HttpHeaderValue::Str("server_rs")));
-        response.set_header(&content_type(HttpHeaderValue::Str("text/plain")));
+    pub(crate) fn error_response(
+        &self,
+        stream: &SharedStream,
+        code: HttpResponseCode,
+        message: &str,
+        accept: Option<Accept>,
+    ) {
+        let mut response = HttpResponse::new(HttpVersion::default(), Box::new(stream.clone()))
+            .with_error_pages(&self.error_pages)
+            .with_accept(accept);
+
+        response.set_header(&server("server_rs"));
         response.set_header(&date(SystemTime::now()));
-        let _ = response.write("Invalid request".as_bytes());
+        response.send_error(code, message);
         let _ = response.flush();
     }
 }
 
-fn parse_url(query: &str) -> (String, HashMap<&str, Vec<&str>>) {
+/**
+ * Answer of request without client certificate required by the listener.
+ */
+fn forbidden(response: &mut HttpResponse) {
+    response.send_error(HttpResponseCode::Forbidden, "Client certificate required");
+}
+
+// first `Accept` of a head not made into a request
+fn accept(head: &RequestHead) -> Option<Accept> {
+    return Accept::parse(head.field("accept")?);
+}
+
+pub(crate) fn parse_url(query: &str) -> (String, HashMap<&str, Vec<&str>>) {
     let path_param: Vec<&str> = query.split("?").collect();
 
     if path_param.len() < 2 {
@@ -228,7 +462,443 @@ fn put_data_to_hashmap<'a>(map: &mut HashMap<&'a str, Vec<&'a str>>, key: &'a st
 
 #[cfg(test)]
 mod test {
-    use crate::http::http::parse_url;
+    use std::{
+        io::{Cursor, Write},
+        time::Duration,
+    };
+
+    use crate::{
+        http::{
+            body::Body,
+            error_page::ErrorPages,
+            forwarded::TrustedProxies,
+            handler::Handler,
+            http::{Http1, parse_url},
+            limit::Limits,
+            request::HttpRequest,
+            response::HttpResponse,
+            test_client::{TestClient, TestRequest, TestResponse},
+            timeout::Timeouts,
+            value::{HttpMethod, HttpResponseCode},
+        },
+        process,
+    };
+
+    struct EchoHandler;
+
+    impl Handler for EchoHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            let length = req.header().content_length().unwrap_or(0) as usize;
+            let mut body = vec![0; length];
+            req.reader().read_exact(&mut body).unwrap();
+
+            if req.path() == "/missing" {
+                res.set_response_code(HttpResponseCode::NotFound);
+            }
+            let _ = write!(
+                res,
+                "{} {} {} ",
+                req.method(),
+                req.path(),
+                req.remote_addr()
+            );
+            let _ = res.write(&body);
+        }
+    }
+
+    fn client() -> TestClient<Http1<EchoHandler>> {
+        return TestClient::new(Http1::new(8192, EchoHandler));
+    }
+
+    #[test]
+    fn test_respond() {
+        let response = client().send(TestRequest::get("/hello?a=b"));
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("server"), Some("server_rs"));
+        assert_eq!(response.text(), "GET /hello 127.0.0.1:50000 ");
+
+        let response = client().send(TestRequest::post("/missing").body("data"));
+        assert_eq!(response.status, 404);
+        assert_eq!(response.text(), "POST /missing 127.0.0.1:50000 data");
+    }
+
+    #[test]
+    fn test_head() {
+        let response = client().send(TestRequest::head("/"));
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-length"), Some("23"));
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let response = client().send_raw(b"GET / HTTP/1.1\nHost: a\n\n");
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn test_smuggling() {
+        for raw in [
+            &b"POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\nabc"[..],
+            b"POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd",
+            b"POST / HTTP/1.1\r\nContent-Length : 3\r\n\r\nabc",
+            b"GET / HTTP/1.1\r\nHost: a\r\n folded\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: a\n\r\n",
+            b"GET /a\"b HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/1.1\r\nno colon\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding:\r\n\r\n0\r\n\r\n",
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: ,\r\n\r\n0\r\n\r\n",
+        ] {
+            let response = client().send_raw(raw);
+            assert_eq!(response.status, 400, "{}", String::from_utf8_lossy(raw));
+        }
+
+        // a body of these codings can not be read
+        for raw in [
+            &b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n"[..],
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked, chunked\r\n\r\n0\r\n\r\n",
+        ] {
+            let response = client().send_raw(raw);
+            assert_eq!(response.status, 501, "{}", String::from_utf8_lossy(raw));
+        }
+
+        // leading empty line of RFC 9112 2.2
+        let response = client().send_raw(b"\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert_eq!(response.status, 200);
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let client = TestClient::new(Http1::new(8192, EchoHandler).with_timeouts(Timeouts {
+            header_read: Duration::from_millis(50),
+            ..Timeouts::default()
+        }));
+
+        // rest of the header never comes
+        let response = client.send_raw(b"GET / HTTP/1.1\r\nHost: a\r\n");
+        assert_eq!(response.status, 408);
+    }
+
+    #[test]
+    fn test_limits() {
+        let client = TestClient::new(Http1::new(8192, EchoHandler).with_limits(Limits {
+            request_line: 64,
+            header_count: 2,
+            header_field: 32,
+            body: 8,
+        }));
+
+        let response = client.send(TestRequest::get(&format!("/{}", "a".repeat(64))));
+        assert_eq!(response.status, 414);
+        assert_eq!(response.text(), "request line too long");
+
+        let response = client.send(TestRequest::get("/").header("X-Long", &"a".repeat(32)));
+        assert_eq!(response.status, 431);
+
+        let request = TestRequest::get("/")
+            .header("A", "1")
+            .header("B", "2")
+            .header("C", "3");
+        assert_eq!(client.send(request).status, 431);
+
+        let response = client.send(TestRequest::post("/").body("123456789"));
+        assert_eq!(response.status, 413);
+        let response = client.send(TestRequest::post("/").body("12345678"));
+        assert_eq!(response.status, 200);
+
+        // the whole header still counts
+        let client = TestClient::new(Http1::new(64, EchoHandler));
+        let request = TestRequest::get("/").header("X-Long", &"a".repeat(64));
+        assert_eq!(client.send(request).status, 431);
+    }
+
+    struct ForwardedHandler;
+
+    impl Handler for ForwardedHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            let _ = write!(
+                res,
+                "{} {}://{}",
+                req.client_addr(),
+                req.scheme(),
+                req.host().unwrap_or("-")
+            );
+        }
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let request = || {
+            return TestRequest::get("/")
+                .header("X-Forwarded-For", "1.1.1.1")
+                .header("X-Forwarded-Proto", "https")
+                .header("X-Forwarded-Host", "example.com");
+        };
+        let trusted = TrustedProxies::parse(&["127.0.0.0/8".to_string()]).unwrap();
+
+        let direct = TestClient::new(Http1::new(8192, ForwardedHandler));
+        assert_eq!(
+            direct.send(request()).text(),
+            "127.0.0.1:50000 http://localhost"
+        );
+
+        let proxied =
+            TestClient::new(Http1::new(8192, ForwardedHandler).with_trusted_proxies(trusted));
+        assert_eq!(
+            proxied.send(request()).text(),
+            "1.1.1.1:0 https://example.com"
+        );
+    }
+
+    struct CookieHandler;
+
+    impl Handler for CookieHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            for cookie in req.header().get_list("cookie") {
+                res.header_mut()
+                    .append("Set-Cookie", format!("{cookie}; Max-Age=0"));
+            }
+            res.header_mut().insert("X-Count", "0");
+            res.header_mut().insert(
+                "x-count",
+                req.header().get_all("COOKIE").count().to_string(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_header_map() {
+        let client = TestClient::new(Http1::new(8192, CookieHandler));
+        let response = client.send(
+            TestRequest::get("/")
+                .header("Cookie", "a=1; b=2")
+                .header("cookie", "c=3"),
+        );
+
+        let cookies: Vec<&str> = response
+            .headers
+            .iter()
+            .filter(|(k, _)| k == "Set-Cookie")
+            .map(|(_, v)| v.as_str())
+            .collect();
+        assert_eq!(cookies, vec!["a=1; b=2; Max-Age=0", "c=3; Max-Age=0"]);
+        // replaced in place, casing of the first one kept
+        assert_eq!(
+            response
+                .headers
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case("x-count"))
+                .collect::<Vec<_>>(),
+            vec![&("X-Count".to_string(), "2".to_string())]
+        );
+    }
+
+    struct RedirectHandler;
+
+    impl Handler for RedirectHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            let to = req.param().get("to").map(|v| v[0]).unwrap_or("/");
+            let to = to.replace("%0D", "\r").replace("%0A", "\n");
+            res.set_response_code(HttpResponseCode::MovedTemporarily);
+            res.header_mut().append("Location", to);
+            if req.path() == "/name" {
+                res.header_mut().append("X Bad", "1");
+            }
+            if req.path() == "/stream" {
+                let _ = res.start_streaming();
+                let _ = res.write_chunk(b"chunk");
+            }
+            let _ = write!(res, "secret");
+        }
+    }
+
+    #[test]
+    fn test_header_injection() {
+        let client = TestClient::new(Http1::new(8192, RedirectHandler));
+
+        let response = client.send(TestRequest::get("/?to=/next"));
+        assert_eq!(response.status, 302);
+        assert_eq!(response.header("Location"), Some("/next"));
+
+        for target in [
+            "/?to=/%0D%0ASet-Cookie:%20a=1",
+            "/?to=/%0ASet-Cookie:%20a=1",
+            "/name?to=/next",
+            "/stream?to=/%0D%0A%0D%0Ainjected",
+        ] {
+            let raw = client.exchange(&TestRequest::get(target).encode());
+            let response = TestResponse::parse(&raw);
+            assert_eq!(response.status, 500, "{target}");
+            assert_eq!(response.header("Location"), None);
+            assert_eq!(response.header("Set-Cookie"), None);
+            assert_eq!(response.text(), "Internal Server Error");
+            assert!(!String::from_utf8_lossy(&raw).contains("injected"));
+        }
+    }
+
+    struct BodyHandler;
+
+    impl Handler for BodyHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            match req.path() {
+                "/bytes" => res.set_body("static bytes"),
+                "/reader" => res.set_body(Body::reader(Cursor::new(b"0123456789"), Some(4))),
+                "/stream" => res.set_body(Body::reader(Cursor::new(b"0123456789"), None)),
+                "/chunks" => {
+                    res.set_body(Body::chunks((0..3).map(|i| format!("c{i}").into_bytes())))
+                }
+                _ => {
+                    let _ = write!(res, "written");
+                    res.set_body(vec![b'v'; 3]);
+                    assert!(write!(res, "after").is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_body() {
+        let client = TestClient::new(Http1::new(8192, BodyHandler));
+
+        let response = client.send(TestRequest::get("/bytes"));
+        assert_eq!(response.header("Content-Length"), Some("12"));
+        assert_eq!(response.text(), "static bytes");
+
+        let response = client.send(TestRequest::get("/reader"));
+        assert_eq!(response.header("Content-Length"), Some("4"));
+        assert_eq!(response.text(), "0123");
+
+        let response = client.send(TestRequest::get("/vec"));
+        assert_eq!(response.text(), "vvv");
+
+        // unknown length is chunked for HTTP/1.1
+        for (target, body) in [("/stream", "0123456789"), ("/chunks", "c0c1c2")] {
+            let response = client.send(TestRequest::get(target));
+            assert_eq!(response.header("Transfer-Encoding"), Some("chunked"));
+            assert_eq!(response.header("Content-Length"), None);
+            assert_eq!(response.text(), body);
+        }
+
+        // and ends with the connection for HTTP/1.0
+        let raw = client.exchange(&TestRequest::get("/chunks").version("HTTP/1.0").encode());
+        assert!(raw.starts_with(b"HTTP/1.0 200 OK\r\n"));
+        assert!(raw.ends_with(b"\r\n\r\nc0c1c2"));
+
+        let raw = client.exchange(&TestRequest::head("/bytes").encode());
+        let response = TestResponse::parse(&raw);
+        assert_eq!(response.header("Content-Length"), Some("12"));
+        assert!(raw.ends_with(b"\r\n\r\n"));
+    }
+
+    struct ErrorHandler;
+
+    impl Handler for ErrorHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            let _ = write!(res, "not sent");
+            if req.method() != HttpMethod::GET {
+                res.send_error(HttpResponseCode::MethodNotAllowed, "only GET <here>");
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_pages() {
+        let client = TestClient::new(
+            Http1::new(8192, ErrorHandler).with_error_pages(
+                ErrorPages::new()
+                    .with_template(Some(405), "<h1>{reason}</h1><p>{message}</p>")
+                    .with_problem_json(true),
+            ),
+        );
+
+        let response = client.send(TestRequest::post("/"));
+        assert_eq!(response.status, 405);
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("text/plain; charset=utf-8")
+        );
+        assert_eq!(response.text(), "only GET <here>");
+
+        let response = client.send(TestRequest::post("/").header("Accept", "text/html, */*;q=0.1"));
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(
+            response.text(),
+            "<h1>Method Not Allowed</h1><p>only GET &lt;here&gt;</p>"
+        );
+
+        let response = client.send(TestRequest::post("/").header("Accept", "application/json"));
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("application/problem+json")
+        );
+        assert_eq!(
+            response.text(),
+            r#"{"type":"about:blank","title":"Method Not Allowed","status":405,"detail":"only GET <here>"}"#
+        );
+
+        // errors of the server itself, before the handler
+        let request = TestRequest::post("/")
+            .header("Accept", "text/html")
+            .header("Content-Length", "2000000");
+        let response = client.send(request);
+        assert_eq!(response.status, 413);
+        assert!(response.text().contains("<h1>413 Payload Too Large</h1>"));
+
+        let response = client.send_raw(b"GET / HTTP/1.1\nAccept: text/html\n\n");
+        assert_eq!(response.status, 400);
+        assert_eq!(response.text(), "Invalid request");
+    }
+
+    struct PanicHandler;
+
+    impl Handler for PanicHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            res.header_mut().insert("Set-Cookie", "session=1");
+            let _ = write!(res, "not sent");
+            match req.path() {
+                "/stream" => {
+                    res.start_streaming().unwrap();
+                    res.write_chunk(b"started").unwrap();
+                    panic!("after streaming");
+                }
+                "/ok" => {}
+                path => panic!("no route to {path}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_handler_panic() {
+        let client = TestClient::new(Http1::new(8192, PanicHandler).with_panic_budget(3));
+
+        let response = client.send(TestRequest::get("/missing"));
+        assert_eq!(response.status, 500);
+        assert_eq!(response.header("Set-Cookie"), None);
+        assert_eq!(response.text(), "Internal Server Error");
+
+        // the same process keeps serving
+        let response = client.send(TestRequest::get("/ok"));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "not sent");
+        assert!(!process::recycling());
+
+        // status went out already, the connection is closed after what was sent
+        let raw = client.exchange(&TestRequest::get("/stream").encode());
+        let raw = String::from_utf8_lossy(&raw);
+        assert!(raw.starts_with("HTTP/1.1 200 OK\r\n"), "{raw}");
+        assert!(raw.ends_with("started"), "{raw}");
+
+        // the panic reaching the budget recycles the worker
+        let response = client.send(TestRequest::get("/missing").version("HTTP/1.0"));
+        assert_eq!(response.status, 500);
+        assert!(process::recycling());
+        process::cancel_recycle();
+    }
 
     #[test]
     fn test_parse_url() {
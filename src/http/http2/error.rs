@@ -0,0 +1,95 @@
+/**
+ * HTTP/2 error codes (RFC 9113 7)
+ */
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    NoError,
+    ProtocolError,
+    InternalError,
+    FlowControlError,
+    SettingsTimeout,
+    StreamClosed,
+    FrameSizeError,
+    RefusedStream,
+    Cancel,
+    CompressionError,
+    ConnectError,
+    EnhanceYourCalm,
+    InadequateSecurity,
+    Http11Required,
+    Unknown(u32),
+}
+
+impl ErrorCode {
+    pub fn code(&self) -> u32 {
+        return match self {
+            ErrorCode::NoError => 0x0,
+            ErrorCode::ProtocolError => 0x1,
+            ErrorCode::InternalError => 0x2,
+            ErrorCode::FlowControlError => 0x3,
+            ErrorCode::SettingsTimeout => 0x4,
+            ErrorCode::StreamClosed => 0x5,
+            ErrorCode::FrameSizeError => 0x6,
+            ErrorCode::RefusedStream => 0x7,
+            ErrorCode::Cancel => 0x8,
+            ErrorCode::CompressionError => 0x9,
+            ErrorCode::ConnectError => 0xa,
+            ErrorCode::EnhanceYourCalm => 0xb,
+            ErrorCode::InadequateSecurity => 0xc,
+            ErrorCode::Http11Required => 0xd,
+            ErrorCode::Unknown(code) => *code,
+        };
+    }
+
+    pub fn from_code(code: u32) -> Self {
+        return match code {
+            0x0 => ErrorCode::NoError,
+            0x1 => ErrorCode::ProtocolError,
+            0x2 => ErrorCode::InternalError,
+            0x3 => ErrorCode::FlowControlError,
+            0x4 => ErrorCode::SettingsTimeout,
+            0x5 => ErrorCode::StreamClosed,
+            0x6 => ErrorCode::FrameSizeError,
+            0x7 => ErrorCode::RefusedStream,
+            0x8 => ErrorCode::Cancel,
+            0x9 => ErrorCode::CompressionError,
+            0xa => ErrorCode::ConnectError,
+            0xb => ErrorCode::EnhanceYourCalm,
+            0xc => ErrorCode::InadequateSecurity,
+            0xd => ErrorCode::Http11Required,
+            code => ErrorCode::Unknown(code),
+        };
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // connection error, GOAWAY is sent and connection is closed
+    Connection(ErrorCode, String),
+    // stream error, RST_STREAM is sent and connection goes on
+    Stream(u32, ErrorCode),
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        return Error::Io(value);
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Error::Connection(code, msg) => {
+                f.write_fmt(format_args!("Connection({code:?}, {msg})"))
+            }
+            Error::Stream(id, code) => f.write_fmt(format_args!("Stream({id}, {code:?})")),
+            Error::Io(e) => f.write_fmt(format_args!("Io({e})")),
+        };
+    }
+}
+
+pub fn protocol_error(msg: impl Into<String>) -> Error {
+    return Error::Connection(ErrorCode::ProtocolError, msg.into());
+}
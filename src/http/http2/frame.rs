@@ -0,0 +1,366 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::http::http2::error::{Error, ErrorCode, protocol_error};
+
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+pub const FRAME_HEADER_LEN: usize = 9;
+pub const MAX_WINDOW_SIZE: i64 = (1 << 31) - 1;
+pub const DEFAULT_WINDOW_SIZE: u32 = 65535;
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 16384;
+
+pub const FLAG_END_STREAM: u8 = 0x1;
+pub const FLAG_ACK: u8 = 0x1;
+pub const FLAG_END_HEADERS: u8 = 0x4;
+pub const FLAG_PADDED: u8 = 0x8;
+pub const FLAG_PRIORITY: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Data,
+    Headers,
+    Priority,
+    RstStream,
+    Settings,
+    PushPromise,
+    Ping,
+    GoAway,
+    WindowUpdate,
+    Continuation,
+    Unknown(u8),
+}
+
+impl FrameType {
+    pub fn from_code(code: u8) -> Self {
+        return match code {
+            0x0 => FrameType::Data,
+            0x1 => FrameType::Headers,
+            0x2 => FrameType::Priority,
+            0x3 => FrameType::RstStream,
+            0x4 => FrameType::Settings,
+            0x5 => FrameType::PushPromise,
+            0x6 => FrameType::Ping,
+            0x7 => FrameType::GoAway,
+            0x8 => FrameType::WindowUpdate,
+            0x9 => FrameType::Continuation,
+            code => FrameType::Unknown(code),
+        };
+    }
+
+    pub fn code(&self) -> u8 {
+        return match self {
+            FrameType::Data => 0x0,
+            FrameType::Headers => 0x1,
+            FrameType::Priority => 0x2,
+            FrameType::RstStream => 0x3,
+            FrameType::Settings => 0x4,
+            FrameType::PushPromise => 0x5,
+            FrameType::Ping => 0x6,
+            FrameType::GoAway => 0x7,
+            FrameType::WindowUpdate => 0x8,
+            FrameType::Continuation => 0x9,
+            FrameType::Unknown(code) => *code,
+        };
+    }
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub kind: FrameType,
+    pub flags: u8,
+    pub stream_id: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn has_flag(&self, flag: u8) -> bool {
+        return self.flags & flag != 0;
+    }
+
+    /**
+     * Payload without padding of DATA, HEADERS frame
+     */
+    pub fn unpadded(&self) -> Result<&[u8], Error> {
+        if !self.has_flag(FLAG_PADDED) {
+            return Ok(&self.payload);
+        }
+
+        let pad_len = *self
+            .payload
+            .first()
+            .ok_or_else(|| protocol_error("padded frame without pad length"))?
+            as usize;
+        if pad_len >= self.payload.len() {
+            return Err(protocol_error("padding exceeds frame payload"));
+        }
+
+        return Ok(&self.payload[1..self.payload.len() - pad_len]);
+    }
+}
+
+/**
+ * Incremental frame reader.
+ * Read timeout of the stream only means no data yet, partially read frame is kept for next call.
+ */
+pub struct FrameReader {
+    buf: Vec<u8>,
+    max_frame_size: u32,
+    expect_preface: bool,
+    readed: usize,
+}
+
+impl FrameReader {
+    pub fn new(max_frame_size: u32, expect_preface: bool) -> Self {
+        return Self {
+            buf: vec![],
+            max_frame_size,
+            expect_preface,
+            readed: 0,
+        };
+    }
+
+    pub fn readed(&self) -> usize {
+        return self.readed;
+    }
+
+    /**
+     * Returns `None` when read timed out before whole frame arrived.
+     */
+    pub fn read(&mut self, reader: &mut dyn Read) -> Result<Option<Frame>, Error> {
+        loop {
+            if let Some(frame) = self.parse()? {
+                return Ok(Some(frame));
+            }
+
+            let mut tmp = [0; 8192];
+            match reader.read(&mut tmp) {
+                Ok(0) => return Err(Error::Io(ErrorKind::UnexpectedEof.into())),
+                Ok(n) => {
+                    self.readed += n;
+                    self.buf.extend_from_slice(&tmp[..n]);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    return Ok(None);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+    }
+
+    fn parse(&mut self) -> Result<Option<Frame>, Error> {
+        if self.expect_preface {
+            if self.buf.len() < PREFACE.len() {
+                return Ok(None);
+            }
+            if &self.buf[..PREFACE.len()] != PREFACE {
+                return Err(protocol_error("invalid connection preface"));
+            }
+            self.buf.drain(..PREFACE.len());
+            self.expect_preface = false;
+        }
+
+        if self.buf.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+
+        let length = u32::from_be_bytes([0, self.buf[0], self.buf[1], self.buf[2]]);
+        if length > self.max_frame_size {
+            return Err(Error::Connection(
+                ErrorCode::FrameSizeError,
+                format!("frame size {length} exceeds limit"),
+            ));
+        }
+
+        let total = FRAME_HEADER_LEN + length as usize;
+        if self.buf.len() < total {
+            return Ok(None);
+        }
+
+        let frame = Frame {
+            kind: FrameType::from_code(self.buf[3]),
+            flags: self.buf[4],
+            stream_id: read_u32(&self.buf[5..9]) & 0x7fff_ffff,
+            payload: self.buf[FRAME_HEADER_LEN..total].to_vec(),
+        };
+        self.buf.drain(..total);
+
+        return Ok(Some(frame));
+    }
+}
+
+pub fn write_frame(
+    writer: &mut dyn Write,
+    kind: FrameType,
+    flags: u8,
+    stream_id: u32,
+    payload: &[u8],
+) -> std::io::Result<usize> {
+    let len = (payload.len() as u32).to_be_bytes();
+    let mut buf = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    buf.extend_from_slice(&len[1..]);
+    buf.push(kind.code());
+    buf.push(flags);
+    buf.extend_from_slice(&(stream_id & 0x7fff_ffff).to_be_bytes());
+    buf.extend_from_slice(payload);
+
+    writer.write_all(&buf)?;
+    return Ok(buf.len());
+}
+
+pub fn read_u32(bytes: &[u8]) -> u32 {
+    return u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+}
+
+const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub header_table_size: u32,
+    pub enable_push: bool,
+    pub max_concurrent_streams: Option<u32>,
+    pub initial_window_size: u32,
+    pub max_frame_size: u32,
+    pub max_header_list_size: Option<u32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Self {
+            header_table_size: 4096,
+            enable_push: true,
+            max_concurrent_streams: None,
+            initial_window_size: DEFAULT_WINDOW_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_header_list_size: None,
+        };
+    }
+}
+
+impl Settings {
+    /**
+     * Apply SETTINGS payload, unknown identifiers are ignored.
+     */
+    pub fn apply(&mut self, payload: &[u8]) -> Result<(), Error> {
+        if !payload.len().is_multiple_of(6) {
+            return Err(Error::Connection(
+                ErrorCode::FrameSizeError,
+                "invalid SETTINGS length".to_string(),
+            ));
+        }
+
+        for setting in payload.chunks(6) {
+            let id = u16::from_be_bytes([setting[0], setting[1]]);
+            let value = read_u32(&setting[2..]);
+
+            match id {
+                SETTINGS_HEADER_TABLE_SIZE => self.header_table_size = value,
+                SETTINGS_ENABLE_PUSH => match value {
+                    0 => self.enable_push = false,
+                    1 => self.enable_push = true,
+                    _ => return Err(protocol_error("invalid SETTINGS_ENABLE_PUSH")),
+                },
+                SETTINGS_MAX_CONCURRENT_STREAMS => self.max_concurrent_streams = Some(value),
+                SETTINGS_INITIAL_WINDOW_SIZE => {
+                    if value as i64 > MAX_WINDOW_SIZE {
+                        return Err(Error::Connection(
+                            ErrorCode::FlowControlError,
+                            "invalid SETTINGS_INITIAL_WINDOW_SIZE".to_string(),
+                        ));
+                    }
+                    self.initial_window_size = value;
+                }
+                SETTINGS_MAX_FRAME_SIZE => {
+                    if !(DEFAULT_MAX_FRAME_SIZE..=0xff_ffff).contains(&value) {
+                        return Err(protocol_error("invalid SETTINGS_MAX_FRAME_SIZE"));
+                    }
+                    self.max_frame_size = value;
+                }
+                SETTINGS_MAX_HEADER_LIST_SIZE => self.max_header_list_size = Some(value),
+                _ => (),
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut values = vec![
+            (SETTINGS_HEADER_TABLE_SIZE, self.header_table_size),
+            (SETTINGS_ENABLE_PUSH, self.enable_push as u32),
+            (SETTINGS_INITIAL_WINDOW_SIZE, self.initial_window_size),
+            (SETTINGS_MAX_FRAME_SIZE, self.max_frame_size),
+        ];
+        if let Some(max) = self.max_concurrent_streams {
+            values.push((SETTINGS_MAX_CONCURRENT_STREAMS, max));
+        }
+        if let Some(max) = self.max_header_list_size {
+            values.push((SETTINGS_MAX_HEADER_LIST_SIZE, max));
+        }
+
+        let mut payload = Vec::with_capacity(values.len() * 6);
+        for (id, value) in values {
+            payload.extend_from_slice(&id.to_be_bytes());
+            payload.extend_from_slice(&value.to_be_bytes());
+        }
+
+        return payload;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::http::http2::frame::{
+        FLAG_END_HEADERS, FrameReader, FrameType, PREFACE, Settings, write_frame,
+    };
+
+    #[test]
+    fn test_read_written_frame() {
+        let mut buf = PREFACE.to_vec();
+        write_frame(&mut buf, FrameType::Headers, FLAG_END_HEADERS, 3, b"abc").unwrap();
+
+        let mut reader = FrameReader::new(16384, true);
+        let frame = reader.read(&mut Cursor::new(buf)).unwrap().unwrap();
+        assert_eq!(frame.kind, FrameType::Headers);
+        assert_eq!(frame.flags, FLAG_END_HEADERS);
+        assert_eq!(frame.stream_id, 3);
+        assert_eq!(frame.payload, b"abc");
+    }
+
+    #[test]
+    fn test_frame_over_max_size() {
+        let mut buf = vec![];
+        write_frame(&mut buf, FrameType::Data, 0, 1, &[0; 100]).unwrap();
+
+        let mut reader = FrameReader::new(50, false);
+        assert!(reader.read(&mut Cursor::new(buf)).is_err());
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let settings = Settings {
+            max_concurrent_streams: Some(10),
+            initial_window_size: 1024,
+            ..Default::default()
+        };
+
+        let mut applied = Settings::default();
+        applied.apply(&settings.encode()).unwrap();
+        assert_eq!(applied.max_concurrent_streams, Some(10));
+        assert_eq!(applied.initial_window_size, 1024);
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let mut settings = Settings::default();
+        assert!(settings.apply(&[0, 5, 0, 0, 0, 1]).is_err());
+        assert!(settings.apply(&[0, 2, 0, 0, 0, 2]).is_err());
+        assert!(settings.apply(&[0, 2, 0]).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::VecDeque;
+
+use crate::http::http2::{error::ErrorCode, huffman};
+
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+// every entry has 32 octets overhead (RFC 7541 4.1)
+const ENTRY_OVERHEAD: usize = 32;
+
+struct DynamicTable {
+    entries: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl DynamicTable {
+    fn new(max_size: usize) -> Self {
+        return Self {
+            entries: VecDeque::new(),
+            size: 0,
+            max_size,
+        };
+    }
+
+    fn get(&self, idx: usize) -> Option<&(String, String)> {
+        return self.entries.get(idx);
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        let entry_size = name.len() + value.len() + ENTRY_OVERHEAD;
+        // inserting entry larger than table empties the table
+        if entry_size > self.max_size {
+            self.entries.clear();
+            self.size = 0;
+            return;
+        }
+
+        self.size += entry_size;
+        self.entries.push_front((name, value));
+        self.evict();
+    }
+
+    fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            match self.entries.pop_back() {
+                Some((n, v)) => self.size -= n.len() + v.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+}
+
+/**
+ * HPACK header block decoder (RFC 7541)
+ * One decoder lives as long as the connection because dynamic table is shared by all header blocks.
+ */
+pub struct Decoder {
+    table: DynamicTable,
+    // SETTINGS_HEADER_TABLE_SIZE we sent to peer
+    max_size_limit: usize,
+    // SETTINGS_MAX_HEADER_LIST_SIZE we sent to peer
+    max_list_size: usize,
+}
+
+impl Decoder {
+    pub fn new(max_size: usize) -> Self {
+        return Self {
+            table: DynamicTable::new(max_size),
+            max_size_limit: max_size,
+            max_list_size: usize::MAX,
+        };
+    }
+
+    /**
+     * Decoded header list is bounded by size of RFC 7541 (name + value + 32 per field), not block length.
+     * A small block indexing one large table entry over and over would decode to a huge list otherwise.
+     */
+    pub fn with_max_header_list_size(mut self, max_list_size: usize) -> Self {
+        self.max_list_size = max_list_size;
+        return self;
+    }
+
+    /**
+     * Fails with `EnhanceYourCalm` once the header list passes its limit, the block is not decoded further.
+     */
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>, ErrorCode> {
+        let mut headers = vec![];
+        let mut pos = 0;
+        let mut field_seen = false;
+        let mut list_size = 0;
+
+        while pos < block.len() {
+            let b = block[pos];
+
+            let (name, value) = if b & 0x80 != 0 {
+                // indexed header field
+                let idx = decode_int(block, &mut pos, 7)?;
+                self.entry(idx)?
+            } else if b & 0x40 != 0 {
+                // literal with incremental indexing
+                let (name, value) = self.decode_literal(block, &mut pos, 6)?;
+                self.table.insert(name.clone(), value.clone());
+                (name, value)
+            } else if b & 0x20 != 0 {
+                // dynamic table size update, only allowed at the beginning of block
+                if field_seen {
+                    return Err(ErrorCode::CompressionError);
+                }
+                let size = decode_int(block, &mut pos, 5)?;
+                if size > self.max_size_limit {
+                    return Err(ErrorCode::CompressionError);
+                }
+                self.table.set_max_size(size);
+                continue;
+            } else {
+                // literal without indexing, literal never indexed
+                self.decode_literal(block, &mut pos, 4)?
+            };
+            field_seen = true;
+
+            list_size += name.len() + value.len() + ENTRY_OVERHEAD;
+            if list_size > self.max_list_size {
+                return Err(ErrorCode::EnhanceYourCalm);
+            }
+            headers.push((name, value));
+        }
+
+        return Ok(headers);
+    }
+
+    fn entry(&self, idx: usize) -> Result<(String, String), ErrorCode> {
+        if idx == 0 {
+            return Err(ErrorCode::CompressionError);
+        }
+
+        if idx <= STATIC_TABLE.len() {
+            let (name, value) = STATIC_TABLE[idx - 1];
+            return Ok((name.to_string(), value.to_string()));
+        }
+
+        return self
+            .table
+            .get(idx - STATIC_TABLE.len() - 1)
+            .cloned()
+            .ok_or(ErrorCode::CompressionError);
+    }
+
+    fn decode_literal(
+        &self,
+        block: &[u8],
+        pos: &mut usize,
+        prefix: u8,
+    ) -> Result<(String, String), ErrorCode> {
+        let idx = decode_int(block, pos, prefix)?;
+        let name = if idx == 0 {
+            decode_string(block, pos)?
+        } else {
+            self.entry(idx)?.0
+        };
+        let value = decode_string(block, pos)?;
+
+        return Ok((name, value));
+    }
+}
+
+/**
+ * HPACK header block encoder.
+ * Dynamic table is never used, so peer's SETTINGS_HEADER_TABLE_SIZE does not matter.
+ */
+pub struct Encoder;
+
+impl Encoder {
+    pub fn encode(&self, headers: &[(String, String)], dst: &mut Vec<u8>) {
+        for (name, value) in headers {
+            let name_idx = STATIC_TABLE.iter().position(|(n, _)| n == name);
+            let full_idx = STATIC_TABLE
+                .iter()
+                .position(|(n, v)| n == name && v == value);
+
+            if let Some(idx) = full_idx {
+                encode_int(idx + 1, 7, 0x80, dst);
+                continue;
+            }
+
+            match name_idx {
+                Some(idx) => encode_int(idx + 1, 4, 0x00, dst),
+                None => {
+                    dst.push(0x00);
+                    encode_string(name.as_bytes(), dst);
+                }
+            }
+            encode_string(value.as_bytes(), dst);
+        }
+    }
+}
+
+fn decode_int(block: &[u8], pos: &mut usize, prefix: u8) -> Result<usize, ErrorCode> {
+    let mask = ((1u16 << prefix) - 1) as u8;
+    let first = *block.get(*pos).ok_or(ErrorCode::CompressionError)? & mask;
+    *pos += 1;
+
+    if first < mask {
+        return Ok(first as usize);
+    }
+
+    let mut value = mask as usize;
+    let mut shift = 0;
+    loop {
+        let b = *block.get(*pos).ok_or(ErrorCode::CompressionError)?;
+        *pos += 1;
+
+        // limit to 4 continuation octets, large enough for any sane length
+        if shift > 28 {
+            return Err(ErrorCode::CompressionError);
+        }
+        value += ((b & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn decode_string(block: &[u8], pos: &mut usize) -> Result<String, ErrorCode> {
+    let is_huffman = block.get(*pos).map(|b| b & 0x80 != 0).unwrap_or(false);
+    let len = decode_int(block, pos, 7)?;
+    let end = pos.checked_add(len).ok_or(ErrorCode::CompressionError)?;
+    let raw = block.get(*pos..end).ok_or(ErrorCode::CompressionError)?;
+    *pos = end;
+
+    let bytes = if is_huffman {
+        huffman::decode(raw)?
+    } else {
+        raw.to_vec()
+    };
+
+    return Ok(String::from_utf8_lossy(&bytes).into_owned());
+}
+
+fn encode_int(value: usize, prefix: u8, flags: u8, dst: &mut Vec<u8>) {
+    let mask = (1usize << prefix) - 1;
+    if value < mask {
+        dst.push(flags | value as u8);
+        return;
+    }
+
+    dst.push(flags | mask as u8);
+    let mut rest = value - mask;
+    while rest >= 0x80 {
+        dst.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    dst.push(rest as u8);
+}
+
+fn encode_string(value: &[u8], dst: &mut Vec<u8>) {
+    let huffman_len = huffman::encoded_len(value);
+    if huffman_len < value.len() {
+        encode_int(huffman_len, 7, 0x80, dst);
+        huffman::encode(value, dst);
+    } else {
+        encode_int(value.len(), 7, 0x00, dst);
+        dst.extend_from_slice(value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::http::http2::{
+        error::ErrorCode,
+        hpack::{Decoder, Encoder, decode_int, encode_int},
+    };
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        return (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect();
+    }
+
+    fn pairs(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        return headers
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+    }
+
+    #[test]
+    fn test_integer() {
+        // RFC 7541 C.1.2
+        let mut dst = vec![];
+        encode_int(1337, 5, 0, &mut dst);
+        assert_eq!(dst, vec![0x1f, 0x9a, 0x0a]);
+
+        let mut pos = 0;
+        assert_eq!(decode_int(&dst, &mut pos, 5), Ok(1337));
+        assert_eq!(pos, 3);
+    }
+
+    #[test]
+    fn test_decode_requests_with_huffman() {
+        // RFC 7541 C.4
+        let mut decoder = Decoder::new(4096);
+
+        let first = decoder
+            .decode(&hex("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff"))
+            .unwrap();
+        assert_eq!(
+            first,
+            pairs(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ])
+        );
+
+        let second = decoder
+            .decode(&hex("8286 84be 5886 a8eb 1064 9cbf"))
+            .unwrap();
+        assert_eq!(
+            second,
+            pairs(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+                ("cache-control", "no-cache"),
+            ])
+        );
+
+        let third = decoder
+            .decode(&hex(
+                "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+            ))
+            .unwrap();
+        assert_eq!(
+            third,
+            pairs(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_invalid_index() {
+        let mut decoder = Decoder::new(4096);
+        assert!(decoder.decode(&[0x80]).is_err());
+        assert!(decoder.decode(&[0xbe]).is_err());
+    }
+
+    #[test]
+    fn test_table_size_update_over_limit() {
+        let mut decoder = Decoder::new(4096);
+        let mut block = vec![];
+        encode_int(8192, 5, 0x20, &mut block);
+        assert!(decoder.decode(&block).is_err());
+    }
+
+    #[test]
+    fn test_header_list_size() {
+        // one 133 byte entry, then indexed again and again, one byte each
+        let mut block = vec![0x40, 0x01, b'x', 100];
+        block.extend_from_slice(&[b'a'; 100]);
+        block.extend_from_slice(&[0xbe; 1000]);
+
+        let mut decoder = Decoder::new(4096).with_max_header_list_size(8192);
+        assert_eq!(decoder.decode(&block), Err(ErrorCode::EnhanceYourCalm));
+        // 61 * 133 = 8113
+        assert_eq!(decoder.decode(&[0xbe; 61]).unwrap().len(), 61);
+        assert!(decoder.decode(&[0xbe; 62]).is_err());
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let headers = pairs(&[
+            (":status", "200"),
+            (":status", "201"),
+            ("content-type", "text/plain"),
+            ("x-custom", "value"),
+        ]);
+
+        let mut block = vec![];
+        Encoder.encode(&headers, &mut block);
+        assert_eq!(block[0], 0x88);
+        assert_eq!(Decoder::new(4096).decode(&block).unwrap(), headers);
+    }
+}
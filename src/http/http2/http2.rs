@@ -0,0 +1,1254 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufReader, Cursor, ErrorKind, Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    http::{
+        handler::Handler,
+        header::server,
+        header_map::HeaderMap,
+        http::{Http1, parse_url},
+        http2::{
+            error::{Error, ErrorCode, protocol_error},
+            frame::{
+                DEFAULT_WINDOW_SIZE, FLAG_ACK, FLAG_END_HEADERS, FLAG_END_STREAM, FLAG_PRIORITY,
+                Frame, FrameReader, FrameType, MAX_WINDOW_SIZE, Settings, read_u32, write_frame,
+            },
+            hpack::{Decoder, Encoder},
+        },
+        parser::{RequestHead, with_request_head},
+        request::HttpRequest,
+        response::{HeaderSetter, HttpResponse},
+        timeout::TimedReader,
+        value::{HttpMethod, HttpVersion},
+    },
+    process::{
+        self, Process,
+        address::Address,
+        proxy::ProxyHeader,
+        stream::{self, SharedStream, Stream as _},
+    },
+    tls::TlsInfo,
+    util::base64,
+};
+
+// HTTP/2 connection wakes this often to send pending data and check idle
+const READ_POLL: Duration = Duration::from_millis(100);
+
+// second half of connection preface, first half is read as HTTP/1 request line
+const PREFACE_REST: &[u8] = b"SM\r\n\r\n";
+
+// headers which are only meaningful for HTTP/1 connection (RFC 9113 8.2.2)
+const CONNECTION_HEADERS: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/**
+ * HTTP/2 over cleartext TCP (h2c).
+ * Connection with prior knowledge starts with HTTP/2 preface,
+ * HTTP/1.1 request with `Upgrade: h2c` is switched to HTTP/2 and becomes stream 1.
+ * Any other request is served by wrapped `Http1`, with the same handler.
+ */
+pub struct Http2<T: Handler> {
+    http1: Http1<T>,
+    max_concurrent_streams: u32,
+    max_body_size: usize,
+    idle_timeout: Duration,
+    stream_timeout: Duration,
+}
+
+impl<T> Process for Http2<T>
+where
+    T: Handler,
+{
+    fn process(
+        &self,
+        stream: Box<dyn stream::Stream>,
+        client_addr: &Address,
+    ) -> Result<(usize, usize), process::Error> {
+        let stream = SharedStream::new(stream);
+        self.http1.init_stream(&stream);
+
+        let mut reader = BufReader::new(TimedReader::new(stream.clone(), self.http1.timeouts()));
+        let timed_out = reader.get_ref().timed_out();
+
+        return with_request_head(|head| {
+            self.http1
+                .read_header(client_addr, &mut reader, head)
+                .map_err(|err| {
+                    self.http1
+                        .error_response_for_header(&stream, &err, timed_out.get());
+                    process::Error::IoFail(format!("Read header failed: ({})", err))
+                })?;
+
+            // "PRI * HTTP/2.0\r\n\r\n" looks like HTTP/1 request without header
+            if head.method() == "PRI"
+                && head.target() == "*"
+                && head.version() == "HTTP/2.0"
+                && head.fields().next().is_none()
+            {
+                let mut rest = [0; PREFACE_REST.len()];
+                reader
+                    .read_exact(&mut rest)
+                    .map_err(|e| process::Error::IoFail(format!("Read preface failed: ({})", e)))?;
+                if rest != PREFACE_REST {
+                    return Err(process::Error::ParseFail("invalid preface".to_string()));
+                }
+                reader.get_mut().release();
+                let _ = stream.set_read_timeout(Some(READ_POLL));
+
+                let connection =
+                    Connection::new(self, client_addr, &stream, reader, stream.clone(), false);
+                return connection
+                    .serve(None)
+                    .map(|(r, w)| (head.len() + PREFACE_REST.len() + r, w));
+            }
+
+            if let Some(settings) = upgrade_settings(head) {
+                let mut writer = stream.clone();
+                let switching =
+                    b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n";
+                writer
+                    .write_all(switching)
+                    .map_err(|e| process::Error::IoFail(e.to_string()))?;
+                reader.get_mut().release();
+                let _ = stream.set_read_timeout(Some(READ_POLL));
+
+                let connection =
+                    Connection::new(self, client_addr, &stream, reader, stream.clone(), true);
+                let upgrade = Upgrade {
+                    settings,
+                    headers: upgrade_headers(head),
+                };
+                return connection
+                    .serve(Some(upgrade))
+                    .map(|(r, w)| (head.len() + r, switching.len() + w));
+            }
+
+            reader.get_mut().start_body();
+            return self
+                .http1
+                .respond(&stream, client_addr, head, Box::new(reader), &timed_out);
+        });
+    }
+
+    fn name(&self) -> String {
+        return "http2".to_string();
+    }
+}
+
+#[allow(dead_code)]
+impl<T> Http2<T>
+where
+    T: Handler,
+{
+    pub fn new(http1: Http1<T>) -> Self {
+        return Self {
+            http1,
+            max_concurrent_streams: 100,
+            max_body_size: 1024 * 1024,
+            idle_timeout: Duration::from_secs(5),
+            stream_timeout: Duration::from_secs(30),
+        };
+    }
+
+    pub fn with_max_concurrent_streams(mut self, max_concurrent_streams: u32) -> Self {
+        self.max_concurrent_streams = max_concurrent_streams;
+        return self;
+    }
+
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        return self;
+    }
+
+    /**
+     * Connection without any open stream is closed with GOAWAY after idle timeout.
+     */
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        return self;
+    }
+
+    /**
+     * Stream still open after stream timeout, its request not fully received or its response not fully sent,
+     * is reset with RST_STREAM CANCEL. It applies while other streams keep the connection busy.
+     */
+    pub fn with_stream_timeout(mut self, stream_timeout: Duration) -> Self {
+        self.stream_timeout = stream_timeout;
+        return self;
+    }
+
+    fn local_settings(&self) -> Settings {
+        return Settings {
+            enable_push: false,
+            max_concurrent_streams: Some(self.max_concurrent_streams),
+            max_header_list_size: Some(self.http1.max_header_length() as u32),
+            ..Default::default()
+        };
+    }
+}
+
+struct Upgrade {
+    settings: Vec<u8>,
+    headers: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+struct Stream {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    // END_STREAM received from client
+    remote_closed: bool,
+    // content-length of request, DATA must add up to it
+    content_length: Option<usize>,
+    send_window: i64,
+    // response body waiting for flow control window
+    pending: Option<Vec<u8>>,
+    sent: usize,
+    // stream is reset when still open then
+    deadline: Option<Instant>,
+}
+
+struct HeaderBlock {
+    stream_id: u32,
+    block: Vec<u8>,
+    end_stream: bool,
+}
+
+struct Connection<'c, T: Handler, R: Read, W: Write> {
+    http2: &'c Http2<T>,
+    client_addr: &'c Address,
+    local_addr: Address,
+    tls: Option<TlsInfo>,
+    proxy_header: Option<ProxyHeader>,
+    reader: R,
+    writer: W,
+    frames: FrameReader,
+    decoder: Decoder,
+    encoder: Encoder,
+    local: Settings,
+    peer: Settings,
+    streams: BTreeMap<u32, Stream>,
+    send_window: i64,
+    last_stream_id: u32,
+    // header block waiting for CONTINUATION
+    header_block: Option<HeaderBlock>,
+    goaway_received: bool,
+    written: usize,
+}
+
+impl<'c, T, R, W> Connection<'c, T, R, W>
+where
+    T: Handler,
+    R: Read,
+    W: Write,
+{
+    fn new(
+        http2: &'c Http2<T>,
+        client_addr: &'c Address,
+        // connection metadata handed to requests
+        meta: &dyn stream::Stream,
+        reader: R,
+        writer: W,
+        expect_preface: bool,
+    ) -> Self {
+        let local = http2.local_settings();
+        return Self {
+            http2,
+            client_addr,
+            local_addr: meta.local_addr(),
+            tls: meta.tls_info(),
+            proxy_header: meta.proxy_header(),
+            reader,
+            writer,
+            frames: FrameReader::new(local.max_frame_size, expect_preface),
+            decoder: Decoder::new(local.header_table_size as usize)
+                .with_max_header_list_size(http2.http1.max_header_length()),
+            encoder: Encoder,
+            local,
+            peer: Settings::default(),
+            streams: BTreeMap::new(),
+            // connection window is not affected by SETTINGS_INITIAL_WINDOW_SIZE
+            send_window: DEFAULT_WINDOW_SIZE as i64,
+            last_stream_id: 0,
+            header_block: None,
+            goaway_received: false,
+            written: 0,
+        };
+    }
+
+    fn serve(mut self, upgrade: Option<Upgrade>) -> Result<(usize, usize), process::Error> {
+        let result = self.run(upgrade);
+        let _ = self.writer.flush();
+        let readed = self.frames.readed();
+
+        return match result {
+            Ok(()) => Ok((readed, self.written)),
+            Err(Error::Connection(code, msg)) => {
+                let _ = self.goaway(code);
+                let _ = self.writer.flush();
+                Err(process::Error::ParseFail(format!(
+                    "HTTP/2 connection error {code:?}: {msg}"
+                )))
+            }
+            Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => Ok((readed, self.written)),
+            Err(e) => Err(process::Error::IoFail(e.to_string())),
+        };
+    }
+
+    fn run(&mut self, upgrade: Option<Upgrade>) -> Result<(), Error> {
+        let settings = self.local.encode();
+        self.write_frame(FrameType::Settings, 0, 0, &settings)?;
+
+        if let Some(upgrade) = upgrade {
+            // HTTP2-Settings header carries SETTINGS payload of client
+            self.peer.apply(&upgrade.settings)?;
+            self.last_stream_id = 1;
+            self.streams.insert(
+                1,
+                Stream {
+                    headers: upgrade.headers,
+                    remote_closed: true,
+                    send_window: self.peer.initial_window_size as i64,
+                    deadline: Some(Instant::now() + self.http2.stream_timeout),
+                    ..Default::default()
+                },
+            );
+            self.dispatch(1)?;
+        }
+
+        let mut last_activity = Instant::now();
+        loop {
+            self.send_pending()?;
+            self.reset_expired()?;
+            self.writer.flush()?;
+
+            if self.goaway_received && self.streams.is_empty() {
+                return Ok(());
+            }
+
+            let frame = match self.frames.read(&mut self.reader)? {
+                Some(frame) => frame,
+                None => {
+                    if self.streams.is_empty() && last_activity.elapsed() > self.http2.idle_timeout
+                    {
+                        log::debug!("HTTP/2 connection idle, close");
+                        return self.goaway(ErrorCode::NoError);
+                    }
+                    continue;
+                }
+            };
+            last_activity = Instant::now();
+
+            log::trace!(
+                "<< {:?} flags:{:#x} stream:{} len:{}",
+                frame.kind,
+                frame.flags,
+                frame.stream_id,
+                frame.payload.len()
+            );
+
+            match self.handle_frame(frame) {
+                Ok(()) => (),
+                Err(Error::Stream(id, code)) => {
+                    log::debug!("HTTP/2 stream {id} reset: {code:?}");
+                    self.streams.remove(&id);
+                    self.write_frame(FrameType::RstStream, 0, id, &code.code().to_be_bytes())?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn handle_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        if let Some(block) = &self.header_block
+            && (frame.kind != FrameType::Continuation || frame.stream_id != block.stream_id)
+        {
+            return Err(protocol_error("CONTINUATION expected"));
+        }
+
+        return match frame.kind {
+            FrameType::Data => self.on_data(frame),
+            FrameType::Headers => self.on_headers(frame),
+            FrameType::Continuation => self.on_continuation(frame),
+            FrameType::Priority => {
+                if frame.stream_id == 0 {
+                    return Err(protocol_error("PRIORITY on stream 0"));
+                }
+                if frame.payload.len() != 5 {
+                    return Err(Error::Stream(frame.stream_id, ErrorCode::FrameSizeError));
+                }
+                Ok(())
+            }
+            FrameType::RstStream => {
+                if frame.stream_id == 0 || frame.stream_id > self.last_stream_id {
+                    return Err(protocol_error("RST_STREAM on idle stream"));
+                }
+                if frame.payload.len() != 4 {
+                    return Err(Error::Connection(
+                        ErrorCode::FrameSizeError,
+                        "invalid RST_STREAM length".to_string(),
+                    ));
+                }
+                self.streams.remove(&frame.stream_id);
+                Ok(())
+            }
+            FrameType::Settings => self.on_settings(frame),
+            FrameType::PushPromise => Err(protocol_error("PUSH_PROMISE from client")),
+            FrameType::Ping => {
+                if frame.stream_id != 0 {
+                    return Err(protocol_error("PING on stream"));
+                }
+                if frame.payload.len() != 8 {
+                    return Err(Error::Connection(
+                        ErrorCode::FrameSizeError,
+                        "invalid PING length".to_string(),
+                    ));
+                }
+                if !frame.has_flag(FLAG_ACK) {
+                    self.write_frame(FrameType::Ping, FLAG_ACK, 0, &frame.payload)?;
+                }
+                Ok(())
+            }
+            FrameType::GoAway => {
+                if frame.stream_id != 0 {
+                    return Err(protocol_error("GOAWAY on stream"));
+                }
+                if frame.payload.len() < 8 {
+                    return Err(Error::Connection(
+                        ErrorCode::FrameSizeError,
+                        "invalid GOAWAY length".to_string(),
+                    ));
+                }
+                let code = ErrorCode::from_code(read_u32(&frame.payload[4..8]));
+                log::debug!("HTTP/2 GOAWAY received: {code:?}");
+                self.goaway_received = true;
+                Ok(())
+            }
+            FrameType::WindowUpdate => self.on_window_update(frame),
+            FrameType::Unknown(_) => Ok(()),
+        };
+    }
+
+    fn on_data(&mut self, frame: Frame) -> Result<(), Error> {
+        let id = frame.stream_id;
+        if id == 0 {
+            return Err(protocol_error("DATA on stream 0"));
+        }
+
+        // padding is also counted by flow control
+        let flow_len = frame.payload.len() as u32;
+        if flow_len > 0 {
+            self.write_frame(FrameType::WindowUpdate, 0, 0, &flow_len.to_be_bytes())?;
+        }
+
+        let data = frame.unpadded()?;
+        let stream = match self.streams.get_mut(&id) {
+            Some(stream) if !stream.remote_closed => stream,
+            _ if id > self.last_stream_id => return Err(protocol_error("DATA on idle stream")),
+            _ => return Err(Error::Stream(id, ErrorCode::StreamClosed)),
+        };
+
+        if stream.body.len() + data.len() > self.http2.max_body_size {
+            log::warn!(
+                "HTTP/2 stream {id} body exceeds {}",
+                self.http2.max_body_size
+            );
+            return Err(Error::Stream(id, ErrorCode::Cancel));
+        }
+        if stream
+            .content_length
+            .is_some_and(|length| stream.body.len() + data.len() > length)
+        {
+            return Err(Error::Stream(id, ErrorCode::ProtocolError));
+        }
+        stream.body.extend_from_slice(data);
+
+        if frame.has_flag(FLAG_END_STREAM) {
+            stream.remote_closed = true;
+            return self.dispatch(id);
+        }
+
+        if flow_len > 0 {
+            self.write_frame(FrameType::WindowUpdate, 0, id, &flow_len.to_be_bytes())?;
+        }
+        return Ok(());
+    }
+
+    fn on_headers(&mut self, frame: Frame) -> Result<(), Error> {
+        if frame.stream_id == 0 {
+            return Err(protocol_error("HEADERS on stream 0"));
+        }
+
+        let mut fragment = frame.unpadded()?;
+        if frame.has_flag(FLAG_PRIORITY) {
+            if fragment.len() < 5 {
+                return Err(protocol_error("invalid HEADERS priority"));
+            }
+            fragment = &fragment[5..];
+        }
+
+        let block = HeaderBlock {
+            stream_id: frame.stream_id,
+            block: fragment.to_vec(),
+            end_stream: frame.has_flag(FLAG_END_STREAM),
+        };
+
+        if frame.has_flag(FLAG_END_HEADERS) {
+            return self.on_header_block(block);
+        }
+
+        self.header_block = Some(block);
+        return Ok(());
+    }
+
+    fn on_continuation(&mut self, frame: Frame) -> Result<(), Error> {
+        let mut block = self
+            .header_block
+            .take()
+            .ok_or_else(|| protocol_error("unexpected CONTINUATION"))?;
+
+        block.block.extend_from_slice(&frame.payload);
+        // header block is buffered before decoding, bound it against CONTINUATION flood
+        if block.block.len() > self.http2.http1.max_header_length() {
+            return Err(Error::Connection(
+                ErrorCode::EnhanceYourCalm,
+                "header block too large".to_string(),
+            ));
+        }
+
+        if frame.has_flag(FLAG_END_HEADERS) {
+            return self.on_header_block(block);
+        }
+
+        self.header_block = Some(block);
+        return Ok(());
+    }
+
+    fn on_header_block(&mut self, block: HeaderBlock) -> Result<(), Error> {
+        let id = block.stream_id;
+        // decode even if the stream will be refused, to keep HPACK state in sync
+        let headers = self.decoder.decode(&block.block).map_err(|code| {
+            let msg = match code {
+                ErrorCode::EnhanceYourCalm => "header list too large",
+                _ => "header decode failed",
+            };
+            return Error::Connection(code, msg.to_string());
+        })?;
+
+        if let Some(stream) = self.streams.get_mut(&id) {
+            // trailer
+            if stream.remote_closed {
+                return Err(Error::Stream(id, ErrorCode::StreamClosed));
+            }
+            if !block.end_stream {
+                return Err(Error::Stream(id, ErrorCode::ProtocolError));
+            }
+            stream.remote_closed = true;
+            return self.dispatch(id);
+        }
+
+        if id.is_multiple_of(2) || id <= self.last_stream_id {
+            return Err(protocol_error(format!("invalid stream id {id}")));
+        }
+        self.last_stream_id = id;
+
+        if self.streams.len() >= self.http2.max_concurrent_streams as usize {
+            return Err(Error::Stream(id, ErrorCode::RefusedStream));
+        }
+
+        let content_length =
+            validate_request_headers(&headers).map_err(|code| Error::Stream(id, code))?;
+
+        self.streams.insert(
+            id,
+            Stream {
+                headers,
+                remote_closed: block.end_stream,
+                content_length,
+                send_window: self.peer.initial_window_size as i64,
+                deadline: Some(Instant::now() + self.http2.stream_timeout),
+                ..Default::default()
+            },
+        );
+
+        if block.end_stream {
+            return self.dispatch(id);
+        }
+        return Ok(());
+    }
+
+    fn on_settings(&mut self, frame: Frame) -> Result<(), Error> {
+        if frame.stream_id != 0 {
+            return Err(protocol_error("SETTINGS on stream"));
+        }
+
+        if frame.has_flag(FLAG_ACK) {
+            if !frame.payload.is_empty() {
+                return Err(Error::Connection(
+                    ErrorCode::FrameSizeError,
+                    "SETTINGS ack with payload".to_string(),
+                ));
+            }
+            return Ok(());
+        }
+
+        let old_window = self.peer.initial_window_size as i64;
+        self.peer.apply(&frame.payload)?;
+
+        let delta = self.peer.initial_window_size as i64 - old_window;
+        for stream in self.streams.values_mut() {
+            stream.send_window += delta;
+            if stream.send_window > MAX_WINDOW_SIZE {
+                return Err(Error::Connection(
+                    ErrorCode::FlowControlError,
+                    "stream window overflow".to_string(),
+                ));
+            }
+        }
+
+        self.write_frame(FrameType::Settings, FLAG_ACK, 0, &[])?;
+        return Ok(());
+    }
+
+    fn on_window_update(&mut self, frame: Frame) -> Result<(), Error> {
+        if frame.payload.len() != 4 {
+            return Err(Error::Connection(
+                ErrorCode::FrameSizeError,
+                "invalid WINDOW_UPDATE length".to_string(),
+            ));
+        }
+
+        let increment = (read_u32(&frame.payload) & 0x7fff_ffff) as i64;
+        let id = frame.stream_id;
+
+        if id == 0 {
+            if increment == 0 {
+                return Err(protocol_error("zero WINDOW_UPDATE"));
+            }
+            self.send_window += increment;
+            if self.send_window > MAX_WINDOW_SIZE {
+                return Err(Error::Connection(
+                    ErrorCode::FlowControlError,
+                    "connection window overflow".to_string(),
+                ));
+            }
+            return Ok(());
+        }
+
+        if increment == 0 {
+            return Err(Error::Stream(id, ErrorCode::ProtocolError));
+        }
+
+        // update for closed stream is ignored
+        if let Some(stream) = self.streams.get_mut(&id) {
+            stream.send_window += increment;
+            if stream.send_window > MAX_WINDOW_SIZE {
+                return Err(Error::Stream(id, ErrorCode::FlowControlError));
+            }
+        }
+        return Ok(());
+    }
+
+    /**
+     * Run handler for completely received request and queue response.
+     */
+    fn dispatch(&mut self, id: u32) -> Result<(), Error> {
+        let (headers, body) = match self.streams.get_mut(&id) {
+            // RFC 9113 8.1.1, body shorter than content-length is malformed
+            Some(stream)
+                if stream
+                    .content_length
+                    .is_some_and(|length| length != stream.body.len()) =>
+            {
+                return Err(Error::Stream(id, ErrorCode::ProtocolError));
+            }
+            Some(stream) => (
+                std::mem::take(&mut stream.headers),
+                std::mem::take(&mut stream.body),
+            ),
+            None => return Ok(()),
+        };
+
+        let (status, fields, body) = self.handle(&headers, body);
+
+        let mut response_headers = vec![(":status".to_string(), status.to_string())];
+        response_headers.extend(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k.to_ascii_lowercase(), v))
+                .filter(|(k, _)| !CONNECTION_HEADERS.contains(&k.as_str())),
+        );
+
+        let mut block = vec![];
+        self.encoder.encode(&response_headers, &mut block);
+
+        let end_stream = body.as_ref().map(|b| b.is_empty()).unwrap_or(true);
+        self.write_header_block(id, &block, end_stream)?;
+
+        if end_stream {
+            self.streams.remove(&id);
+        } else if let Some(stream) = self.streams.get_mut(&id) {
+            stream.pending = body;
+        }
+
+        return Ok(());
+    }
+
+    fn handle(
+        &self,
+        headers: &[(String, String)],
+        body: Vec<u8>,
+    ) -> (i32, Vec<(String, String)>, Option<Vec<u8>>) {
+        let pseudo = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        };
+
+        let method = pseudo(":method").unwrap_or("GET");
+        let (path, param) = parse_url(pseudo(":path").unwrap_or("/"));
+
+        let mut header_map: HeaderMap = headers
+            .iter()
+            .filter(|(n, _)| !n.starts_with(':'))
+            .map(|(n, v)| (n.as_str(), v.as_str()))
+            .collect();
+        if let Some(authority) = pseudo(":authority")
+            && !header_map.contains("host")
+        {
+            header_map.append("host", authority);
+        }
+
+        let mut request = HttpRequest::new(
+            self.client_addr,
+            HttpMethod::parse(method),
+            HttpVersion::Http2,
+            path,
+            header_map,
+            param,
+            Box::new(Cursor::new(body)),
+        )
+        .with_local_addr(self.local_addr.clone())
+        .with_tls(self.tls.clone())
+        .with_proxy_header(self.proxy_header.clone())
+        .with_trusted_proxies(self.http2.http1.trusted_proxies());
+
+        let mut response = HttpResponse::from_request(&request, Box::new(std::io::sink()))
+            .with_error_pages(self.http2.http1.error_pages());
+        response.set_header(&server("server_rs"));
+
+        // never streaming, a panic is answered 500
+        self.http2.http1.handle(&mut request, &mut response);
+
+        return response.into_parts();
+    }
+
+    fn write_header_block(&mut self, id: u32, block: &[u8], end_stream: bool) -> Result<(), Error> {
+        let max = self.peer.max_frame_size as usize;
+        let mut chunks = block.chunks(max).peekable();
+        let end_stream_flag = if end_stream { FLAG_END_STREAM } else { 0 };
+
+        let first = chunks.next().unwrap_or(&[]);
+        let end_headers = if chunks.peek().is_none() {
+            FLAG_END_HEADERS
+        } else {
+            0
+        };
+        self.write_frame(FrameType::Headers, end_stream_flag | end_headers, id, first)?;
+
+        while let Some(chunk) = chunks.next() {
+            let end_headers = if chunks.peek().is_none() {
+                FLAG_END_HEADERS
+            } else {
+                0
+            };
+            self.write_frame(FrameType::Continuation, end_headers, id, chunk)?;
+        }
+
+        return Ok(());
+    }
+
+    /**
+     * Send queued response bodies as much as flow control windows allow.
+     */
+    fn send_pending(&mut self) -> Result<(), Error> {
+        let ids: Vec<u32> = self
+            .streams
+            .iter()
+            .filter(|(_, s)| s.pending.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in ids {
+            while let Some(stream) = self.streams.get_mut(&id) {
+                let data = match &stream.pending {
+                    Some(data) => data,
+                    None => break,
+                };
+
+                let remains = data.len() - stream.sent;
+                let size = remains
+                    .min(self.peer.max_frame_size as usize)
+                    .min(self.send_window.max(0) as usize)
+                    .min(stream.send_window.max(0) as usize);
+                if size == 0 {
+                    break;
+                }
+
+                let chunk = data[stream.sent..stream.sent + size].to_vec();
+                stream.sent += size;
+                stream.send_window -= size as i64;
+                self.send_window -= size as i64;
+
+                let done = size == remains;
+                let flags = if done { FLAG_END_STREAM } else { 0 };
+                self.write_frame(FrameType::Data, flags, id, &chunk)?;
+
+                if done {
+                    self.streams.remove(&id);
+                    break;
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /**
+     * Reset streams open past their deadline, a client sending frames all the time must not keep them forever.
+     */
+    fn reset_expired(&mut self) -> Result<(), Error> {
+        let now = Instant::now();
+        let expired: Vec<u32> = self
+            .streams
+            .iter()
+            .filter(|(_, s)| s.deadline.is_some_and(|d| d <= now))
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            log::debug!("HTTP/2 stream {id} timed out");
+            self.streams.remove(&id);
+            self.write_frame(
+                FrameType::RstStream,
+                0,
+                id,
+                &ErrorCode::Cancel.code().to_be_bytes(),
+            )?;
+        }
+        return Ok(());
+    }
+
+    fn goaway(&mut self, code: ErrorCode) -> Result<(), Error> {
+        let mut payload = self.last_stream_id.to_be_bytes().to_vec();
+        payload.extend_from_slice(&code.code().to_be_bytes());
+        self.write_frame(FrameType::GoAway, 0, 0, &payload)?;
+        return Ok(());
+    }
+
+    fn write_frame(
+        &mut self,
+        kind: FrameType,
+        flags: u8,
+        stream_id: u32,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        log::trace!(
+            ">> {:?} flags:{:#x} stream:{} len:{}",
+            kind,
+            flags,
+            stream_id,
+            payload.len()
+        );
+        self.written += write_frame(&mut self.writer, kind, flags, stream_id, payload)?;
+        return Ok(());
+    }
+}
+
+/**
+ * Request header of HTTP/2 must be lower case, pseudo headers come first,
+ * and HTTP/1 connection headers are not allowed (RFC 9113 8.2, 8.3).
+ */
+/**
+ * Check request header list is well formed (RFC 9113 8.2, 8.3) and return its content-length.
+ */
+fn validate_request_headers(headers: &[(String, String)]) -> Result<Option<usize>, ErrorCode> {
+    let mut regular_seen = false;
+    let mut pseudo_seen: Vec<&str> = vec![];
+    let mut content_length = None;
+
+    for (name, value) in headers {
+        if name.bytes().any(|b| b.is_ascii_uppercase()) {
+            return Err(ErrorCode::ProtocolError);
+        }
+        // would split or cut the field once written as HTTP/1
+        if value.bytes().any(|b| matches!(b, b'\r' | b'\n' | b'\0')) {
+            return Err(ErrorCode::ProtocolError);
+        }
+
+        if let Some(pseudo) = name.strip_prefix(':') {
+            if regular_seen || pseudo_seen.contains(&pseudo) {
+                return Err(ErrorCode::ProtocolError);
+            }
+            if !["method", "scheme", "path", "authority"].contains(&pseudo) {
+                return Err(ErrorCode::ProtocolError);
+            }
+            if pseudo == "path" && value.is_empty() {
+                return Err(ErrorCode::ProtocolError);
+            }
+            pseudo_seen.push(pseudo);
+            continue;
+        }
+
+        regular_seen = true;
+        if CONNECTION_HEADERS.contains(&name.as_str()) {
+            return Err(ErrorCode::ProtocolError);
+        }
+        if name == "te" && value != "trailers" {
+            return Err(ErrorCode::ProtocolError);
+        }
+        if name == "content-length" {
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ErrorCode::ProtocolError);
+            }
+            let length = value.parse().map_err(|_| ErrorCode::ProtocolError)?;
+            if content_length.is_some_and(|other| other != length) {
+                return Err(ErrorCode::ProtocolError);
+            }
+            content_length = Some(length);
+        }
+    }
+
+    let is_connect = headers
+        .iter()
+        .any(|(n, v)| n == ":method" && v == "CONNECT");
+    let required: &[&str] = if is_connect {
+        &["method", "authority"]
+    } else {
+        &["method", "scheme", "path"]
+    };
+    if required.iter().any(|r| !pseudo_seen.contains(r)) {
+        return Err(ErrorCode::ProtocolError);
+    }
+
+    return Ok(content_length);
+}
+
+fn has_token(value: &str, token: &str) -> bool {
+    return value
+        .split(',')
+        .any(|t| t.trim().eq_ignore_ascii_case(token));
+}
+
+/**
+ * SETTINGS payload of `HTTP2-Settings` when request asks upgrade to h2c (RFC 7540 3.2).
+ * Request with body is not upgraded, it is served by HTTP/1.
+ */
+fn upgrade_settings(head: &RequestHead) -> Option<Vec<u8>> {
+    if head.version() != "HTTP/1.1" {
+        return None;
+    }
+
+    let mut upgrade = false;
+    let mut connection = "";
+    let mut settings = None;
+    for (name, value) in head.fields() {
+        if name.eq_ignore_ascii_case("upgrade") {
+            upgrade |= has_token(value, "h2c");
+        } else if name.eq_ignore_ascii_case("connection") {
+            connection = value;
+        } else if name.eq_ignore_ascii_case("http2-settings") {
+            if settings.is_some() {
+                return None;
+            }
+            settings = Some(value);
+        } else if name.eq_ignore_ascii_case("transfer-encoding")
+            || (name.eq_ignore_ascii_case("content-length") && value != "0")
+        {
+            return None;
+        }
+    }
+
+    if !upgrade || !has_token(connection, "upgrade") || !has_token(connection, "http2-settings") {
+        return None;
+    }
+
+    return base64::decode(settings?);
+}
+
+/**
+ * HTTP/1 request line and header of upgrade request as HTTP/2 header list.
+ */
+fn upgrade_headers(head: &RequestHead) -> Vec<(String, String)> {
+    let mut res = vec![
+        (":method".to_string(), head.method().to_string()),
+        (":scheme".to_string(), "http".to_string()),
+        (":path".to_string(), head.target().to_string()),
+    ];
+
+    for (name, value) in head.fields() {
+        let name = name.to_ascii_lowercase();
+        if name == "host" {
+            res.insert(3, (":authority".to_string(), value.to_string()));
+        } else if !CONNECTION_HEADERS.contains(&name.as_str()) && name != "http2-settings" {
+            res.push((name, value.to_string()));
+        }
+    }
+
+    return res;
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::http::{
+        handler::Handler,
+        http::Http1,
+        http2::{
+            error::ErrorCode,
+            frame::{
+                FLAG_END_HEADERS, FLAG_END_STREAM, Frame, FrameReader, FrameType, PREFACE,
+                read_u32, write_frame,
+            },
+            hpack::Encoder,
+            http2::{Http2, upgrade_headers, upgrade_settings, validate_request_headers},
+        },
+        limit::Limits,
+        parser::RequestHead,
+        request::HttpRequest,
+        response::HttpResponse,
+        test_client::TestClient,
+    };
+
+    struct EmptyHandler;
+
+    impl Handler for EmptyHandler {
+        fn handle(&self, _: &mut HttpRequest, _: &mut HttpResponse) {}
+    }
+
+    fn pairs(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        return headers
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+    }
+
+    fn head(lines: &[&str]) -> RequestHead {
+        let raw = format!("{}\r\n\r\n", lines.join("\r\n"));
+        let mut head = RequestHead::default();
+        head.read(&mut raw.as_bytes(), &Limits::default(), 8192)
+            .unwrap();
+        return head;
+    }
+
+    #[test]
+    fn test_validate_request_headers() {
+        let valid = pairs(&[(":method", "GET"), (":scheme", "http"), (":path", "/")]);
+        assert_eq!(validate_request_headers(&valid), Ok(None));
+
+        let missing_path = pairs(&[(":method", "GET"), (":scheme", "http")]);
+        assert_eq!(
+            validate_request_headers(&missing_path),
+            Err(ErrorCode::ProtocolError)
+        );
+
+        let pseudo_after_regular = pairs(&[
+            (":method", "GET"),
+            (":scheme", "http"),
+            ("accept", "*/*"),
+            (":path", "/"),
+        ]);
+        assert!(validate_request_headers(&pseudo_after_regular).is_err());
+
+        let upper_case = pairs(&[
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":path", "/"),
+            ("Accept", "*/*"),
+        ]);
+        assert!(validate_request_headers(&upper_case).is_err());
+
+        let connection = pairs(&[
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":path", "/"),
+            ("connection", "keep-alive"),
+        ]);
+        assert!(validate_request_headers(&connection).is_err());
+
+        for value in ["a\r\nx-injected: 1", "a\nb", "a\0b"] {
+            let control = pairs(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                ("x-value", value),
+            ]);
+            assert_eq!(
+                validate_request_headers(&control),
+                Err(ErrorCode::ProtocolError),
+                "{value:?}"
+            );
+        }
+        let control_path = pairs(&[(":method", "GET"), (":scheme", "http"), (":path", "/\r\n")]);
+        assert!(validate_request_headers(&control_path).is_err());
+
+        let length = pairs(&[
+            (":method", "POST"),
+            (":scheme", "http"),
+            (":path", "/"),
+            ("content-length", "5"),
+        ]);
+        assert_eq!(validate_request_headers(&length), Ok(Some(5)));
+        for values in [&["-1"][..], &["5, 5"], &[""], &["5", "6"]] {
+            let mut invalid = pairs(&[(":method", "POST"), (":scheme", "http"), (":path", "/")]);
+            invalid.extend(
+                values
+                    .iter()
+                    .map(|v| ("content-length".to_string(), v.to_string())),
+            );
+            assert!(validate_request_headers(&invalid).is_err(), "{values:?}");
+        }
+    }
+
+    /**
+     * Response frames to a POST declaring content-length 5 and sending `data` with END_STREAM.
+     */
+    fn post_with_length(data: &[u8]) -> Vec<Frame> {
+        let headers = pairs(&[
+            (":method", "POST"),
+            (":scheme", "http"),
+            (":path", "/"),
+            ("content-length", "5"),
+        ]);
+        let mut block = vec![];
+        Encoder.encode(&headers, &mut block);
+
+        let mut raw = PREFACE.to_vec();
+        write_frame(&mut raw, FrameType::Settings, 0, 0, &[]).unwrap();
+        write_frame(&mut raw, FrameType::Headers, FLAG_END_HEADERS, 1, &block).unwrap();
+        write_frame(&mut raw, FrameType::Data, FLAG_END_STREAM, 1, data).unwrap();
+        let http2 = Http2::new(Http1::new(8192, EmptyHandler))
+            .with_idle_timeout(Duration::from_millis(100));
+        let received = TestClient::new(http2).exchange(&raw);
+
+        let mut frames = FrameReader::new(16384, false);
+        let mut reader = &received[..];
+        let mut res = vec![];
+        while let Ok(Some(frame)) = frames.read(&mut reader) {
+            res.push(frame);
+        }
+        return res;
+    }
+
+    #[test]
+    fn test_content_length() {
+        let frames = post_with_length(b"12345");
+        assert!(
+            frames
+                .iter()
+                .any(|f| f.kind == FrameType::Headers && f.stream_id == 1)
+        );
+        assert!(!frames.iter().any(|f| f.kind == FrameType::RstStream));
+
+        for data in [&b"1234"[..], b"123456"] {
+            let frames = post_with_length(data);
+            let reset = frames
+                .iter()
+                .find(|f| f.kind == FrameType::RstStream)
+                .unwrap_or_else(|| panic!("{data:?}"));
+            assert_eq!(reset.stream_id, 1);
+            assert_eq!(read_u32(&reset.payload), ErrorCode::ProtocolError.code());
+            assert!(!frames.iter().any(|f| f.kind == FrameType::Headers));
+        }
+    }
+
+    #[test]
+    fn test_upgrade_settings() {
+        let upgrade = head(&[
+            "GET / HTTP/1.1",
+            "Host: localhost",
+            "Connection: Upgrade, HTTP2-Settings",
+            "Upgrade: h2c",
+            "HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA",
+        ]);
+        assert_eq!(upgrade_settings(&upgrade).map(|s| s.len()), Some(18));
+
+        let http10 = head(&[
+            "GET / HTTP/1.0",
+            "Connection: Upgrade, HTTP2-Settings",
+            "Upgrade: h2c",
+            "HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA",
+        ]);
+        assert_eq!(upgrade_settings(&http10), None);
+
+        let no_settings = head(&["GET / HTTP/1.1", "Connection: Upgrade", "Upgrade: h2c"]);
+        assert_eq!(upgrade_settings(&no_settings), None);
+    }
+
+    #[test]
+    fn test_upgrade_headers() {
+        let headers = upgrade_headers(&head(&[
+            "GET /path?a=b HTTP/1.1",
+            "Host: localhost",
+            "Connection: Upgrade, HTTP2-Settings",
+            "Upgrade: h2c",
+            "HTTP2-Settings: AAMAAABkAARAAAAAAAIAAAAA",
+            "Accept: */*",
+        ]));
+
+        assert_eq!(
+            headers,
+            pairs(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/path?a=b"),
+                (":authority", "localhost"),
+                ("accept", "*/*"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_stream_timeout() {
+        let http2 = Http2::new(Http1::new(8192, EmptyHandler))
+            .with_stream_timeout(Duration::from_millis(200))
+            .with_idle_timeout(Duration::from_millis(200));
+
+        // request body never comes, the client stays connected
+        let mut raw = PREFACE.to_vec();
+        write_frame(&mut raw, FrameType::Settings, 0, 0, &[]).unwrap();
+        write_frame(
+            &mut raw,
+            FrameType::Headers,
+            FLAG_END_HEADERS,
+            1,
+            &[0x82, 0x86, 0x84],
+        )
+        .unwrap();
+        let received = TestClient::new(http2).exchange(&raw);
+
+        let mut frames = FrameReader::new(16384, false);
+        let mut reader = &received[..];
+        let mut kinds = vec![];
+        while let Ok(Some(frame)) = frames.read(&mut reader) {
+            if frame.kind == FrameType::RstStream {
+                assert_eq!(frame.stream_id, 1);
+                assert_eq!(read_u32(&frame.payload), ErrorCode::Cancel.code());
+            }
+            kinds.push(frame.kind);
+        }
+        assert!(kinds.contains(&FrameType::RstStream), "{kinds:?}");
+        // idle timeout closes the connection once the stream is gone
+        assert_eq!(kinds.last(), Some(&FrameType::GoAway));
+    }
+}
@@ -0,0 +1,5 @@
+mod error;
+mod frame;
+mod hpack;
+pub mod http2;
+mod huffman;
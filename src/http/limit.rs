@@ -0,0 +1,132 @@
+use std::{
+    cell::Cell,
+    fmt::Display,
+    io::{Error, ErrorKind, Read},
+    rc::Rc,
+};
+
+use crate::http::value::HttpResponseCode;
+
+/**
+ * Size limits of a request, each answered with its own status when exceeded.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    // request line including method and version, 414 beyond
+    pub request_line: usize,
+    // header fields after the request line, 431 beyond
+    pub header_count: usize,
+    // one header field line, 431 beyond
+    pub header_field: usize,
+    // request body, 413 beyond, 0 disables
+    pub body: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        return Self {
+            request_line: 8192,
+            header_count: 100,
+            header_field: 8192,
+            body: 1024 * 1024,
+        };
+    }
+}
+
+/**
+ * Which limit a request exceeded.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    RequestLine,
+    HeaderCount,
+    HeaderField,
+    // the whole header, `max_header_length` of `Http1`
+    Header,
+    Body,
+}
+
+impl Limit {
+    pub fn response_code(&self) -> HttpResponseCode {
+        return match self {
+            Limit::RequestLine => HttpResponseCode::UriTooLong,
+            Limit::HeaderCount | Limit::HeaderField | Limit::Header => {
+                HttpResponseCode::RequestHeaderFieldsTooLarge
+            }
+            Limit::Body => HttpResponseCode::PayloadTooLarge,
+        };
+    }
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.write_str(match self {
+            Limit::RequestLine => "request line too long",
+            Limit::HeaderCount => "too many header fields",
+            Limit::HeaderField => "header field too large",
+            Limit::Header => "header too large",
+            Limit::Body => "body too large",
+        });
+    }
+}
+
+/**
+ * Body reader failing once more than `max` bytes are read, for bodies without Content-Length.
+ * The failure stays visible through `exceeded`, to answer 413.
+ */
+pub struct BodyLimitReader<R: Read> {
+    inner: R,
+    left: u64,
+    exceeded: Rc<Cell<bool>>,
+}
+
+impl<R: Read> BodyLimitReader<R> {
+    pub fn new(inner: R, max: u64) -> Self {
+        return Self {
+            inner,
+            left: max,
+            exceeded: Rc::new(Cell::new(false)),
+        };
+    }
+
+    pub fn exceeded(&self) -> Rc<Cell<bool>> {
+        return self.exceeded.clone();
+    }
+}
+
+impl<R: Read> Read for BodyLimitReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // one byte over the limit tells a body just at the limit from a larger one
+        let max = buf.len().min(self.left.saturating_add(1) as usize);
+        let n = self.inner.read(&mut buf[..max])?;
+        if n as u64 > self.left {
+            self.exceeded.set(true);
+            return Err(Error::new(ErrorKind::InvalidData, Limit::Body.to_string()));
+        }
+        self.left -= n as u64;
+        return Ok(n);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use crate::http::limit::BodyLimitReader;
+
+    #[test]
+    fn test_body_limit() {
+        let mut body = String::new();
+        let mut reader = BodyLimitReader::new(Cursor::new("0123456789"), 10);
+        reader.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "0123456789");
+        assert!(!reader.exceeded().get());
+
+        let mut reader = BodyLimitReader::new(Cursor::new("0123456789a"), 10);
+        assert!(reader.read_to_string(&mut body).is_err());
+        assert!(reader.exceeded().get());
+    }
+}
@@ -1,6 +1,19 @@
+pub mod body;
+pub mod error_page;
+pub mod event;
+pub mod forwarded;
 pub mod handler;
 pub mod header;
+pub mod header_map;
 pub mod http;
+pub mod http2;
+pub mod limit;
+pub mod parser;
 pub mod request;
 pub mod response;
+pub mod sse;
+#[cfg(test)]
+pub mod test_client;
+pub mod timeout;
+pub mod typed;
 pub mod value;
@@ -0,0 +1,941 @@
+use std::{
+    cell::{Cell, RefCell},
+    io::{BufRead, ErrorKind, Read},
+    ops::Range,
+    rc::Rc,
+};
+
+use crate::http::limit::{Limit, Limits};
+
+/**
+ * Request line split at its two single spaces.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestLine<'a> {
+    pub method: &'a str,
+    pub target: &'a str,
+    pub version: &'a str,
+}
+
+/**
+ * How the body of a request is delimited.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyLength {
+    None,
+    Length(u64),
+    // Transfer-Encoding ending with chunked, the length is known only while reading
+    Chunked,
+}
+
+/**
+ * `method SP request-target SP HTTP-version` of RFC 9112, without CRLF.
+ * Parsers here refuse anything a proxy in front of us may read differently instead of repairing it,
+ * so both agree where a request ends.
+ */
+pub fn parse_request_line(line: &[u8]) -> Result<RequestLine<'_>, &'static str> {
+    let line = std::str::from_utf8(line).map_err(|_| "invalid request line")?;
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err("request line is not three parts");
+    };
+
+    if !is_token(method.as_bytes()) {
+        return Err("invalid method");
+    }
+    if !is_request_target(target.as_bytes()) {
+        return Err("invalid request target");
+    }
+    if !is_http_version(version.as_bytes()) {
+        return Err("invalid HTTP version");
+    }
+
+    return Ok(RequestLine {
+        method,
+        target,
+        version,
+    });
+}
+
+/**
+ * `field-name ":" OWS field-value OWS`, without CRLF. The value is returned without the surrounding whitespace.
+ * One pass over the name up to the colon, one over the value.
+ */
+pub fn parse_field_line(line: &[u8]) -> Result<(&str, &str), &'static str> {
+    let colon = line
+        .iter()
+        .position(|b| CLASSES[*b as usize] & TCHAR == 0)
+        .unwrap_or(line.len());
+    match line.get(colon) {
+        Some(b':') if colon > 0 => {}
+        Some(b' ' | b'\t') if colon == 0 => return Err("obsolete line folding"),
+        Some(b' ' | b'\t') => return Err("whitespace before colon"),
+        Some(b':') | Some(_) => return Err("invalid header field name"),
+        None => return Err("header field without colon"),
+    }
+
+    // OWS is SP and HTAB only
+    let value = &line[colon + 1..];
+    let value = match value.iter().position(|b| *b != b' ' && *b != b'\t') {
+        Some(start) => {
+            let end = value.iter().rposition(|b| *b != b' ' && *b != b'\t');
+            &value[start..=end.unwrap_or(start)]
+        }
+        // still a slice of the line, `RequestHead` keeps its position
+        None => &value[value.len()..],
+    };
+    if !is_field_value(value) {
+        return Err("invalid header field value");
+    }
+
+    // a token is ASCII, the value is checked only for its obs-text
+    let name = std::str::from_utf8(&line[..colon]).map_err(|_| "invalid header field name")?;
+    let value = std::str::from_utf8(value).map_err(|_| "header field value is not UTF-8")?;
+    return Ok((name, value));
+}
+
+/**
+ * Body framing of the header fields. Duplicate or malformed Content-Length, Content-Length with Transfer-Encoding
+ * and an empty or malformed Transfer-Encoding are refused, each of them is a way to smuggle a request.
+ * Any transfer coding but a single `chunked` is not implemented, its body could not be read.
+ */
+pub fn body_length<'a>(
+    fields: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Result<BodyLength, HeadError> {
+    let mut length = None;
+    // codings of every Transfer-Encoding field line, one list
+    let mut codings = None;
+    let mut chunked = false;
+    for (name, value) in fields {
+        if name.eq_ignore_ascii_case("content-length") {
+            if length.is_some() {
+                return Err(HeadError::Invalid("duplicate Content-Length"));
+            }
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(HeadError::Invalid("invalid Content-Length"));
+            }
+            length = Some(
+                value
+                    .parse()
+                    .map_err(|_| HeadError::Invalid("invalid Content-Length"))?,
+            );
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            let mut field_codings = 0;
+            for coding in value.split(',') {
+                let coding = coding.trim_matches([' ', '\t']);
+                if coding.is_empty() {
+                    continue;
+                }
+                let coding_name = coding.split(';').next().unwrap_or_default();
+                if !is_token(coding_name.trim_end_matches([' ', '\t']).as_bytes()) {
+                    return Err(HeadError::Invalid("invalid Transfer-Encoding"));
+                }
+                field_codings += 1;
+                chunked = coding.eq_ignore_ascii_case("chunked");
+            }
+            if field_codings == 0 {
+                return Err(HeadError::Invalid("empty Transfer-Encoding"));
+            }
+            codings = Some(codings.unwrap_or(0) + field_codings);
+        }
+    }
+
+    return match (length, codings) {
+        (Some(_), Some(_)) => Err(HeadError::Invalid("Content-Length with Transfer-Encoding")),
+        (Some(length), None) => Ok(BodyLength::Length(length)),
+        (None, Some(1)) if chunked => Ok(BodyLength::Chunked),
+        (None, Some(_)) => Err(HeadError::Unsupported("transfer coding other than chunked")),
+        (None, None) => Ok(BodyLength::None),
+    };
+}
+
+/**
+ * Failure reading a request head.
+ */
+#[derive(Debug)]
+pub enum HeadError {
+    Read(std::io::Error),
+    // connection closed before any byte of the request
+    Eof,
+    // connection closed in the middle of the head
+    Incomplete,
+    TooLarge(Limit),
+    Invalid(&'static str),
+    // framing the server can not read, 501
+    Unsupported(&'static str),
+}
+
+thread_local! {
+    static HEAD: RefCell<RequestHead> = RefCell::new(RequestHead::default());
+}
+
+/**
+ * Run `f` with the head buffer of this thread, so every connection a worker serves reuses one buffer.
+ * A nested call, e.g. a handler driving another `Http1`, gets a buffer of its own.
+ */
+pub fn with_request_head<R>(f: impl FnOnce(&mut RequestHead) -> R) -> R {
+    return HEAD.with(|head| match head.try_borrow_mut() {
+        Ok(mut head) => f(&mut head),
+        Err(_) => f(&mut RequestHead::default()),
+    });
+}
+
+/**
+ * Head of one request, read into a buffer and parsed line by line as it arrives.
+ * Request line parts and header fields are ranges of the buffer, so once the buffer grew to the usual head size
+ * reading another head allocates nothing. Lines are checked by `parse_request_line` and `parse_field_line`,
+ * the framing by `body_length`.
+ */
+#[derive(Debug, Default)]
+pub struct RequestHead {
+    buf: Vec<u8>,
+    method: Range<usize>,
+    target: Range<usize>,
+    version: Range<usize>,
+    // name and value of each field
+    fields: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl RequestHead {
+    /**
+     * Read up to the empty line ending the head and no further, the body stays in `reader`.
+     * Every line is bound by its limit in `limits` and the whole head by `max_length`, before it is buffered.
+     */
+    pub fn read(
+        &mut self,
+        reader: &mut impl BufRead,
+        limits: &Limits,
+        max_length: usize,
+    ) -> Result<(), HeadError> {
+        self.clear();
+        let mut line_start = 0;
+        loop {
+            let request_line = self.method.is_empty();
+            let (line_limit, limit) = if request_line {
+                (limits.request_line, Limit::RequestLine)
+            } else {
+                (limits.header_field, Limit::HeaderField)
+            };
+            // with CRLF, a request line over the whole head is still a long request line
+            let left = max_length.saturating_sub(line_start);
+            let (max, limit) = if line_limit + 2 <= left || request_line {
+                ((line_limit + 2).min(left), limit)
+            } else {
+                (left, Limit::Header)
+            };
+
+            let available = match reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(HeadError::Read(e)),
+            };
+            if available.is_empty() {
+                return Err(if self.buf.is_empty() {
+                    HeadError::Eof
+                } else {
+                    HeadError::Incomplete
+                });
+            }
+
+            let room = max - (self.buf.len() - line_start);
+            let chunk = &available[..available.len().min(room)];
+            let Some(lf) = memchr::memchr(b'\n', chunk) else {
+                let length = chunk.len();
+                self.buf.extend_from_slice(chunk);
+                reader.consume(length);
+                if length == room {
+                    return Err(HeadError::TooLarge(limit));
+                }
+                continue;
+            };
+            self.buf.extend_from_slice(&chunk[..=lf]);
+            reader.consume(lf + 1);
+
+            let line = line_start..self.buf.len();
+            line_start = self.buf.len();
+            if self.end_line(line, limits)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // parse a complete line, true at the end of head
+    fn end_line(&mut self, line: Range<usize>, limits: &Limits) -> Result<bool, HeadError> {
+        // bare LF is refused, a proxy may not end the line there
+        if !self.buf[line.clone()].ends_with(b"\r\n") {
+            return Err(HeadError::Invalid("line not ending with CRLF"));
+        }
+        let content = line.start..line.end - 2;
+
+        if content.is_empty() {
+            // empty lines before the request line are ignored, RFC 9112 2.2
+            if self.method.is_empty() {
+                return Ok(false);
+            }
+            body_length(self.fields())?;
+            return Ok(true);
+        }
+
+        let line = &self.buf[content.clone()];
+        log::trace!("<< {}", String::from_utf8_lossy(line));
+        let range = |part: &str| {
+            let start = part.as_ptr() as usize - line.as_ptr() as usize + content.start;
+            return start..start + part.len();
+        };
+
+        if self.method.is_empty() {
+            let line = parse_request_line(line).map_err(HeadError::Invalid)?;
+            (self.method, self.target, self.version) =
+                (range(line.method), range(line.target), range(line.version));
+        } else {
+            let (name, value) = parse_field_line(line).map_err(HeadError::Invalid)?;
+            let field = (range(name), range(value));
+            self.fields.push(field);
+            if self.fields.len() > limits.header_count {
+                return Err(HeadError::TooLarge(Limit::HeaderCount));
+            }
+        }
+        return Ok(false);
+    }
+
+    fn clear(&mut self) {
+        self.buf.clear();
+        self.method = 0..0;
+        self.target = 0..0;
+        self.version = 0..0;
+        self.fields.clear();
+    }
+
+    // every range is a `&str` returned by `parse_request_line` or `parse_field_line` for these bytes
+    fn str(&self, range: &Range<usize>) -> &str {
+        return std::str::from_utf8(&self.buf[range.clone()]).unwrap_or_default();
+    }
+
+    /**
+     * Bytes of the head including its empty line.
+     */
+    pub fn len(&self) -> usize {
+        return self.buf.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.buf.is_empty();
+    }
+
+    pub fn method(&self) -> &str {
+        return self.str(&self.method);
+    }
+
+    pub fn target(&self) -> &str {
+        return self.str(&self.target);
+    }
+
+    pub fn version(&self) -> &str {
+        return self.str(&self.version);
+    }
+
+    /**
+     * Header fields in the order of the request.
+     */
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        return self
+            .fields
+            .iter()
+            .map(|(name, value)| (self.str(name), self.str(value)));
+    }
+
+    /**
+     * First value of the field, the name is case-insensitive.
+     */
+    pub fn field(&self, name: &str) -> Option<&str> {
+        return self
+            .fields()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v);
+    }
+}
+
+// chunk size line, or all trailer lines together
+const MAX_CHUNK_LINE: usize = 8192;
+
+/**
+ * Decoder of a chunked request body, reading from `inner` up to the end of the trailer section and no further.
+ * Chunk extensions are skipped and trailer fields checked like header fields, then dropped.
+ * A malformed body fails the read with `InvalidData` and stays visible through `invalid`, to answer 400.
+ * A body cut short fails with `UnexpectedEof`.
+ */
+pub struct ChunkedReader<R: BufRead> {
+    inner: R,
+    // data left of the current chunk
+    left: u64,
+    // a chunk was read, its CRLF comes before the next size line
+    started: bool,
+    done: bool,
+    line: Vec<u8>,
+    invalid: Rc<Cell<bool>>,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    pub fn new(inner: R) -> Self {
+        return Self {
+            inner,
+            left: 0,
+            started: false,
+            done: false,
+            line: vec![],
+            invalid: Rc::new(Cell::new(false)),
+        };
+    }
+
+    pub fn invalid(&self) -> Rc<Cell<bool>> {
+        return self.invalid.clone();
+    }
+
+    fn fail(&self, msg: &'static str) -> std::io::Error {
+        self.invalid.set(true);
+        return std::io::Error::new(ErrorKind::InvalidData, msg);
+    }
+
+    // next line without CRLF into `line`, at most `max` bytes with it
+    fn read_line(&mut self, max: usize) -> std::io::Result<()> {
+        self.line.clear();
+        loop {
+            let available = match self.inner.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if available.is_empty() {
+                return Err(std::io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "chunked body cut short",
+                ));
+            }
+            let room = max - self.line.len();
+            let chunk = &available[..available.len().min(room)];
+            let Some(lf) = memchr::memchr(b'\n', chunk) else {
+                let length = chunk.len();
+                self.line.extend_from_slice(chunk);
+                self.inner.consume(length);
+                if length == room {
+                    return Err(self.fail("chunk line too long"));
+                }
+                continue;
+            };
+            self.line.extend_from_slice(&chunk[..=lf]);
+            self.inner.consume(lf + 1);
+            if !self.line.ends_with(b"\r\n") {
+                return Err(self.fail("line not ending with CRLF"));
+            }
+            self.line.truncate(self.line.len() - 2);
+            return Ok(());
+        }
+    }
+
+    // `chunk-size [ chunk-ext ]`, the extensions are not used
+    fn read_size(&mut self) -> std::io::Result<u64> {
+        self.read_line(MAX_CHUNK_LINE)?;
+        let digits = self
+            .line
+            .iter()
+            .take_while(|b| b.is_ascii_hexdigit())
+            .count();
+        let ext = &self.line[digits..];
+        let ext = &ext[ext
+            .iter()
+            .take_while(|b| **b == b' ' || **b == b'\t')
+            .count()..];
+        if digits == 0 || !(ext.is_empty() || ext[0] == b';') || !is_field_value(ext) {
+            return Err(self.fail("invalid chunk size"));
+        }
+        // ASCII hex digits
+        let digits = std::str::from_utf8(&self.line[..digits]).unwrap_or_default();
+        return u64::from_str_radix(digits, 16).map_err(|_| self.fail("invalid chunk size"));
+    }
+
+    fn read_trailers(&mut self) -> std::io::Result<()> {
+        let mut left = MAX_CHUNK_LINE;
+        loop {
+            self.read_line(left)?;
+            if self.line.is_empty() {
+                return Ok(());
+            }
+            left -= self.line.len() + 2;
+            if parse_field_line(&self.line).is_err() {
+                return Err(self.fail("invalid trailer field"));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.done {
+            return Ok(0);
+        }
+        if self.left == 0 {
+            if self.started {
+                self.read_line(MAX_CHUNK_LINE)?;
+                if !self.line.is_empty() {
+                    return Err(self.fail("chunk data longer than its size"));
+                }
+            }
+            self.started = true;
+            self.left = self.read_size()?;
+            if self.left == 0 {
+                self.read_trailers()?;
+                self.done = true;
+                return Ok(0);
+            }
+        }
+
+        let max = buf.len().min(self.left.min(usize::MAX as u64) as usize);
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "chunked body cut short",
+            ));
+        }
+        self.left -= n as u64;
+        return Ok(n);
+    }
+}
+
+// byte classes of the request head, one table lookup per byte
+const TCHAR: u8 = 1;
+const TARGET: u8 = 2;
+const FIELD_VALUE: u8 = 4;
+static CLASSES: [u8; 256] = classes();
+
+const fn classes() -> [u8; 256] {
+    let mut classes = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let b = i as u8;
+        // tchar of RFC 9110
+        if b.is_ascii_alphanumeric()
+            || matches!(b, b'!' | b'#'..=b'\'' | b'*' | b'+' | b'-' | b'.')
+            || matches!(b, b'^' | b'_' | b'`' | b'|' | b'~')
+        {
+            classes[i] |= TCHAR;
+        }
+        // characters of RFC 3986 in a request target, '%' is checked with its digits
+        if b.is_ascii_alphanumeric()
+            || matches!(
+                b,
+                b'-' | b'.' | b'_' | b'~' | b'!' | b'$' | b'&'..=b',' | b';' | b'='
+            )
+            || matches!(b, b':' | b'@' | b'/' | b'?' | b'[' | b']')
+        {
+            classes[i] |= TARGET;
+        }
+        // VCHAR, obs-text, SP and HTAB
+        if b == b'\t' || (b >= b' ' && b != 0x7f) {
+            classes[i] |= FIELD_VALUE;
+        }
+        i += 1;
+    }
+    return classes;
+}
+
+/**
+ * Visible characters, obs-text, SP and HTAB. CR and LF would end the field and start another field or message.
+ */
+pub(crate) fn is_field_value(bytes: &[u8]) -> bool {
+    // no early exit, so the check runs over many bytes at once
+    let invalid = bytes.iter().fold(false, |invalid, b| {
+        return invalid | ((*b < b' ' && *b != b'\t') || *b == 0x7f);
+    });
+    return !invalid;
+}
+
+pub(crate) fn is_token(bytes: &[u8]) -> bool {
+    return !bytes.is_empty() && bytes.iter().all(|b| CLASSES[*b as usize] & TCHAR != 0);
+}
+
+// origin-form, absolute-form, authority-form or asterisk-form
+fn is_request_target(bytes: &[u8]) -> bool {
+    if bytes.is_empty() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if CLASSES[bytes[i] as usize] & TARGET != 0 {
+            i += 1;
+            continue;
+        }
+        let hex = bytes.get(i + 1..i + 3);
+        if bytes[i] != b'%' || !hex.is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+            return false;
+        }
+        i += 3;
+    }
+    return true;
+}
+
+fn is_http_version(bytes: &[u8]) -> bool {
+    return bytes.len() == 8
+        && bytes.starts_with(b"HTTP/")
+        && bytes[5].is_ascii_digit()
+        && bytes[6] == b'.'
+        && bytes[7].is_ascii_digit();
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, ErrorKind, Read},
+        time::Instant,
+    };
+
+    use crate::http::{
+        header_map::HeaderMap,
+        limit::{Limit, Limits},
+        parser::{
+            BodyLength, ChunkedReader, HeadError, RequestHead, RequestLine, body_length,
+            parse_field_line, parse_request_line,
+        },
+    };
+
+    const REQUEST: &[u8] = b"GET /index.html?a=b HTTP/1.1\r\n\
+        Host: localhost:8080\r\n\
+        User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:130.0) Gecko/20100101 Firefox/130.0\r\n\
+        Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+        Accept-Language: en-US,en;q=0.5\r\n\
+        Accept-Encoding: gzip, deflate, br\r\n\
+        Connection: keep-alive\r\n\
+        Cookie: session=0123456789abcdef; theme=dark\r\n\
+        Upgrade-Insecure-Requests: 1\r\n\
+        Cache-Control: max-age=0\r\n\
+        \r\n\
+        body";
+
+    #[test]
+    fn test_request_head() {
+        // a byte at a time from the reader
+        let mut reader = BufReader::with_capacity(1, REQUEST);
+        let mut head = RequestHead::default();
+        head.read(&mut reader, &Limits::default(), 8192).unwrap();
+
+        assert_eq!(head.method(), "GET");
+        assert_eq!(head.target(), "/index.html?a=b");
+        assert_eq!(head.version(), "HTTP/1.1");
+        assert_eq!(head.fields().count(), 9);
+        assert_eq!(head.field("connection"), Some("keep-alive"));
+        assert_eq!(head.len(), REQUEST.len() - 4);
+
+        let mut body = String::new();
+        reader.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "body");
+
+        // reused for the next request
+        head.read(
+            &mut &b"\r\nPOST / HTTP/1.0\r\n\r\n"[..],
+            &Limits::default(),
+            8192,
+        )
+        .unwrap();
+        assert_eq!(head.method(), "POST");
+        assert_eq!(head.fields().count(), 0);
+
+        let read = |raw: &[u8], max_length| {
+            let mut head = RequestHead::default();
+            return head.read(&mut &raw[..], &Limits::default(), max_length);
+        };
+        assert!(matches!(read(b"", 8192), Err(HeadError::Eof)));
+        assert!(matches!(
+            read(b"GET / HTTP/1.1\r\nHost", 8192),
+            Err(HeadError::Incomplete)
+        ));
+        assert!(matches!(
+            read(b"GET / HTTP/1.1\nHost: a\n\n", 8192),
+            Err(HeadError::Invalid(_))
+        ));
+        assert!(matches!(
+            read(REQUEST, 64),
+            Err(HeadError::TooLarge(Limit::Header))
+        ));
+    }
+
+    // the header path before RequestHead: a String per line, checked when read and parsed again
+    // for the framing and for the request
+    // `Http1::read_header` of the baseline, the error type and `nth(0)` of clippy aside
+    fn baseline_read_header(
+        max_header_length: usize,
+        reader: &mut BufReader<Box<dyn Read + '_>>,
+    ) -> Result<(usize, Vec<String>), String> {
+        let mut res = vec![];
+        let mut readed = 0;
+        loop {
+            let mut buf = String::new();
+            let result = reader.read_line(&mut buf);
+            if let Err(err) = result {
+                return Err(format!("{}", err));
+            }
+            readed += result.unwrap();
+
+            if readed > max_header_length {
+                return Err("header size limit exceed".to_string());
+            }
+
+            while buf
+                .chars()
+                .next()
+                .map(|v| v.is_whitespace())
+                .unwrap_or(false)
+            {
+                buf.remove(0);
+            }
+
+            // head end
+            if buf.is_empty() {
+                break;
+            }
+
+            if !buf.ends_with("\r\n") {
+                return Err(format!("Invalid heaader {}", buf));
+            }
+
+            buf.remove(buf.len() - 1); // delete \n
+            buf.remove(buf.len() - 1); // delete \r
+
+            log::trace!("<< {}", buf);
+
+            res.push(buf);
+        }
+
+        if readed == 0 {
+            return Err("EOF".to_string());
+        }
+
+        return Ok((readed, res));
+    }
+
+    // request line split of `Http1::init_request` and `Http1::init_header` of the baseline
+    fn baseline_init_header(header: &[String]) -> (&str, &str, HashMap<&str, Vec<&str>>) {
+        let buf = &header[0];
+        let mut req_line = buf.split(" ");
+        let method = req_line.next().unwrap();
+        let path_query = req_line.next().unwrap();
+
+        let mut header_map: HashMap<&str, Vec<&str>> = HashMap::new();
+        for buf in header.iter().skip(1) {
+            let div_idx = match buf.find(':') {
+                Some(idx) => idx,
+                None => continue,
+            };
+
+            let (key, value) = buf.split_at(div_idx);
+
+            header_map
+                .entry(key.trim())
+                .or_default()
+                .push(value[1..].trim());
+        }
+
+        return (method, path_query, header_map);
+    }
+
+    /**
+     * Baseline head reading against `RequestHead`, both from a new `BufReader` as on every connection and up to
+     * the header map handlers get. Run with `cargo test --release bench_request_head -- --ignored --nocapture`.
+     */
+    #[test]
+    #[ignore]
+    fn bench_request_head() {
+        const ROUNDS: u32 = 200_000;
+        let limits = Limits::default();
+        let reader = || BufReader::new(Box::new(REQUEST) as Box<dyn Read>);
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            let (_, headers) = baseline_read_header(8192, &mut reader()).unwrap();
+            let (method, _, map) = baseline_init_header(&headers);
+            assert_eq!((method, map.len()), ("GET", 9));
+        }
+        let baseline = start.elapsed() / ROUNDS;
+
+        let mut head = RequestHead::default();
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            head.read(&mut reader(), &limits, 8192).unwrap();
+            let map: HeaderMap = head.fields().collect();
+            assert_eq!((head.method(), map.len()), ("GET", 9));
+        }
+        let borrowed = start.elapsed() / ROUNDS;
+
+        println!("baseline: {baseline:?}/request, RequestHead: {borrowed:?}/request");
+    }
+
+    #[test]
+    fn test_request_line() {
+        assert_eq!(
+            parse_request_line(b"GET /a%20b?c=d HTTP/1.1"),
+            Ok(RequestLine {
+                method: "GET",
+                target: "/a%20b?c=d",
+                version: "HTTP/1.1",
+            })
+        );
+        assert!(parse_request_line(b"PRI * HTTP/2.0").is_ok());
+        assert!(parse_request_line(b"CONNECT example.com:443 HTTP/1.1").is_ok());
+
+        for line in [
+            "GET  / HTTP/1.1",
+            "GET / HTTP/1.1 ",
+            "GET\t/ HTTP/1.1",
+            "GET /",
+            "G(T / HTTP/1.1",
+            "GET /a b HTTP/1.1",
+            "GET /<script> HTTP/1.1",
+            "GET /a#b HTTP/1.1",
+            "GET /%zz HTTP/1.1",
+            "GET /\u{7f} HTTP/1.1",
+            "GET / http/1.1",
+            "GET / HTTP/1.10",
+        ] {
+            assert!(parse_request_line(line.as_bytes()).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_field_line() {
+        assert_eq!(parse_field_line(b"Host:  a \t"), Ok(("Host", "a")));
+        assert_eq!(parse_field_line(b"X-Empty:"), Ok(("X-Empty", "")));
+        assert_eq!(
+            parse_field_line("X-Name: caf\u{e9}".as_bytes()),
+            Ok(("X-Name", "caf\u{e9}"))
+        );
+        assert!(parse_field_line(b"X-Name: caf\xe9").is_err());
+
+        for line in [
+            " folded",
+            "\tfolded: a",
+            "Host : a",
+            "Host\t: a",
+            "no colon",
+            ": a",
+            "Ho(st: a",
+            "Host: a\rb",
+            "Host: a\0b",
+        ] {
+            assert!(parse_field_line(line.as_bytes()).is_err(), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn test_body_length() {
+        let length = |fields: &[(&'static str, &'static str)]| {
+            return body_length(fields.iter().copied());
+        };
+
+        assert_eq!(length(&[]).unwrap(), BodyLength::None);
+        assert_eq!(
+            length(&[("content-length", "10")]).unwrap(),
+            BodyLength::Length(10)
+        );
+        assert_eq!(
+            length(&[("Transfer-Encoding", " Chunked, ")]).unwrap(),
+            BodyLength::Chunked
+        );
+
+        for fields in [
+            &[("Content-Length", "10"), ("Content-Length", "10")][..],
+            &[("Content-Length", "10"), ("content-length", "11")],
+            &[("Content-Length", "10, 10")],
+            &[("Content-Length", "+10")],
+            &[("Content-Length", "")],
+            &[("Content-Length", "99999999999999999999999")],
+            &[("Content-Length", "10"), ("Transfer-Encoding", "chunked")],
+            &[("Transfer-Encoding", "chunked"), ("Content-Length", "10")],
+            &[("Content-Length", "10"), ("Transfer-Encoding", "gzip")],
+            // an empty field still says the body is not framed by Content-Length
+            &[
+                ("Content-Length", "5"),
+                ("Transfer-Encoding", "chunked"),
+                ("Transfer-Encoding", ""),
+            ],
+            &[("Transfer-Encoding", "chunked"), ("Transfer-Encoding", ",")],
+            &[("Transfer-Encoding", "")],
+            &[("Transfer-Encoding", "chu nked")],
+        ] {
+            assert!(
+                matches!(length(fields), Err(HeadError::Invalid(_))),
+                "{fields:?}"
+            );
+        }
+
+        for fields in [
+            &[("Transfer-Encoding", "gzip, chunked")][..],
+            &[("Transfer-Encoding", "chunked, gzip")],
+            &[("Transfer-Encoding", "chunked, chunked")],
+            &[("Transfer-Encoding", "chunked;x=1")],
+            &[
+                ("Transfer-Encoding", "gzip"),
+                ("Transfer-Encoding", "chunked"),
+            ],
+        ] {
+            assert!(
+                matches!(length(fields), Err(HeadError::Unsupported(_))),
+                "{fields:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_chunked_reader() {
+        let decode = |body: &[u8]| {
+            let mut rest = body;
+            let mut reader = ChunkedReader::new(&mut rest);
+            let mut data = vec![];
+            let result = reader.read_to_end(&mut data).map(|_| data);
+            let invalid = reader.invalid().get();
+            return (result, invalid, rest.len());
+        };
+
+        // stops after the trailer section, the next request is left
+        let (result, invalid, left) =
+            decode(b"3\r\nabc\r\nA;name=\"v\"\r\n0123456789\r\n0\r\nX-Sum: 1\r\n\r\nGET");
+        assert_eq!(result.unwrap(), b"abc0123456789");
+        assert!(!invalid);
+        assert_eq!(left, 3);
+
+        for body in [
+            &b"3\r\nab"[..],
+            b"3\r\nabc\r\n",
+            b"0\r\n",
+            b"0\r\nX-Sum: 1\r\n",
+        ] {
+            let (result, invalid, _) = decode(body);
+            assert_eq!(
+                result.unwrap_err().kind(),
+                ErrorKind::UnexpectedEof,
+                "{body:?}"
+            );
+            assert!(!invalid);
+        }
+
+        for body in [
+            &b"x\r\nabc\r\n0\r\n\r\n"[..],
+            b"+3\r\nabc\r\n0\r\n\r\n",
+            b"3\nabc\r\n0\r\n\r\n",
+            b"3 x\r\nabc\r\n0\r\n\r\n",
+            b"2\r\nabc\r\n0\r\n\r\n",
+            b"10000000000000000\r\n",
+            b"0\r\n bad\r\n\r\n",
+        ] {
+            let (result, invalid, _) = decode(body);
+            assert_eq!(
+                result.unwrap_err().kind(),
+                ErrorKind::InvalidData,
+                "{body:?}"
+            );
+            assert!(invalid, "{body:?}");
+        }
+    }
+}
@@ -1,29 +1,40 @@
 use std::collections::HashMap;
 use std::io::Read;
-use std::net::SocketAddr;
 
-use crate::http::value::{HttpMethod, HttpVersion};
+use crate::{
+    http::{
+        forwarded::{Forwarded, TrustedProxies},
+        header_map::HeaderMap,
+        value::{HttpMethod, HttpVersion},
+    },
+    process::{address::Address, proxy::ProxyHeader},
+    tls::{ClientCertificate, TlsInfo},
+};
 
 #[allow(dead_code)]
 pub struct HttpRequest<'a> {
-    remote_addr: &'a SocketAddr,
+    remote_addr: &'a Address,
     method: HttpMethod,
     http_version: HttpVersion,
     path: String,
-    header: HashMap<&'a str, Vec<&'a str>>,
+    header: HeaderMap<'a>,
     param: HashMap<&'a str, Vec<&'a str>>,
     reader: Box<dyn Read + 'a>,
+    local_addr: Address,
+    tls: Option<TlsInfo>,
+    proxy_header: Option<ProxyHeader>,
+    forwarded: Option<Forwarded>,
     // TODO : 필요한건 나중에 추가
 }
 
 #[allow(dead_code)]
 impl<'a> HttpRequest<'a> {
     pub fn new(
-        remote_addr: &'a SocketAddr,
+        remote_addr: &'a Address,
         method: HttpMethod,
         http_version: HttpVersion,
         path: String,
-        header: HashMap<&'a str, Vec<&'a str>>,
+        header: HeaderMap<'a>,
         param: HashMap<&'a str, Vec<&'a str>>,
         reader: Box<dyn Read + 'a>,
     ) -> Self {
@@ -35,9 +46,23 @@ impl<'a> HttpRequest<'a> {
             header,
             param,
             reader,
+            local_addr: Address::Unnamed,
+            tls: None,
+            proxy_header: None,
+            forwarded: None,
         };
     }
 
+    pub fn with_local_addr(mut self, local_addr: Address) -> Self {
+        self.local_addr = local_addr;
+        return self;
+    }
+
+    pub fn with_tls(mut self, tls: Option<TlsInfo>) -> Self {
+        self.tls = tls;
+        return self;
+    }
+
     pub fn version(&self) -> HttpVersion {
         return self.http_version.clone();
     }
@@ -46,15 +71,68 @@ impl<'a> HttpRequest<'a> {
         return self.method.clone();
     }
 
-    pub fn remote_addr(&self) -> SocketAddr {
-        return *self.remote_addr;
+    pub fn remote_addr(&self) -> &Address {
+        return self.remote_addr;
+    }
+
+    /**
+     * Believe forwarding headers of the request when the peer is one of `trusted`.
+     */
+    pub fn with_trusted_proxies(mut self, trusted: &TrustedProxies) -> Self {
+        self.forwarded = Forwarded::resolve(self.remote_addr, &self.header, trusted);
+        return self;
+    }
+
+    /**
+     * Effective client address. It is `remote_addr` unless a trusted proxy forwarded the request for someone.
+     */
+    pub fn client_addr(&self) -> &Address {
+        return self
+            .forwarded
+            .as_ref()
+            .and_then(|f| f.client.as_ref())
+            .unwrap_or(self.remote_addr);
+    }
+
+    /**
+     * Effective scheme, `http` or `https` unless a trusted proxy said otherwise.
+     */
+    pub fn scheme(&self) -> &str {
+        if let Some(proto) = self.forwarded.as_ref().and_then(|f| f.proto.as_ref()) {
+            return proto;
+        }
+        return if self.tls.is_some() { "https" } else { "http" };
+    }
+
+    /**
+     * Effective host, forwarded one or `Host` header.
+     */
+    pub fn host(&self) -> Option<&str> {
+        if let Some(host) = self.forwarded.as_ref().and_then(|f| f.host.as_ref()) {
+            return Some(host);
+        }
+        return self.header.get("host");
+    }
+
+    /**
+     * Forwarding information when the peer is a trusted proxy.
+     */
+    pub fn forwarded(&self) -> Option<&Forwarded> {
+        return self.forwarded.as_ref();
+    }
+
+    /**
+     * Address the connection was accepted on.
+     */
+    pub fn local_addr(&self) -> &Address {
+        return &self.local_addr;
     }
 
     pub fn path(&self) -> &str {
         return self.path.as_str();
     }
 
-    pub fn header(&self) -> &HashMap<&'a str, Vec<&'a str>> {
+    pub fn header(&self) -> &HeaderMap<'a> {
         return &self.header;
     }
 
@@ -62,7 +140,41 @@ impl<'a> HttpRequest<'a> {
         return &self.param;
     }
 
-    pub fn reader(&self) -> &Box<dyn Read + 'a> {
-        return &self.reader;
+    pub fn with_proxy_header(mut self, proxy_header: Option<ProxyHeader>) -> Self {
+        self.proxy_header = proxy_header;
+        return self;
+    }
+
+    /**
+     * PROXY protocol header of the connection, `remote_addr` already is its source address.
+     */
+    pub fn proxy_header(&self) -> Option<&ProxyHeader> {
+        return self.proxy_header.as_ref();
+    }
+
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        return self.tls.as_ref();
+    }
+
+    /**
+     * Certificate the client presented and the CA bundle verified in mutual TLS.
+     */
+    pub fn client_certificate(&self) -> Option<&ClientCertificate> {
+        return self.tls.as_ref()?.client_certificate.as_ref();
+    }
+
+    /**
+     * Whether client authentication of the listener allows this request.
+     */
+    pub fn permitted(&self) -> bool {
+        return self
+            .tls
+            .as_ref()
+            .map(|tls| tls.permits(&self.path))
+            .unwrap_or(true);
+    }
+
+    pub fn reader(&mut self) -> &mut (dyn Read + 'a) {
+        return self.reader.as_mut();
     }
 }
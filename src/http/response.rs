@@ -1,24 +1,40 @@
 use std::{
-    collections::HashMap,
     io::{IoSlice, Write},
-    rc::Rc,
     time::SystemTime,
 };
 
-use crate::http::{
-    header::{HttpHeader, content_length, date},
-    request::HttpRequest,
-    value::{HttpMethod, HttpResponseCode, HttpVersion},
+use crate::{
+    http::{
+        body::Body,
+        error_page::ErrorPages,
+        header::{HttpHeader, content_length, date},
+        header_map::HeaderMap,
+        parser::{is_field_value, is_token},
+        request::HttpRequest,
+        typed::{Accept, ContentLength, TypedHeader},
+        value::{HttpMethod, HttpResponseCode, HttpVersion},
+    },
+    process::stream::{SharedStream, Stream},
 };
 
 pub struct HttpResponse<'a> {
     version: HttpVersion,
     code: HttpResponseCode,
-    header: HashMap<&'static str, Rc<dyn crate::http::header::ToString>>,
-    header_str: HashMap<Rc<String>, Rc<dyn crate::http::header::ToString>>,
+    header: HeaderMap<'static>,
     writer: Box<dyn Write + 'a>,
     buffer: Vec<Vec<u8>>,
+    // set by `set_body`, sent instead of `buffer`
+    body: Option<Body>,
+    // socket under `writer` for sendfile of a file body
+    sendfile: Option<SharedStream>,
+    // documents of `send_error`, the built-in ones when unset
+    error_pages: Option<&'a ErrorPages>,
+    // `Accept` of the request, choosing the format of error documents
+    accept: Option<Accept>,
     header_only: bool,
+    streaming: bool,
+    // header was refused, the response is a 500 error document whatever the handler writes
+    refused: bool,
     written: usize,
 }
 
@@ -27,11 +43,16 @@ impl<'a> HttpResponse<'a> {
         return Self {
             version: version,
             code: HttpResponseCode::Ok,
-            header: HashMap::new(),
-            header_str: HashMap::new(),
+            header: HeaderMap::new(),
             writer: writer,
             buffer: vec![],
+            body: None,
+            sendfile: None,
+            error_pages: None,
+            accept: None,
             header_only: false,
+            streaming: false,
+            refused: false,
             written: 0,
         };
     }
@@ -40,28 +61,214 @@ impl<'a> HttpResponse<'a> {
         return Self {
             version: request.version(),
             code: HttpResponseCode::Ok,
-            header: HashMap::new(),
-            header_str: HashMap::new(),
+            header: HeaderMap::new(),
             writer: writer,
             buffer: vec![],
+            body: None,
+            sendfile: None,
+            error_pages: None,
+            accept: request.header().typed(),
             header_only: request.method() == HttpMethod::HEAD,
+            streaming: false,
+            refused: false,
             written: 0,
         };
     }
 
+    /**
+     * Send file bodies from `stream` with sendfile(2) when it is a plain socket.
+     */
+    pub(crate) fn with_sendfile(mut self, stream: SharedStream) -> Self {
+        self.sendfile = Some(stream);
+        return self;
+    }
+
+    /**
+     * Render `send_error` documents, and the 500 of a refused header, with `error_pages`.
+     */
+    pub(crate) fn with_error_pages(mut self, error_pages: &'a ErrorPages) -> Self {
+        self.error_pages = Some(error_pages);
+        return self;
+    }
+
+    /**
+     * `Accept` of the request the error documents are negotiated with, for a response made without one.
+     */
+    pub(crate) fn with_accept(mut self, accept: Option<Accept>) -> Self {
+        self.accept = accept;
+        return self;
+    }
+
+    #[allow(dead_code)]
+    pub fn header(&self) -> &HeaderMap<'static> {
+        return &self.header;
+    }
+
+    /**
+     * Header fields to send, e.g. to `append` one more `Set-Cookie`. `set_header` replaces a field.
+     */
+    #[allow(dead_code)]
+    pub fn header_mut(&mut self) -> &mut HeaderMap<'static> {
+        return &mut self.header;
+    }
+
     pub fn written(&self) -> usize {
         self.written
     }
+
+    pub fn is_header_only(&self) -> bool {
+        self.header_only
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.streaming
+    }
+
+    /**
+     * Send `body` as the whole response body, replacing anything written. Writing after it fails.
+     * Its length decides the framing: Content-Length when known, chunked otherwise.
+     */
+    #[allow(dead_code)]
+    pub fn set_body(&mut self, body: impl Into<Body>) {
+        self.buffer.clear();
+        if !self.refused {
+            self.body = Some(body.into());
+        }
+    }
+
+    /**
+     * Answer with error `code` and its document, replacing anything written or set as body.
+     * The document is HTML, JSON or plain text as the request `Accept` prefers, see `ErrorPages`.
+     * `message` tells more than the reason phrase, it may be empty. A streaming response has sent its status already.
+     */
+    pub fn send_error(&mut self, code: HttpResponseCode, message: &str) {
+        if self.refused || self.streaming {
+            return;
+        }
+        let default = ErrorPages::default();
+        let page =
+            self.error_pages
+                .unwrap_or(&default)
+                .render(&code, message, self.accept.as_ref());
+        self.code = code;
+        self.set_header(&page.content_type);
+        self.buffer.clear();
+        self.body = Some(Body::from(page.body));
+    }
+
+    /**
+     * Send status line and header now and switch to streaming mode.
+     * Body length is unknown, so the connection close marks the end of body.
+     * After this call data must be sent by `write_chunk`.
+     */
+    pub fn start_streaming(&mut self) -> std::io::Result<()> {
+        if self.streaming {
+            return Ok(());
+        }
+
+        if let HttpVersion::Http2 = self.version {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "streaming response is not supported on HTTP/2",
+            ));
+        }
+
+        if !self.check_header() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid response header field",
+            ));
+        }
+        if self.body.is_some() {
+            return Err(std::io::Error::other("response body is already set"));
+        }
+
+        self.set_header(&date(SystemTime::now()));
+        self.written += self.write_header()?;
+        self.writer.flush()?;
+        self.streaming = true;
+
+        let buffered: Vec<Vec<u8>> = self.buffer.drain(..).collect();
+        for b in buffered {
+            self.write_chunk(&b)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Status code, header fields and body of buffered response.
+     * Used by protocols which do not send HTTP/1 formatted response, like HTTP/2.
+     */
+    pub(crate) fn into_parts(mut self) -> (i32, Vec<(String, String)>, Option<Vec<u8>>) {
+        self.check_header();
+        let body = match self.body.take().map(Body::into_bytes) {
+            Some(Ok(body)) => body,
+            Some(Err(e)) => {
+                log::error!(target: "HttpResponse", "reading response body failed: {}, answering 500", e);
+                self.code = HttpResponseCode::InternalServerError;
+                vec![]
+            }
+            None => self.buffer.drain(..).flatten().collect(),
+        };
+        self.set_header(&content_length(body.len()));
+        self.set_header(&date(SystemTime::now()));
+
+        let fields: Vec<(String, String)> = self
+            .header
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let body = if self.header_only { None } else { Some(body) };
+        return (self.code.code(), fields, body);
+    }
+
+    /**
+     * Write data to client and flush immediately. Only available in streaming mode.
+     */
+    pub fn write_chunk(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if !self.streaming {
+            return Err(std::io::Error::other("response is not streaming"));
+        }
+
+        if self.header_only {
+            return Ok(());
+        }
+
+        self.writer.write_all(data)?;
+        self.writer.flush()?;
+        self.written += data.len();
+        Ok(())
+    }
 }
 
 impl<'a> Write for HttpResponse<'a> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.refused {
+            return Ok(buf.len());
+        }
+        if self.body.is_some() {
+            return Err(std::io::Error::other("response body is already set"));
+        }
         self.buffer.push(buf.to_vec());
 
         return Ok(buf.len());
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
+        if self.streaming {
+            let buffered: Vec<Vec<u8>> = self.buffer.drain(..).collect();
+            for b in buffered {
+                self.write_chunk(&b)?;
+            }
+            return self.writer.flush();
+        }
+
+        self.check_header();
+        if let Some(body) = self.body.take() {
+            return self.flush_body(body);
+        }
         self.set_header(&content_length(
             self.buffer.iter().map(|b| b.len()).sum::<usize>(),
         ));
@@ -74,8 +281,18 @@ impl<'a> Write for HttpResponse<'a> {
             return Ok(());
         }
 
-        let data: Vec<IoSlice<'_>> = self.buffer.iter().map(|b| IoSlice::new(&b)).collect();
-        let body_written = self.writer.write_vectored(&data)?;
+        let mut data: Vec<IoSlice<'_>> = self.buffer.iter().map(|b| IoSlice::new(b)).collect();
+        let mut slices = &mut data[..];
+        let mut body_written = 0;
+        // writer may take only a part, e.g. default write_vectored writes the first slice only
+        while !slices.is_empty() {
+            let n = self.writer.write_vectored(slices)?;
+            if n == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            body_written += n;
+            IoSlice::advance_slices(&mut slices, n);
+        }
 
         self.writer.flush()?;
         self.written = header_written + body_written;
@@ -90,12 +307,14 @@ pub trait HeaderSetter<T> {
 
 impl<'a> HeaderSetter<&HttpHeader> for HttpResponse<'a> {
     fn set_header(&mut self, header: &HttpHeader) {
-        let value = header.value().clone();
-        if let Some(key) = header.key_str() {
-            self.header.insert(key, value);
-        } else if let Some(key) = header.key_string() {
-            self.header_str.insert(key, value);
-        }
+        self.header
+            .insert(header.name().clone(), header.value().clone());
+    }
+}
+
+impl<'a, H: TypedHeader> HeaderSetter<&H> for HttpResponse<'a> {
+    fn set_header(&mut self, header: &H) {
+        self.header.set_typed(header);
     }
 }
 
@@ -107,6 +326,32 @@ impl HttpResponse<'_> {
         self.code = code;
     }
 
+    /**
+     * Refuse header fields which could split the response: a name which is not a token,
+     * a value with CR, LF or another control character. The response becomes the 500 error document,
+     * so nothing the handler set is sent. Returns whether the header was valid.
+     */
+    fn check_header(&mut self) -> bool {
+        let invalid = self
+            .header
+            .iter()
+            .find(|(name, value)| !is_token(name.as_bytes()) || !is_field_value(value.as_bytes()));
+        let Some((name, value)) = invalid else {
+            return true;
+        };
+
+        log::error!(
+            target: "HttpResponse",
+            "invalid header field {:?}: {:?}, answering 500",
+            name,
+            value
+        );
+        self.header = HeaderMap::new();
+        self.send_error(HttpResponseCode::InternalServerError, "");
+        self.refused = true;
+        return false;
+    }
+
     pub fn write_header(&mut self) -> std::io::Result<usize> {
         let status_line = format!(
             "{} {} {}",
@@ -118,23 +363,50 @@ impl HttpResponse<'_> {
         let mut written = self.writer.write(status_line.as_bytes())?;
         written += self.writer.write(LINE_END)?;
 
-        if !self.header.is_empty() {
-            for (key, value) in self.header.clone().into_iter() {
-                written +=
-                    self.write_header_value(&key.as_bytes(), value.to_string().as_bytes())?;
-            }
+        let header = std::mem::take(&mut self.header);
+        for (key, value) in header.iter() {
+            written += self.write_header_value(key.as_bytes(), value.as_bytes())?;
         }
+        self.header = header;
 
-        if !self.header_str.is_empty() {
-            for (key, value) in self.header_str.clone().into_iter() {
-                written +=
-                    self.write_header_value(&key.as_bytes(), value.to_string().as_bytes())?;
+        written += self.writer.write(LINE_END)?;
+
+        return Ok(written);
+    }
+
+    // body of `set_body`, framed by its length, chunks for HTTP/1.1 or the end of connection
+    fn flush_body(&mut self, body: Body) -> std::io::Result<()> {
+        let chunked = match body.length() {
+            Some(length) => {
+                self.header.remove("Transfer-Encoding");
+                self.set_header(&ContentLength(length));
+                false
+            }
+            None => {
+                self.header.remove("Content-Length");
+                matches!(self.version, HttpVersion::Http11)
             }
+        };
+        if chunked {
+            self.header.insert("Transfer-Encoding", "chunked");
         }
+        self.set_header(&date(SystemTime::now()));
+        let header_written = self.write_header()?;
 
-        written += self.writer.write(LINE_END)?;
+        if self.header_only {
+            self.writer.flush()?;
+            self.written = header_written;
+            return Ok(());
+        }
 
-        return Ok(written);
+        let sendfile = self.sendfile.clone();
+        let body_written = body.write_to(
+            self.writer.as_mut(),
+            sendfile.as_ref().map(|s| s as &dyn Stream),
+            chunked,
+        )?;
+        self.written = header_written + body_written;
+        return Ok(());
     }
 
     fn write_header_value(&mut self, k: &[u8], v: &[u8]) -> std::io::Result<usize> {
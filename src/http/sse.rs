@@ -0,0 +1,258 @@
+use std::{
+    fmt::Write as _,
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+
+use crate::http::{
+    request::HttpRequest,
+    response::{HeaderSetter, HttpResponse},
+    typed::{CacheControl, CacheDirective, ContentType},
+};
+
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(15);
+
+/**
+ * Single Server-Sent Event.
+ * `data` may contain new lines, each line becomes its own `data:` field.
+ */
+#[allow(dead_code)]
+#[derive(Debug, Default, Clone)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+#[allow(dead_code)]
+impl Event {
+    pub fn new(data: impl Into<String>) -> Self {
+        return Self {
+            data: data.into(),
+            ..Default::default()
+        };
+    }
+
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        return self;
+    }
+
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        return self;
+    }
+
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        return self;
+    }
+
+    fn encode(&self) -> String {
+        let mut buf = String::new();
+        if let Some(event) = &self.event {
+            let _ = writeln!(buf, "event: {}", single_line(event));
+        }
+        if let Some(id) = &self.id {
+            let _ = writeln!(buf, "id: {}", single_line(id));
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(buf, "retry: {}", retry.as_millis());
+        }
+        for line in lines(&self.data) {
+            let _ = writeln!(buf, "data: {}", line);
+        }
+        buf.push('\n');
+        return buf;
+    }
+}
+
+// lines of a multi line value, any of CRLF, CR and LF ends a line as for the client
+fn lines(value: &str) -> impl Iterator<Item = &str> {
+    return value
+        .split("\r\n")
+        .flat_map(|line| line.split(['\r', '\n']));
+}
+
+fn encode_comment(comment: &str) -> String {
+    let mut buf = String::new();
+    for line in lines(comment) {
+        let _ = writeln!(buf, ": {}", line);
+    }
+    buf.push('\n');
+    return buf;
+}
+
+// id and event name are single line fields, new line would start another field
+fn single_line(value: &str) -> String {
+    return value.chars().filter(|c| *c != '\r' && *c != '\n').collect();
+}
+
+/**
+ * `text/event-stream` response.
+ * Header is sent when the stream is created and every event is flushed to the
+ * client immediately. Any write failure (including write timeout of a stalled
+ * client) closes the stream, handler should stop when `is_closed` returns true.
+ */
+pub struct EventStream<'r, 'a> {
+    response: &'r mut HttpResponse<'a>,
+    heartbeat: Duration,
+    last_write: Instant,
+    closed: bool,
+}
+
+#[allow(dead_code)]
+impl<'r, 'a> EventStream<'r, 'a> {
+    pub fn start(response: &'r mut HttpResponse<'a>) -> std::io::Result<Self> {
+        response.set_header(&ContentType::new("text", "event-stream"));
+        response.set_header(&CacheControl(vec![CacheDirective::NoCache]));
+        response.start_streaming()?;
+
+        let closed = response.is_header_only();
+        return Ok(Self {
+            response,
+            heartbeat: DEFAULT_HEARTBEAT,
+            last_write: Instant::now(),
+            closed,
+        });
+    }
+
+    pub fn set_heartbeat_interval(&mut self, interval: Duration) {
+        self.heartbeat = interval;
+    }
+
+    pub fn send(&mut self, event: &Event) -> std::io::Result<()> {
+        return self.write(event.encode().as_bytes());
+    }
+
+    pub fn comment(&mut self, comment: &str) -> std::io::Result<()> {
+        return self.write(encode_comment(comment).as_bytes());
+    }
+
+    /**
+     * Send heartbeat comment when nothing was written during heartbeat interval.
+     * Returns whether a heartbeat was sent.
+     * Heartbeat keeps intermediaries from closing an idle connection and lets us
+     * notice a disconnected client even when there is no event to send.
+     */
+    pub fn heartbeat(&mut self) -> std::io::Result<bool> {
+        if self.last_write.elapsed() < self.heartbeat {
+            return Ok(false);
+        }
+
+        self.write(b":\n\n")?;
+        return Ok(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        return self.closed;
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if self.closed {
+            return Err(std::io::Error::new(
+                ErrorKind::NotConnected,
+                "event stream closed",
+            ));
+        }
+
+        if let Err(e) = self.response.write_chunk(data) {
+            log::debug!("event stream closed: {e}");
+            self.closed = true;
+            return Err(e);
+        }
+
+        self.last_write = Instant::now();
+        return Ok(());
+    }
+}
+
+/**
+ * `Last-Event-ID` sent by a reconnecting EventSource client.
+ */
+#[allow(dead_code)]
+pub fn last_event_id<'a>(req: &'a HttpRequest) -> Option<&'a str> {
+    return req.header().get_all("last-event-id").last();
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::http::{
+        handler::Handler,
+        http::Http1,
+        request::HttpRequest,
+        response::HttpResponse,
+        sse::{Event, EventStream, encode_comment, last_event_id},
+        test_client::{TestClient, TestRequest},
+    };
+
+    struct EventHandler;
+
+    impl Handler for EventHandler {
+        fn handle(&self, req: &mut HttpRequest, res: &mut HttpResponse) {
+            let start: u32 = last_event_id(req)
+                .and_then(|id| id.parse().ok())
+                .map(|id: u32| id + 1)
+                .unwrap_or(0);
+
+            let mut events = EventStream::start(res).unwrap();
+            for id in start..start + 2 {
+                events
+                    .send(&Event::new(format!("tick {id}")).id(id.to_string()))
+                    .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_event() {
+        let event = Event::new("first\nsecond")
+            .id("1")
+            .event("update")
+            .retry(Duration::from_secs(3));
+
+        assert_eq!(
+            event.encode(),
+            "event: update\nid: 1\nretry: 3000\ndata: first\ndata: second\n\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_split_every_line_ending() {
+        let event = Event::new("a\r\nb\rc\nd\r");
+        assert_eq!(
+            event.encode(),
+            "data: a\ndata: b\ndata: c\ndata: d\ndata: \n\n"
+        );
+
+        // a bare CR must not smuggle a field into the comment
+        assert_eq!(
+            encode_comment("note\rdata: evil\r\nend"),
+            ": note\n: data: evil\n: end\n\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_strip_new_line_in_field() {
+        let event = Event::new("data").id("1\r\nevent: evil");
+
+        assert_eq!(event.encode(), "id: 1event: evil\ndata: data\n\n");
+    }
+
+    #[test]
+    fn test_event_stream_response() {
+        let client = TestClient::new(Http1::new(8192, EventHandler));
+        let response = client.send(TestRequest::get("/events").header("last-event-id", "4"));
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some("text/event-stream"));
+        assert_eq!(response.header("content-length"), None);
+        assert_eq!(
+            response.text(),
+            "id: 5\ndata: tick 5\n\nid: 6\ndata: tick 6\n\n"
+        );
+    }
+}
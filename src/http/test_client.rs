@@ -0,0 +1,248 @@
+use std::io::{Read, Write};
+
+use crate::process::{Process, address::Address, pipe::pipe};
+
+/**
+ * Drive a `Process` such as `Http1` with requests over in-memory pipe, no port or thread needed.
+ * The request is written up front and the process runs on the calling thread until it returns.
+ */
+pub struct TestClient<P: Process> {
+    process: P,
+    remote_addr: Address,
+}
+
+#[allow(dead_code)]
+impl<P: Process> TestClient<P> {
+    pub fn new(process: P) -> Self {
+        return Self {
+            process,
+            remote_addr: Address::Inet("127.0.0.1:50000".parse().unwrap()),
+        };
+    }
+
+    pub fn with_remote_addr(mut self, remote_addr: Address) -> Self {
+        self.remote_addr = remote_addr;
+        return self;
+    }
+
+    pub fn send(&self, request: TestRequest) -> TestResponse {
+        return self.send_raw(&request.encode());
+    }
+
+    /**
+     * Send bytes as is, for malformed requests.
+     */
+    pub fn send_raw(&self, raw: &[u8]) -> TestResponse {
+        return TestResponse::parse(&self.exchange(raw));
+    }
+
+    /**
+     * Raw bytes written by the process for `raw`.
+     */
+    pub fn exchange(&self, raw: &[u8]) -> Vec<u8> {
+        let (server, mut client) = pipe();
+        client.write_all(raw).unwrap();
+
+        let server = server.with_addr(Address::Unnamed, self.remote_addr.clone());
+        if let Err(e) = self.process.process(Box::new(server), &self.remote_addr) {
+            log::debug!("test process failed {:?}", e);
+        }
+
+        let mut received = vec![];
+        client.read_to_end(&mut received).unwrap();
+        return received;
+    }
+}
+
+pub struct TestRequest {
+    method: String,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl TestRequest {
+    pub fn new(method: &str, target: &str) -> Self {
+        return Self {
+            method: method.to_string(),
+            target: target.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: vec![("Host".to_string(), "localhost".to_string())],
+            body: vec![],
+        };
+    }
+
+    pub fn get(target: &str) -> Self {
+        return Self::new("GET", target);
+    }
+
+    pub fn head(target: &str) -> Self {
+        return Self::new("HEAD", target);
+    }
+
+    pub fn post(target: &str) -> Self {
+        return Self::new("POST", target);
+    }
+
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        return self;
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        return self;
+    }
+
+    /**
+     * Body with matching `Content-Length`.
+     */
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self.headers
+            .push(("Content-Length".to_string(), self.body.len().to_string()));
+        return self;
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut res = format!("{} {} {}\r\n", self.method, self.target, self.version).into_bytes();
+        for (name, value) in &self.headers {
+            res.extend(format!("{name}: {value}\r\n").as_bytes());
+        }
+        res.extend(b"\r\n");
+        res.extend(&self.body);
+        return res;
+    }
+}
+
+/**
+ * Parsed HTTP/1 response. Malformed response panics, it is a test failure anyway.
+ */
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct TestResponse {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl TestResponse {
+    pub fn parse(raw: &[u8]) -> Self {
+        let head_end = raw
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .unwrap_or_else(|| panic!("no end of head: {:?}", String::from_utf8_lossy(raw)));
+        let head = String::from_utf8(raw[..head_end].to_vec()).unwrap();
+        let rest = &raw[head_end + 4..];
+
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap();
+        let mut status = status_line.splitn(3, ' ');
+        let version = status.next().unwrap().to_string();
+        let code = status.next().unwrap().parse().unwrap();
+        let reason = status.next().unwrap_or("").to_string();
+
+        let headers: Vec<(String, String)> = lines
+            .map(|line| {
+                let (name, value) = line.split_once(':').unwrap();
+                return (name.trim().to_string(), value.trim().to_string());
+            })
+            .collect();
+
+        let mut response = Self {
+            version,
+            status: code,
+            reason,
+            headers,
+            body: vec![],
+        };
+
+        response.body = if response
+            .header("transfer-encoding")
+            .map(|v| v.eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false)
+        {
+            decode_chunked(rest)
+        } else {
+            match response.header("content-length") {
+                Some(len) => rest[..len.parse::<usize>().unwrap().min(rest.len())].to_vec(),
+                None => rest.to_vec(),
+            }
+        };
+
+        return response;
+    }
+
+    /**
+     * First value of header, name is case-insensitive.
+     */
+    pub fn header(&self, name: &str) -> Option<&str> {
+        return self
+            .headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str());
+    }
+
+    pub fn text(&self) -> String {
+        return String::from_utf8_lossy(&self.body).into_owned();
+    }
+}
+
+fn decode_chunked(mut data: &[u8]) -> Vec<u8> {
+    let mut res = vec![];
+    loop {
+        let line_end = match data.windows(2).position(|w| w == b"\r\n") {
+            Some(pos) => pos,
+            None => return res,
+        };
+        let size_str = String::from_utf8_lossy(&data[..line_end]);
+        let size_str = size_str.split(';').next().unwrap().trim();
+        let size = usize::from_str_radix(size_str, 16).unwrap();
+        data = &data[line_end + 2..];
+
+        if size == 0 {
+            return res;
+        }
+        res.extend(&data[..size.min(data.len())]);
+        data = data.get(size + 2..).unwrap_or(&[]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::http::test_client::{TestRequest, TestResponse};
+
+    #[test]
+    fn test_encode_request() {
+        let request = TestRequest::post("/echo?a=b")
+            .header("X-Test", "1")
+            .body("hi");
+
+        assert_eq!(
+            String::from_utf8(request.encode()).unwrap(),
+            "POST /echo?a=b HTTP/1.1\r\nHost: localhost\r\nX-Test: 1\r\nContent-Length: 2\r\n\r\nhi"
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = TestResponse::parse(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 3\r\nX-A: b\r\n\r\nabcdef",
+        );
+        assert_eq!(response.status, 404);
+        assert_eq!(response.reason, "Not Found");
+        assert_eq!(response.header("x-a"), Some("b"));
+        assert_eq!(response.text(), "abc");
+
+        let chunked = TestResponse::parse(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;x=y\r\nde\r\n0\r\n\r\n",
+        );
+        assert_eq!(chunked.text(), "abcde");
+    }
+}
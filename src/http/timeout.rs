@@ -0,0 +1,243 @@
+use std::{
+    cell::Cell,
+    io::{Error, ErrorKind, Read},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::process::stream::{SharedStream, Stream};
+
+/**
+ * How long a client may take for each part of a request.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeouts {
+    // each read while the header arrives
+    pub header_read: Duration,
+    // whole header from the start of the request, against slowloris
+    pub header_deadline: Duration,
+    // each read of the body, and the grace before `min_body_rate` counts
+    pub body_read: Duration,
+    // body bytes per second, 0 disables
+    pub min_body_rate: u64,
+    // each write to client
+    pub write: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        return Self {
+            header_read: Duration::from_secs(5),
+            header_deadline: Duration::from_secs(20),
+            body_read: Duration::from_secs(10),
+            min_body_rate: 500,
+            write: Duration::from_secs(10),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Header,
+    Body,
+    // the protocol keeps its own timeouts, e.g. HTTP/2 connection
+    Released,
+}
+
+/**
+ * Request reader applying `Timeouts` to the stream.
+ * The body may take `body_read` plus one second for every `min_body_rate` bytes, like mod_reqtimeout of Apache.
+ * An expired limit is a `TimedOut` error and stays visible through `timed_out`, to answer 408.
+ */
+pub struct TimedReader {
+    stream: SharedStream,
+    timeouts: Timeouts,
+    phase: Phase,
+    started: Instant,
+    body_bytes: u64,
+    timed_out: Rc<Cell<bool>>,
+}
+
+impl TimedReader {
+    pub fn new(stream: SharedStream, timeouts: &Timeouts) -> Self {
+        return Self {
+            stream,
+            timeouts: timeouts.clone(),
+            phase: Phase::Header,
+            started: Instant::now(),
+            body_bytes: 0,
+            timed_out: Rc::new(Cell::new(false)),
+        };
+    }
+
+    pub fn start_body(&mut self) {
+        self.phase = Phase::Body;
+        self.started = Instant::now();
+        self.body_bytes = 0;
+    }
+
+    /**
+     * Stop applying the limits, the read timeout of the stream stays as the caller sets it.
+     */
+    pub fn release(&mut self) {
+        self.phase = Phase::Released;
+    }
+
+    pub fn timed_out(&self) -> Rc<Cell<bool>> {
+        return self.timed_out.clone();
+    }
+
+    // time left in the current phase
+    fn left(&self) -> Option<Duration> {
+        let allowed = match self.phase {
+            Phase::Header => self.timeouts.header_deadline,
+            Phase::Body if self.timeouts.min_body_rate > 0 => {
+                self.timeouts.body_read
+                    + Duration::from_secs_f64(
+                        self.body_bytes as f64 / self.timeouts.min_body_rate as f64,
+                    )
+            }
+            Phase::Body | Phase::Released => return None,
+        };
+        return Some(allowed.saturating_sub(self.started.elapsed()));
+    }
+
+    fn expire(&self, msg: &str) -> Error {
+        self.timed_out.set(true);
+        return Error::new(ErrorKind::TimedOut, msg.to_string());
+    }
+}
+
+impl Read for TimedReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let wait = match self.phase {
+            Phase::Header => self.timeouts.header_read,
+            Phase::Body => self.timeouts.body_read,
+            Phase::Released => return self.stream.read(buf),
+        };
+        let wait = match self.left() {
+            Some(left) if left.is_zero() => return Err(self.expire("request too slow")),
+            Some(left) => wait.min(left),
+            None => wait,
+        };
+        let _ = self.stream.set_read_timeout(Some(wait));
+
+        return match self.stream.read(buf) {
+            Ok(n) => {
+                if self.phase == Phase::Body {
+                    self.body_bytes += n as u64;
+                }
+                Ok(n)
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Err(self.expire("request read timed out"))
+            }
+            Err(e) => Err(e),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{ErrorKind, Read, Write},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        http::timeout::{TimedReader, Timeouts},
+        process::{
+            pipe::pipe,
+            stream::{SharedStream, Stream},
+        },
+    };
+
+    fn timeouts() -> Timeouts {
+        return Timeouts {
+            header_read: Duration::from_millis(100),
+            header_deadline: Duration::from_millis(300),
+            body_read: Duration::from_millis(100),
+            min_body_rate: 100,
+            write: Duration::from_millis(100),
+        };
+    }
+
+    #[test]
+    fn test_header_deadline() {
+        let (server, mut client) = pipe();
+        let mut reader = TimedReader::new(SharedStream::new(Box::new(server)), &timeouts());
+        let timed_out = reader.timed_out();
+
+        // a byte in every 50ms never hits header_read
+        let writer = thread::spawn(move || {
+            for _ in 0..20 {
+                if client.write_all(b"a").is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let start = Instant::now();
+        let mut buf = [0; 16];
+        let err = loop {
+            match reader.read(&mut buf) {
+                Ok(n) => assert!(n > 0),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(timed_out.get());
+        assert!(start.elapsed() < Duration::from_millis(600));
+
+        drop(reader);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_body_rate() {
+        let (server, mut client) = pipe();
+        let rate = Timeouts {
+            min_body_rate: 1000,
+            ..timeouts()
+        };
+        let mut reader = TimedReader::new(SharedStream::new(Box::new(server)), &rate);
+        reader.start_body();
+
+        // every read is in time, but 20 bytes/s is below the rate
+        let writer = thread::spawn(move || {
+            for _ in 0..20 {
+                if client.write_all(b"a").is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let mut buf = [0; 100];
+        let err = loop {
+            match reader.read(&mut buf) {
+                Ok(n) => assert!(n > 0),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        drop(reader);
+        writer.join().unwrap();
+
+        // released reader only waits as the stream says
+        let (server, _client) = pipe();
+        let mut reader = TimedReader::new(SharedStream::new(Box::new(server)), &timeouts());
+        reader.release();
+        reader
+            .stream
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        assert!(!reader.timed_out().get());
+    }
+}
@@ -0,0 +1,1032 @@
+use std::{
+    fmt::{Display, Write},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    http::{
+        parser::is_token,
+        value::{HttpMethod, WeightedValue},
+    },
+    util::{base64, date},
+};
+
+/**
+ * Header field with a typed value, parsed from and encoded to the field value.
+ */
+pub trait TypedHeader: Sized {
+    const NAME: &'static str;
+    // values of repeated fields are one comma separated list, RFC 9110 5.3
+    const LIST: bool = false;
+
+    fn parse(value: &str) -> Option<Self>;
+
+    fn encode(&self) -> String;
+}
+
+/**
+ * `type/subtype` with parameters, of `Content-Type` and `Accept`. Type, subtype and parameter names are lowercase.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    main_type: String,
+    sub_type: String,
+    params: Vec<(String, String)>,
+}
+
+#[allow(dead_code)]
+impl MediaType {
+    pub fn new(main_type: &str, sub_type: &str) -> Self {
+        return Self {
+            main_type: main_type.to_ascii_lowercase(),
+            sub_type: sub_type.to_ascii_lowercase(),
+            params: vec![],
+        };
+    }
+
+    pub fn with_param(mut self, name: &str, value: &str) -> Self {
+        self.params
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        return self;
+    }
+
+    pub fn parse(str: &str) -> Option<Self> {
+        let parts = split_list(str, b';');
+        let (main_type, sub_type) = parts.first()?.split_once('/')?;
+        if !is_token(main_type.as_bytes()) || !is_token(sub_type.as_bytes()) {
+            return None;
+        }
+        return Some(Self {
+            main_type: main_type.to_ascii_lowercase(),
+            sub_type: sub_type.to_ascii_lowercase(),
+            params: parse_params(&parts[1..])?,
+        });
+    }
+
+    pub fn main_type(&self) -> &str {
+        return &self.main_type;
+    }
+
+    pub fn sub_type(&self) -> &str {
+        return &self.sub_type;
+    }
+
+    /**
+     * `type/subtype` without parameters.
+     */
+    pub fn essence(&self) -> String {
+        return format!("{}/{}", self.main_type, self.sub_type);
+    }
+
+    /**
+     * Whether the essence is `essence`, case-insensitively. `*` in the type or subtype of this one matches any.
+     */
+    pub fn is(&self, essence: &str) -> bool {
+        let Some((main_type, sub_type)) = essence.split_once('/') else {
+            return false;
+        };
+        let matches = |this: &str, other: &str| this == "*" || this.eq_ignore_ascii_case(other);
+        return matches(&self.main_type, main_type) && matches(&self.sub_type, sub_type);
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        return param(&self.params, name);
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        return self.param("charset");
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut str = format!("{}/{}", self.main_type, self.sub_type);
+        encode_params(&mut str, &self.params);
+        return f.write_str(&str);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType(pub MediaType);
+
+impl ContentType {
+    pub fn new(main_type: &str, sub_type: &str) -> Self {
+        return Self(MediaType::new(main_type, sub_type));
+    }
+
+    #[allow(dead_code)]
+    pub fn with_charset(self, charset: &str) -> Self {
+        return Self(self.0.with_param("charset", charset));
+    }
+}
+
+impl TypedHeader for ContentType {
+    const NAME: &'static str = "Content-Type";
+
+    fn parse(value: &str) -> Option<Self> {
+        return MediaType::parse(value).map(Self);
+    }
+
+    fn encode(&self) -> String {
+        return self.0.to_string();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheDirective {
+    NoCache,
+    NoStore,
+    NoTransform,
+    MustRevalidate,
+    Public,
+    Private,
+    Immutable,
+    OnlyIfCached,
+    MaxAge(u64),
+    SMaxAge(u64),
+    // request, any stale response when without seconds
+    MaxStale(Option<u64>),
+    MinFresh(u64),
+    // unknown directive, name lowercase
+    Extension(String, Option<String>),
+}
+
+impl CacheDirective {
+    fn parse(str: &str) -> Option<Self> {
+        let (name, value) = match str.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(unquote(value)?)),
+            None => (str, None),
+        };
+        if !is_token(name.as_bytes()) {
+            return None;
+        }
+        let name = name.to_ascii_lowercase();
+        let seconds = || value.as_ref()?.parse::<u64>().ok();
+
+        return Some(match (name.as_str(), &value) {
+            ("no-cache", None) => CacheDirective::NoCache,
+            ("no-store", None) => CacheDirective::NoStore,
+            ("no-transform", None) => CacheDirective::NoTransform,
+            ("must-revalidate", None) => CacheDirective::MustRevalidate,
+            ("public", None) => CacheDirective::Public,
+            ("private", None) => CacheDirective::Private,
+            ("immutable", None) => CacheDirective::Immutable,
+            ("only-if-cached", None) => CacheDirective::OnlyIfCached,
+            ("max-age", Some(_)) => CacheDirective::MaxAge(seconds()?),
+            ("s-maxage", Some(_)) => CacheDirective::SMaxAge(seconds()?),
+            ("max-stale", None) => CacheDirective::MaxStale(None),
+            ("max-stale", Some(_)) => CacheDirective::MaxStale(Some(seconds()?)),
+            ("min-fresh", Some(_)) => CacheDirective::MinFresh(seconds()?),
+            _ => CacheDirective::Extension(name, value),
+        });
+    }
+}
+
+impl Display for CacheDirective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            CacheDirective::NoCache => f.write_str("no-cache"),
+            CacheDirective::NoStore => f.write_str("no-store"),
+            CacheDirective::NoTransform => f.write_str("no-transform"),
+            CacheDirective::MustRevalidate => f.write_str("must-revalidate"),
+            CacheDirective::Public => f.write_str("public"),
+            CacheDirective::Private => f.write_str("private"),
+            CacheDirective::Immutable => f.write_str("immutable"),
+            CacheDirective::OnlyIfCached => f.write_str("only-if-cached"),
+            CacheDirective::MaxAge(s) => f.write_fmt(format_args!("max-age={}", s)),
+            CacheDirective::SMaxAge(s) => f.write_fmt(format_args!("s-maxage={}", s)),
+            CacheDirective::MaxStale(None) => f.write_str("max-stale"),
+            CacheDirective::MaxStale(Some(s)) => f.write_fmt(format_args!("max-stale={}", s)),
+            CacheDirective::MinFresh(s) => f.write_fmt(format_args!("min-fresh={}", s)),
+            CacheDirective::Extension(name, None) => f.write_str(name),
+            CacheDirective::Extension(name, Some(value)) => {
+                f.write_fmt(format_args!("{}={}", name, quote(value)))
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheControl(pub Vec<CacheDirective>);
+
+#[allow(dead_code)]
+impl CacheControl {
+    pub fn contains(&self, directive: &CacheDirective) -> bool {
+        return self.0.contains(directive);
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        return self.0.iter().find_map(|d| match d {
+            CacheDirective::MaxAge(s) => Some(Duration::from_secs(*s)),
+            _ => None,
+        });
+    }
+}
+
+impl TypedHeader for CacheControl {
+    const NAME: &'static str = "Cache-Control";
+    const LIST: bool = true;
+
+    fn parse(value: &str) -> Option<Self> {
+        return split_list(value, b',')
+            .into_iter()
+            .map(CacheDirective::parse)
+            .collect::<Option<Vec<_>>>()
+            .filter(|d| !d.is_empty())
+            .map(Self);
+    }
+
+    fn encode(&self) -> String {
+        return join(&self.0, ", ");
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Disposition {
+    Inline,
+    Attachment,
+    FormData,
+    Other(String),
+}
+
+/**
+ * `Content-Disposition` of RFC 6266 and of `multipart/form-data` parts.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentDisposition {
+    pub disposition: Disposition,
+    // names lowercase, `filename*` kept encoded
+    pub params: Vec<(String, String)>,
+}
+
+#[allow(dead_code)]
+impl ContentDisposition {
+    pub fn inline() -> Self {
+        return Self {
+            disposition: Disposition::Inline,
+            params: vec![],
+        };
+    }
+
+    /**
+     * Download as `filename`. A name beyond printable ASCII goes in `filename*` as UTF-8, with an ASCII fallback.
+     */
+    pub fn attachment(filename: &str) -> Self {
+        let mut params = vec![];
+        if filename.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+            params.push(("filename".to_string(), filename.to_string()));
+        } else {
+            let fallback: String = filename
+                .chars()
+                .map(|c| if (' '..='~').contains(&c) { c } else { '_' })
+                .collect();
+            params.push(("filename".to_string(), fallback));
+            params.push((
+                "filename*".to_string(),
+                format!("UTF-8''{}", percent_encode(filename)),
+            ));
+        }
+        return Self {
+            disposition: Disposition::Attachment,
+            params,
+        };
+    }
+
+    /**
+     * `filename*` when it decodes, `filename` otherwise.
+     */
+    pub fn filename(&self) -> Option<String> {
+        if let Some(ext) = param(&self.params, "filename*").and_then(decode_ext_value) {
+            return Some(ext);
+        }
+        return param(&self.params, "filename").map(|f| f.to_string());
+    }
+
+    /**
+     * Field name of a `form-data` part.
+     */
+    pub fn name(&self) -> Option<&str> {
+        return param(&self.params, "name");
+    }
+}
+
+impl TypedHeader for ContentDisposition {
+    const NAME: &'static str = "Content-Disposition";
+
+    fn parse(value: &str) -> Option<Self> {
+        let parts = split_list(value, b';');
+        let kind = parts.first()?;
+        if !is_token(kind.as_bytes()) {
+            return None;
+        }
+        let disposition = match kind.to_ascii_lowercase().as_str() {
+            "inline" => Disposition::Inline,
+            "attachment" => Disposition::Attachment,
+            "form-data" => Disposition::FormData,
+            other => Disposition::Other(other.to_string()),
+        };
+        return Some(Self {
+            disposition,
+            params: parse_params(&parts[1..])?,
+        });
+    }
+
+    fn encode(&self) -> String {
+        let mut str = match &self.disposition {
+            Disposition::Inline => "inline".to_string(),
+            Disposition::Attachment => "attachment".to_string(),
+            Disposition::FormData => "form-data".to_string(),
+            Disposition::Other(other) => other.clone(),
+        };
+        encode_params(&mut str, &self.params);
+        return str;
+    }
+}
+
+/**
+ * Credentials of `Authorization`. The scheme is case-insensitive.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authorization {
+    Basic { user: String, password: String },
+    Bearer(String),
+    // other schemes, credentials as sent
+    Other { scheme: String, credentials: String },
+}
+
+impl TypedHeader for Authorization {
+    const NAME: &'static str = "Authorization";
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (scheme, credentials) = value.split_once(' ').unwrap_or((value, ""));
+        let credentials = credentials.trim();
+        if !is_token(scheme.as_bytes()) {
+            return None;
+        }
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = String::from_utf8(base64::decode(credentials)?).ok()?;
+            let (user, password) = decoded.split_once(':')?;
+            return Some(Authorization::Basic {
+                user: user.to_string(),
+                password: password.to_string(),
+            });
+        }
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return is_token68(credentials).then(|| Authorization::Bearer(credentials.to_string()));
+        }
+        return Some(Authorization::Other {
+            scheme: scheme.to_string(),
+            credentials: credentials.to_string(),
+        });
+    }
+
+    fn encode(&self) -> String {
+        return match self {
+            Authorization::Basic { user, password } => {
+                format!(
+                    "Basic {}",
+                    base64::encode(format!("{}:{}", user, password).as_bytes())
+                )
+            }
+            Authorization::Bearer(token) => format!("Bearer {}", token),
+            Authorization::Other {
+                scheme,
+                credentials,
+            } => format!("{} {}", scheme, credentials).trim_end().to_string(),
+        };
+    }
+}
+
+/**
+ * One challenge of `WWW-Authenticate`, e.g. `Basic realm="site"`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WwwAuthenticate {
+    pub scheme: String,
+    // names lowercase
+    pub params: Vec<(String, String)>,
+}
+
+#[allow(dead_code)]
+impl WwwAuthenticate {
+    pub fn new(scheme: &str) -> Self {
+        return Self {
+            scheme: scheme.to_string(),
+            params: vec![],
+        };
+    }
+
+    pub fn basic(realm: &str) -> Self {
+        return Self::new("Basic").with_param("realm", realm);
+    }
+
+    pub fn with_param(mut self, name: &str, value: &str) -> Self {
+        self.params
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        return self;
+    }
+
+    pub fn realm(&self) -> Option<&str> {
+        return param(&self.params, "realm");
+    }
+}
+
+impl TypedHeader for WwwAuthenticate {
+    const NAME: &'static str = "WWW-Authenticate";
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (scheme, params) = value.split_once(' ').unwrap_or((value, ""));
+        if !is_token(scheme.as_bytes()) {
+            return None;
+        }
+        return Some(Self {
+            scheme: scheme.to_string(),
+            params: parse_params(&split_list(params, b','))?,
+        });
+    }
+
+    fn encode(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, quote_always(value)))
+            .collect();
+        if params.is_empty() {
+            return self.scheme.clone();
+        }
+        return format!("{} {}", self.scheme, params.join(", "));
+    }
+}
+
+/**
+ * One link of `Link` (RFC 8288), target with its parameters.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkValue {
+    pub target: String,
+    // names lowercase, empty value when the parameter has none
+    pub params: Vec<(String, String)>,
+}
+
+#[allow(dead_code)]
+impl LinkValue {
+    pub fn new(target: &str) -> Self {
+        return Self {
+            target: target.to_string(),
+            params: vec![],
+        };
+    }
+
+    pub fn with_param(mut self, name: &str, value: &str) -> Self {
+        self.params
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        return self;
+    }
+
+    pub fn rel(&self) -> Option<&str> {
+        return param(&self.params, "rel");
+    }
+
+    fn parse(str: &str) -> Option<Self> {
+        let parts = split_list(str, b';');
+        let target = parts.first()?.strip_prefix('<')?.strip_suffix('>')?;
+        if target.contains(['<', '>']) {
+            return None;
+        }
+        return Some(Self {
+            target: target.to_string(),
+            params: parse_params(&parts[1..])?,
+        });
+    }
+}
+
+impl Display for LinkValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut str = format!("<{}>", self.target);
+        encode_params(&mut str, &self.params);
+        return f.write_str(&str);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link(pub Vec<LinkValue>);
+
+#[allow(dead_code)]
+impl Link {
+    /**
+     * First link whose `rel` has `rel`, a relation type compares case-insensitively.
+     */
+    pub fn find(&self, rel: &str) -> Option<&LinkValue> {
+        return self.0.iter().find(|l| {
+            l.rel().is_some_and(|r| {
+                r.split_ascii_whitespace()
+                    .any(|r| r.eq_ignore_ascii_case(rel))
+            })
+        });
+    }
+}
+
+impl TypedHeader for Link {
+    const NAME: &'static str = "Link";
+    const LIST: bool = true;
+
+    fn parse(value: &str) -> Option<Self> {
+        return split_list(value, b',')
+            .into_iter()
+            .map(LinkValue::parse)
+            .collect::<Option<Vec<_>>>()
+            .map(Self);
+    }
+
+    fn encode(&self) -> String {
+        return join(&self.0, ", ");
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryAfter {
+    Delay(Duration),
+    At(SystemTime),
+}
+
+impl TypedHeader for RetryAfter {
+    const NAME: &'static str = "Retry-After";
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if is_digits(value) {
+            return Some(RetryAfter::Delay(Duration::from_secs(value.parse().ok()?)));
+        }
+        return date::Date::parse(value).map(RetryAfter::At);
+    }
+
+    fn encode(&self) -> String {
+        return match self {
+            RetryAfter::Delay(delay) => delay.as_secs().to_string(),
+            RetryAfter::At(time) => date::Date::from(*time).to_rfc1123(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date(pub SystemTime);
+
+impl TypedHeader for Date {
+    const NAME: &'static str = "Date";
+
+    fn parse(value: &str) -> Option<Self> {
+        return date::Date::parse(value).map(Self);
+    }
+
+    fn encode(&self) -> String {
+        return date::Date::from(self.0).to_rfc1123();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLength(pub u64);
+
+impl TypedHeader for ContentLength {
+    const NAME: &'static str = "Content-Length";
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if !is_digits(value) {
+            return None;
+        }
+        return value.parse().ok().map(Self);
+    }
+
+    fn encode(&self) -> String {
+        return self.0.to_string();
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allow(pub Vec<HttpMethod>);
+
+impl TypedHeader for Allow {
+    const NAME: &'static str = "Allow";
+    const LIST: bool = true;
+
+    fn parse(value: &str) -> Option<Self> {
+        return split_list(value, b',')
+            .into_iter()
+            .map(|m| is_token(m.as_bytes()).then(|| HttpMethod::parse(m)))
+            .collect::<Option<Vec<_>>>()
+            .map(Self);
+    }
+
+    fn encode(&self) -> String {
+        return join(&self.0, ", ");
+    }
+}
+
+/**
+ * `Accept` media ranges with their `q` weight.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accept(pub Vec<WeightedValue>);
+
+#[allow(dead_code)]
+impl Accept {
+    /**
+     * Media ranges the client takes, most wanted first. `q=0` ones are left out.
+     */
+    pub fn preferred(&self) -> Vec<&WeightedValue> {
+        let mut values: Vec<&WeightedValue> = self
+            .0
+            .iter()
+            .filter(|v| v.weight().unwrap_or(1.0) > 0.0)
+            .collect();
+        values.sort_by(|a, b| {
+            b.weight()
+                .unwrap_or(1.0)
+                .total_cmp(&a.weight().unwrap_or(1.0))
+        });
+        return values;
+    }
+}
+
+impl TypedHeader for Accept {
+    const NAME: &'static str = "Accept";
+    const LIST: bool = true;
+
+    fn parse(value: &str) -> Option<Self> {
+        let mut values = vec![];
+        for range in split_list(value, b',') {
+            let (range, q) = match range.rsplit_once(';') {
+                Some((range, q)) if q.trim().to_ascii_lowercase().starts_with("q=") => {
+                    (range.trim(), Some(q.trim()[2..].parse::<f64>().ok()?))
+                }
+                _ => (range, None),
+            };
+            if q.is_some_and(|q| !(0.0..=1.0).contains(&q)) {
+                return None;
+            }
+            MediaType::parse(range)?;
+            values.push(WeightedValue::new(range, q));
+        }
+        return Some(Self(values));
+    }
+
+    fn encode(&self) -> String {
+        let values: Vec<String> = self
+            .0
+            .iter()
+            .map(|v| match v.weight() {
+                Some(q) => format!("{};q={}", v.value(), q),
+                None => v.value().to_string(),
+            })
+            .collect();
+        return values.join(", ");
+    }
+}
+
+/**
+ * Split at `sep` outside quoted strings and `<...>`, trimmed, empty elements skipped.
+ */
+fn split_list(value: &str, sep: u8) -> Vec<&str> {
+    let mut res = vec![];
+    let (mut quoted, mut escaped, mut angle) = (false, false, false);
+    let mut start = 0;
+    for (i, b) in value.bytes().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' if quoted => escaped = true,
+            b'"' if !angle => quoted = !quoted,
+            b'<' if !quoted => angle = true,
+            b'>' if !quoted => angle = false,
+            _ if b == sep && !quoted && !angle => {
+                res.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    res.push(value[start..].trim());
+    res.retain(|v| !v.is_empty());
+    return res;
+}
+
+// `name=value` or `name`, names lowercase, values unquoted
+fn parse_params(parts: &[&str]) -> Option<Vec<(String, String)>> {
+    return parts
+        .iter()
+        .map(|part| {
+            let (name, value) = match part.split_once('=') {
+                Some((name, value)) => (name.trim(), unquote(value)?),
+                None => (part.trim(), String::new()),
+            };
+            if !is_token(name.as_bytes()) {
+                return None;
+            }
+            return Some((name.to_ascii_lowercase(), value));
+        })
+        .collect();
+}
+
+fn encode_params(str: &mut String, params: &[(String, String)]) {
+    for (name, value) in params {
+        if value.is_empty() {
+            let _ = write!(str, "; {}", name);
+        } else {
+            let _ = write!(str, "; {}={}", name, quote(value));
+        }
+    }
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    return params
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str());
+}
+
+// token or quoted-string, quotes and escapes removed
+fn unquote(value: &str) -> Option<String> {
+    let value = value.trim();
+    let Some(inner) = value.strip_prefix('"') else {
+        return is_token(value.as_bytes()).then(|| value.to_string());
+    };
+    let inner = inner.strip_suffix('"')?;
+    let mut res = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => res.push(chars.next()?),
+            '"' => return None,
+            c => res.push(c),
+        }
+    }
+    return Some(res);
+}
+
+// token as is, anything else quoted
+fn quote(value: &str) -> String {
+    if is_token(value.as_bytes()) {
+        return value.to_string();
+    }
+    return quote_always(value);
+}
+
+fn quote_always(value: &str) -> String {
+    let mut res = String::with_capacity(value.len() + 2);
+    res.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push('"');
+    return res;
+}
+
+fn join<T: Display>(values: &[T], sep: &str) -> String {
+    return values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(sep);
+}
+
+fn is_digits(value: &str) -> bool {
+    return !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit());
+}
+
+// token68 of RFC 9110 11.2
+fn is_token68(value: &str) -> bool {
+    let body = value.trim_end_matches('=');
+    return !body.is_empty()
+        && body.bytes().all(|b| {
+            b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~' | b'+' | b'/')
+        });
+}
+
+// attr-char of RFC 8187 as is, other bytes percent-encoded
+fn percent_encode(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+            res.push(b as char);
+        } else {
+            let _ = write!(res, "%{:02X}", b);
+        }
+    }
+    return res;
+}
+
+// ext-value of RFC 8187, `charset'language'value`, UTF-8 or ISO-8859-1
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let (charset, _, encoded) = (parts.next()?, parts.next()?, parts.next()?);
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    if charset.eq_ignore_ascii_case("utf-8") {
+        return String::from_utf8(bytes).ok();
+    }
+    if charset.eq_ignore_ascii_case("iso-8859-1") {
+        return Some(bytes.into_iter().map(|b| b as char).collect());
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::http::{
+        typed::{
+            Accept, Allow, Authorization, CacheControl, CacheDirective, ContentDisposition,
+            ContentLength, ContentType, Date, Disposition, Link, LinkValue, MediaType, RetryAfter,
+            TypedHeader, WwwAuthenticate,
+        },
+        value::HttpMethod,
+    };
+
+    // parse, encode and parse again to the same value
+    fn round_trip<H: TypedHeader + PartialEq + std::fmt::Debug>(value: &str) -> H {
+        let header = H::parse(value).unwrap();
+        assert_eq!(H::parse(&header.encode()).as_ref(), Some(&header));
+        return header;
+    }
+
+    #[test]
+    fn test_content_type() {
+        let content_type: ContentType =
+            round_trip("Text/HTML; Charset=\"utf-8\"; q=\"a \\\"b\\\"\"");
+        assert!(content_type.0.is("text/html"));
+        assert_eq!(content_type.0.charset(), Some("utf-8"));
+        assert_eq!(content_type.0.param("Q"), Some("a \"b\""));
+        assert_eq!(
+            content_type.encode(),
+            "text/html; charset=utf-8; q=\"a \\\"b\\\"\""
+        );
+
+        assert_eq!(
+            ContentType::new("text", "plain")
+                .with_charset("utf-8")
+                .encode(),
+            "text/plain; charset=utf-8"
+        );
+        assert!(MediaType::new("image", "*").is("image/png"));
+        assert_eq!(ContentType::parse("text"), None);
+        assert_eq!(ContentType::parse("text/html; charset=\"utf-8"), None);
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let cache: CacheControl =
+            round_trip("no-cache, MAX-AGE=\"60\", private, community=\"UCI\", max-stale");
+        assert_eq!(
+            cache.0,
+            vec![
+                CacheDirective::NoCache,
+                CacheDirective::MaxAge(60),
+                CacheDirective::Private,
+                CacheDirective::Extension("community".to_string(), Some("UCI".to_string())),
+                CacheDirective::MaxStale(None),
+            ]
+        );
+        assert_eq!(cache.max_age(), Some(Duration::from_secs(60)));
+        assert_eq!(
+            cache.encode(),
+            "no-cache, max-age=60, private, community=UCI, max-stale"
+        );
+        assert_eq!(CacheControl::parse("max-age=soon"), None);
+        assert_eq!(CacheControl::parse(", "), None);
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        let disposition: ContentDisposition =
+            round_trip("attachment; filename=\"EURO rates\"; filename*=utf-8''%e2%82%ac%20rates");
+        assert_eq!(disposition.disposition, Disposition::Attachment);
+        assert_eq!(disposition.filename(), Some("€ rates".to_string()));
+
+        let form: ContentDisposition = round_trip("form-data; name=\"field; 1\"");
+        assert_eq!(form.disposition, Disposition::FormData);
+        assert_eq!(form.name(), Some("field; 1"));
+
+        let attachment = ContentDisposition::attachment("résumé.pdf");
+        assert_eq!(
+            attachment.encode(),
+            "attachment; filename=r_sum_.pdf; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+        );
+        assert_eq!(
+            ContentDisposition::parse(&attachment.encode())
+                .unwrap()
+                .filename(),
+            Some("résumé.pdf".to_string())
+        );
+        assert_eq!(
+            ContentDisposition::attachment("a.txt").encode(),
+            "attachment; filename=a.txt"
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        let basic: Authorization = round_trip("basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+        assert_eq!(
+            basic,
+            Authorization::Basic {
+                user: "Aladdin".to_string(),
+                password: "open sesame".to_string(),
+            }
+        );
+        assert_eq!(basic.encode(), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+
+        let bearer: Authorization = round_trip("Bearer mF_9.B5f-4.1JqM");
+        assert_eq!(bearer, Authorization::Bearer("mF_9.B5f-4.1JqM".to_string()));
+        assert_eq!(Authorization::parse("Bearer a b"), None);
+        assert_eq!(Authorization::parse("Basic bm9jb2xvbg=="), None);
+
+        let other: Authorization = round_trip("Digest username=\"a\", realm=\"b\"");
+        assert!(matches!(other, Authorization::Other { scheme, .. } if scheme == "Digest"));
+
+        let challenge: WwwAuthenticate = round_trip("Basic realm=\"simple\", charset=UTF-8");
+        assert_eq!(challenge.realm(), Some("simple"));
+        assert_eq!(
+            WwwAuthenticate::basic("my site").encode(),
+            "Basic realm=\"my site\""
+        );
+    }
+
+    #[test]
+    fn test_link() {
+        let link: Link = round_trip(
+            "<https://example.com/?page=2,3>; rel=\"next last\", </a;b>; rel=prev; title=\"a, b\"",
+        );
+        assert_eq!(
+            link.find("last").map(|l| l.target.as_str()),
+            Some("https://example.com/?page=2,3")
+        );
+        assert_eq!(link.find("PREV").map(|l| l.target.as_str()), Some("/a;b"));
+        assert_eq!(link.find("up"), None);
+        assert_eq!(
+            Link(vec![
+                LinkValue::new("/style.css").with_param("rel", "preload")
+            ])
+            .encode(),
+            "</style.css>; rel=preload"
+        );
+        assert_eq!(Link::parse("/no-brackets; rel=next"), None);
+    }
+
+    #[test]
+    fn test_dates_and_numbers() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(
+            round_trip::<RetryAfter>("120"),
+            RetryAfter::Delay(Duration::from_secs(120))
+        );
+        assert_eq!(
+            round_trip::<RetryAfter>("Sun, 06 Nov 1994 08:49:37 GMT"),
+            RetryAfter::At(time)
+        );
+        assert_eq!(RetryAfter::parse("-1"), None);
+
+        assert_eq!(Date(time).encode(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(round_trip::<ContentLength>(" 42 "), ContentLength(42));
+        assert_eq!(ContentLength::parse("+42"), None);
+
+        let allow: Allow = round_trip("GET, head,POST");
+        assert_eq!(
+            allow.0,
+            vec![HttpMethod::GET, HttpMethod::HEAD, HttpMethod::POST]
+        );
+    }
+
+    #[test]
+    fn test_accept() {
+        let accept: Accept =
+            round_trip("text/*;q=0.3, text/html;level=1, */*;q=0, image/png;q=0.5");
+        let preferred: Vec<&str> = accept
+            .preferred()
+            .iter()
+            .map(|v| v.value().as_str())
+            .collect();
+        assert_eq!(preferred, vec!["text/html;level=1", "image/png", "text/*"]);
+        assert_eq!(Accept::parse("text/html;q=2"), None);
+    }
+}
@@ -1,8 +1,11 @@
-use std::{fmt::Display, hash::Hash, net::SocketAddr};
+use std::{fmt::Display, hash::Hash};
+
+use crate::{http::limit::Limit, process::address::Address};
 
 pub enum HttpVersion {
     Http10,
     Http11,
+    Http2,
 }
 
 #[allow(dead_code)]
@@ -12,6 +15,8 @@ impl HttpVersion {
             Some(HttpVersion::Http10)
         } else if str.eq_ignore_ascii_case("http/1.1") {
             Some(HttpVersion::Http11)
+        } else if str.eq_ignore_ascii_case("http/2") || str.eq_ignore_ascii_case("http/2.0") {
+            Some(HttpVersion::Http2)
         } else {
             None
         };
@@ -29,6 +34,7 @@ impl Display for HttpVersion {
         return f.write_str(match self {
             HttpVersion::Http10 => "HTTP/1.0",
             HttpVersion::Http11 => "HTTP/1.1",
+            HttpVersion::Http2 => "HTTP/2",
         });
     }
 }
@@ -38,6 +44,7 @@ impl Clone for HttpVersion {
         match self {
             Self::Http10 => Self::Http10,
             Self::Http11 => Self::Http11,
+            Self::Http2 => Self::Http2,
         }
     }
 }
@@ -88,6 +95,11 @@ pub enum HttpResponseCode {
     Unauthorized,
     Forbidden,
     NotFound,
+    MethodNotAllowed,
+    RequestTimeout,
+    PayloadTooLarge,
+    UriTooLong,
+    RequestHeaderFieldsTooLarge,
     InternalServerError,
     NotImplemented,
     BadGateway,
@@ -108,6 +120,11 @@ impl HttpResponseCode {
             HttpResponseCode::Unauthorized => 401,
             HttpResponseCode::Forbidden => 403,
             HttpResponseCode::NotFound => 404,
+            HttpResponseCode::MethodNotAllowed => 405,
+            HttpResponseCode::RequestTimeout => 408,
+            HttpResponseCode::PayloadTooLarge => 413,
+            HttpResponseCode::UriTooLong => 414,
+            HttpResponseCode::RequestHeaderFieldsTooLarge => 431,
             HttpResponseCode::InternalServerError => 500,
             HttpResponseCode::NotImplemented => 501,
             HttpResponseCode::BadGateway => 502,
@@ -127,8 +144,13 @@ impl HttpResponseCode {
             HttpResponseCode::BadRequest => "Bad Request",
             HttpResponseCode::Unauthorized => "Unauthorized",
             HttpResponseCode::Forbidden => "Forbidden",
-            HttpResponseCode::NotFound => "NotFound",
-            HttpResponseCode::InternalServerError => "Interna Server Error",
+            HttpResponseCode::NotFound => "Not Found",
+            HttpResponseCode::MethodNotAllowed => "Method Not Allowed",
+            HttpResponseCode::RequestTimeout => "Request Timeout",
+            HttpResponseCode::PayloadTooLarge => "Payload Too Large",
+            HttpResponseCode::UriTooLong => "URI Too Long",
+            HttpResponseCode::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            HttpResponseCode::InternalServerError => "Internal Server Error",
             HttpResponseCode::NotImplemented => "Not Implemented",
             HttpResponseCode::BadGateway => "Bad Gateway",
             HttpResponseCode::ServiceUnavailable => "Service Unavailable",
@@ -142,7 +164,9 @@ pub enum Error {
     ParseFail(String),
     ReadFail(String),
     WriteFail(String),
-    BadRequest(SocketAddr, &'static str),
+    BadRequest(Address, &'static str),
+    TooLarge(Address, Limit),
+    NotImplemented(Address, &'static str),
 }
 
 impl std::fmt::Display for Error {
@@ -152,6 +176,10 @@ impl std::fmt::Display for Error {
             Error::ReadFail(m) => ("read fail", m),
             Error::WriteFail(m) => ("write fail", m),
             Error::BadRequest(remote, msg) => ("bad request", &format!("{} {}", remote, msg)),
+            Error::TooLarge(remote, limit) => ("too large", &format!("{} {}", remote, limit)),
+            Error::NotImplemented(remote, msg) => {
+                ("not implemented", &format!("{} {}", remote, msg))
+            }
         };
 
         return f.write_fmt(format_args!("HttpError: [{}] {}", name.0, name.1));
@@ -178,6 +206,13 @@ impl Hash for WeightedValue {
 
 #[allow(dead_code)]
 impl WeightedValue {
+    pub fn new(value: &str, weight: Option<f64>) -> Self {
+        return Self {
+            value: value.to_string(),
+            weight,
+        };
+    }
+
     pub fn value(&self) -> &String {
         &self.value
     }
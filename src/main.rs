@@ -1,18 +1,31 @@
-use args::Args;
+use args::{Args, Listen};
 use clap::Parser;
-use nix::unistd::getpid;
+use nix::unistd::{Group, User, getpid};
 use std::io::Write;
+use std::path::Path;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
     http::{
+        error_page::{ErrorFormat, ErrorPages},
+        forwarded::TrustedProxies,
         handler::Handler,
-        header::{HttpHeaderValue, content_type},
         http::Http1,
+        http2::http2::Http2,
+        limit::Limits,
         response::HeaderSetter,
+        sse::{Event, EventStream, last_event_id},
+        timeout::Timeouts,
+        typed::{Authorization, ContentType},
         value::HttpResponseCode,
     },
-    server::{Server, ServerArgs, WorkerInfo},
+    process::{Process, datagram::DatagramProcess, echo::EchoProcess, sniff::SniffProcess},
+    server::{
+        AcceptStrategy, Bind, Server, ServerArgs, UdpWorkerInfo, UnixBind, WorkerInfo, WorkerMode,
+    },
+    tls::{ClientAuthMode, TlsConfig, TlsVersion, process::TlsProcess},
     util::date::Date,
 };
 
@@ -20,6 +33,7 @@ mod args;
 mod http;
 mod process;
 mod server;
+mod tls;
 mod util;
 mod worker;
 
@@ -29,17 +43,81 @@ impl Handler for SimpleHandler {
     fn handle(&self, req: &mut http::request::HttpRequest, res: &mut http::response::HttpResponse) {
         res.set_response_code(HttpResponseCode::Ok);
 
+        if req.path() == "/events" {
+            self.handle_events(req, res);
+            return;
+        }
+
         if let Err(e) = writeln!(res, "response") {
             log::error!("error {}", e);
         }
 
+        if let Err(e) = writeln!(
+            res,
+            "from: {} {}://{}",
+            req.client_addr(),
+            req.scheme(),
+            req.host().unwrap_or("-")
+        ) {
+            log::error!("error {}", e);
+        }
+
+        if let Some(Authorization::Basic { user, .. }) = req.header().typed()
+            && let Err(e) = writeln!(res, "user: {}", user)
+        {
+            log::error!("error {}", e);
+        }
+
+        if let Some(cert) = req.client_certificate()
+            && let Err(e) = writeln!(res, "client: {} {}", cert.subject, cert.fingerprint)
+        {
+            log::error!("error {}", e);
+        }
+
         for (k, v) in req.header().iter() {
-            if let Err(e) = writeln!(res, "{}: {}", k, v.join(";")) {
+            if let Err(e) = writeln!(res, "{}: {}", k, v) {
                 log::error!("error {}", e);
             }
         }
 
-        res.set_header(&content_type(HttpHeaderValue::Str("text/plain")));
+        res.set_header(&ContentType::new("text", "plain"));
+    }
+}
+
+impl SimpleHandler {
+    fn handle_events(
+        &self,
+        req: &http::request::HttpRequest,
+        res: &mut http::response::HttpResponse,
+    ) {
+        let start = last_event_id(req)
+            .and_then(|id| id.parse::<u32>().ok())
+            .map(|id| id + 1)
+            .unwrap_or(0);
+
+        let mut events = match EventStream::start(res) {
+            Ok(events) => events,
+            Err(e) => {
+                log::error!("start event stream failed {}", e);
+                return;
+            }
+        };
+        events.set_heartbeat_interval(Duration::from_millis(500));
+
+        for id in start..start + 10 {
+            let event = Event::new(format!("tick {}", id))
+                .id(id.to_string())
+                .event("tick")
+                .retry(Duration::from_secs(1));
+            if events.send(&event).is_err() {
+                return;
+            }
+
+            std::thread::sleep(Duration::from_millis(1000));
+            if events.heartbeat().is_err() {
+                return;
+            }
+        }
     }
 }
 
@@ -65,16 +143,305 @@ fn main() {
     let arg = Args::parse();
     log::info!("server_rs: {:?}", arg);
 
-    let worker_infos = vec![WorkerInfo {
-        host: arg.host.clone(),
-        port: arg.port,
-        worker: arg.worker,
-        process: Rc::new(Http1::new(arg.max_header_size, SimpleHandler)),
-    }];
+    let trusted_proxies = match TrustedProxies::parse(&arg.trusted_proxy) {
+        Ok(trusted_proxies) => trusted_proxies,
+        Err(e) => {
+            log::error!("invalid --trusted-proxy {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let http1 = Http1::new(arg.max_header_size, SimpleHandler)
+        .with_timeouts(Timeouts {
+            header_read: Duration::from_millis(arg.header_timeout_ms),
+            header_deadline: Duration::from_millis(arg.header_deadline_ms),
+            body_read: Duration::from_millis(arg.body_timeout_ms),
+            min_body_rate: arg.min_body_rate,
+            write: Duration::from_millis(arg.timeout_ms),
+        })
+        .with_limits(Limits {
+            request_line: arg.max_request_line,
+            header_count: arg.max_header_count,
+            header_field: arg.max_header_field,
+            body: arg.max_body_size,
+        })
+        .with_trusted_proxies(trusted_proxies)
+        .with_error_pages(error_pages(&arg))
+        .with_panic_budget(arg.panic_budget);
+    let process: Arc<dyn Process> = if arg.h2c {
+        // HTTP/2 buffers each body, without a limit it is only bound by memory
+        let max_body_size = match arg.max_body_size {
+            0 => usize::MAX,
+            max => max as usize,
+        };
+        Arc::new(Http2::new(http1).with_max_body_size(max_body_size))
+    } else {
+        Arc::new(http1)
+    };
+
+    let tls = if arg.tls_cert.is_empty() {
+        None
+    } else {
+        if arg.tls_cert.len() != arg.tls_key.len() {
+            log::error!("--tls-cert and --tls-key must be given in pairs");
+            std::process::exit(1);
+        }
+
+        let mut versions = vec![];
+        for v in &arg.tls_version {
+            match TlsVersion::parse(v) {
+                Some(version) => versions.push(version),
+                None => {
+                    log::error!("unknown TLS version {}", v);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        let config = arg
+            .tls_cert
+            .iter()
+            .zip(arg.tls_key.iter())
+            .fold(TlsConfig::new(), |c, (cert, key)| {
+                c.with_certificate(cert, key)
+            })
+            .with_alpn(arg.tls_alpn.clone())
+            .with_cipher_suites(arg.tls_cipher.clone());
+        let config = if versions.is_empty() {
+            config
+        } else {
+            config.with_versions(versions)
+        };
+
+        Some(match &arg.tls_client_ca {
+            Some(ca) => {
+                let mode = if !arg.tls_client_auth_path.is_empty() {
+                    ClientAuthMode::Paths(arg.tls_client_auth_path.clone())
+                } else {
+                    match arg.tls_client_auth.as_str() {
+                        "optional" => ClientAuthMode::Optional,
+                        "required" => ClientAuthMode::Required,
+                        mode => {
+                            log::error!("unknown client auth mode {}", mode);
+                            std::process::exit(1);
+                        }
+                    }
+                };
+                config.with_client_auth(ca, mode)
+            }
+            None => config,
+        })
+    };
+
+    let listens = if arg.listen.is_empty() {
+        let mut binds = vec![Bind::tcp(arg.host.clone(), arg.port)];
+        if let Some(path) = &arg.unix {
+            binds.push(Bind::Unix(UnixBind::new(path)));
+        }
+        vec![Listen {
+            binds,
+            process: "http".to_string(),
+            tls: tls.is_some(),
+            proxy_protocol: arg.proxy_protocol,
+            workers: None,
+            threads: None,
+            mode: None,
+        }]
+    } else {
+        arg.listen.clone()
+    };
+
+    let mut worker_infos = vec![];
+    let mut udp_infos = vec![];
+    for listen in &listens {
+        let result = if listen.binds.iter().any(|b| b.is_datagram()) {
+            udp_worker_info(listen, &arg).map(|info| udp_infos.push(info))
+        } else {
+            worker_info(listen, &arg, process.clone(), tls.as_ref())
+                .map(|info| worker_infos.push(info))
+        };
+        if let Err(e) = result {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let accept = match AcceptStrategy::parse(&arg.accept) {
+        Some(accept) => accept,
+        None => {
+            log::error!("unknown accept strategy {}", arg.accept);
+            std::process::exit(1);
+        }
+    };
 
     let mut server = Server::new(ServerArgs {
         worker_infos: worker_infos,
+        udp_infos,
+        keep_alive_ms: arg.keep_alive_ms,
         timeout_ms: arg.timeout_ms,
+        accept,
+    });
+    if let Err(e) = server.open_server() {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/**
+ * Error documents of `--error-page`, `--error-format` and `--problem-json`, exits when one is invalid.
+ */
+fn error_pages(arg: &Args) -> ErrorPages {
+    let Some(format) = ErrorFormat::parse(&arg.error_format) else {
+        log::error!("unknown --error-format {}", arg.error_format);
+        std::process::exit(1);
+    };
+    let mut error_pages = ErrorPages::new()
+        .with_default_format(format)
+        .with_problem_json(arg.problem_json);
+
+    for page in &arg.error_page {
+        let (code, path) = match page.split_once('=') {
+            Some((code, path)) => match code.parse::<i32>() {
+                Ok(code) if (400..600).contains(&code) => (Some(code), path),
+                _ => {
+                    log::error!("invalid status of --error-page {}", page);
+                    std::process::exit(1);
+                }
+            },
+            None => (None, page.as_str()),
+        };
+        error_pages = match error_pages.with_file(code, Path::new(path)) {
+            Ok(error_pages) => error_pages,
+            Err(e) => {
+                log::error!("reading --error-page {} failed: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+    }
+    return error_pages;
+}
+
+fn worker_info(
+    listen: &Listen,
+    arg: &Args,
+    http: Arc<dyn Process>,
+    tls: Option<&TlsConfig>,
+) -> Result<WorkerInfo, String> {
+    let process: Arc<dyn Process> = match listen.process.as_str() {
+        "http" => http,
+        "echo" => Arc::new(EchoProcess { prefix: None }),
+        process => return Err(format!("unknown process {process}")),
+    };
+
+    let tls = match (listen.tls, tls) {
+        (false, _) => None,
+        (true, None) => return Err("tls listener needs --tls-cert and --tls-key".to_string()),
+        (true, Some(tls)) => Some(tls.clone()),
+    };
+
+    // TLS and plaintext on the same port
+    let (process, tls): (Arc<dyn Process>, _) = match tls {
+        Some(tls) if arg.sniff => {
+            let tls_process = TlsProcess::new(process.clone(), &tls).map_err(|e| e.to_string())?;
+            let sniff = SniffProcess::new()
+                .with_tls(Arc::new(tls_process))
+                .with_http1(process.clone());
+            let sniff = if arg.h2c && listen.process == "http" {
+                sniff.with_http2(process)
+            } else {
+                sniff
+            };
+            (Arc::new(sniff), None)
+        }
+        tls => (process, tls),
+    };
+
+    let mut binds = vec![];
+    for bind in &listen.binds {
+        binds.push(match bind {
+            Bind::Unix(unix) => Bind::Unix(unix_bind(&unix.path, arg)?),
+            bind => bind.clone(),
+        });
+    }
+
+    let mode = match listen.mode {
+        Some(mode) => mode,
+        None => WorkerMode::parse(&arg.worker_mode)
+            .ok_or_else(|| format!("unknown worker mode {}", arg.worker_mode))?,
+    };
+
+    return Ok(WorkerInfo {
+        binds,
+        mode,
+        worker: listen.workers.unwrap_or(arg.worker),
+        threads: listen.threads.unwrap_or(arg.threads),
+        process,
+        tls,
+        proxy_protocol: listen.proxy_protocol,
+    });
+}
+
+fn udp_worker_info(listen: &Listen, arg: &Args) -> Result<UdpWorkerInfo, String> {
+    if let Some(bind) = listen.binds.iter().find(|b| !b.is_datagram()) {
+        return Err(format!("{bind} can not share workers with UDP"));
+    }
+    if listen.tls || listen.proxy_protocol {
+        return Err("tls and proxy-protocol are not for UDP".to_string());
+    }
+
+    let process: Rc<dyn DatagramProcess> = match listen.process.as_str() {
+        "echo" => Rc::new(EchoProcess { prefix: None }),
+        process => return Err(format!("unknown UDP process {process}")),
+    };
+
+    return Ok(UdpWorkerInfo {
+        binds: listen.binds.clone(),
+        worker: listen.workers.unwrap_or(arg.worker),
+        process,
     });
-    server.open_server();
+}
+
+fn unix_bind(path: &Path, arg: &Args) -> Result<UnixBind, String> {
+    let mut unix = UnixBind::new(path);
+
+    if let Some(mode) = &arg.unix_mode {
+        let mode =
+            u32::from_str_radix(mode, 8).map_err(|_| format!("invalid --unix-mode {mode}"))?;
+        unix = unix.with_mode(mode);
+    }
+
+    if let Some(owner) = &arg.unix_owner {
+        let (user, group) = match owner.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (owner.as_str(), None),
+        };
+
+        let uid = match user {
+            "" => None,
+            user => Some(match user.parse() {
+                Ok(uid) => uid,
+                Err(_) => User::from_name(user)
+                    .ok()
+                    .flatten()
+                    .ok_or_else(|| format!("unknown user {user}"))?
+                    .uid
+                    .as_raw(),
+            }),
+        };
+        let gid = match group {
+            None | Some("") => None,
+            Some(group) => Some(match group.parse() {
+                Ok(gid) => gid,
+                Err(_) => Group::from_name(group)
+                    .ok()
+                    .flatten()
+                    .ok_or_else(|| format!("unknown group {group}"))?
+                    .gid
+                    .as_raw(),
+            }),
+        };
+        unix = unix.with_owner(uid, gid);
+    }
+
+    return Ok(unix);
 }
@@ -0,0 +1,56 @@
+use std::{
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    os::unix::net,
+    path::PathBuf,
+};
+
+/**
+ * Address of either end of a connection.
+ */
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Address {
+    Inet(SocketAddr),
+    // path of the socket, `None` for unnamed socket
+    Unix(Option<PathBuf>),
+    // stdio, in-memory pipe and the like
+    Unnamed,
+}
+
+#[allow(dead_code)]
+impl Address {
+    pub fn inet(&self) -> Option<&SocketAddr> {
+        return match self {
+            Address::Inet(addr) => Some(addr),
+            _ => None,
+        };
+    }
+
+    pub fn ip(&self) -> Option<IpAddr> {
+        return self.inet().map(|addr| addr.ip());
+    }
+}
+
+impl From<SocketAddr> for Address {
+    fn from(addr: SocketAddr) -> Self {
+        return Address::Inet(addr);
+    }
+}
+
+impl From<net::SocketAddr> for Address {
+    fn from(addr: net::SocketAddr) -> Self {
+        return Address::Unix(addr.as_pathname().map(|path| path.to_path_buf()));
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Address::Inet(addr) => addr.fmt(f),
+            Address::Unix(Some(path)) => f.write_fmt(format_args!("unix:{}", path.display())),
+            Address::Unix(None) => f.write_str("unix:"),
+            Address::Unnamed => f.write_str("-"),
+        };
+    }
+}
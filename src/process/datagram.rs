@@ -0,0 +1,60 @@
+use std::{
+    fmt::Display,
+    io::{Error as IoError, ErrorKind},
+    net::UdpSocket,
+};
+
+use crate::process::{Error, address::Address};
+
+/**
+ * Socket a datagram came from, to answer it or send to anyone else.
+ */
+pub trait DatagramSender {
+    fn send_to(&self, data: &[u8], target: &Address) -> std::io::Result<usize>;
+
+    fn local_addr(&self) -> Address {
+        return Address::Unnamed;
+    }
+}
+
+impl DatagramSender for UdpSocket {
+    fn send_to(&self, data: &[u8], target: &Address) -> std::io::Result<usize> {
+        let target = target.inet().ok_or_else(|| {
+            IoError::new(ErrorKind::InvalidInput, format!("{target} is not inet"))
+        })?;
+        return UdpSocket::send_to(self, data, target);
+    }
+
+    fn local_addr(&self) -> Address {
+        return UdpSocket::local_addr(self)
+            .map(Address::Inet)
+            .unwrap_or(Address::Unnamed);
+    }
+}
+
+/**
+ * Counterpart of `Process` for connectionless protocols, called once per received datagram.
+ * There is no connection to hold, so state between datagrams is up to the implementation.
+ */
+#[allow(dead_code)]
+pub trait DatagramProcess {
+    /**
+     * Handle `datagram` from `source`, returns bytes sent through `sender`.
+     */
+    fn process(
+        &self,
+        datagram: &[u8],
+        source: &Address,
+        sender: &dyn DatagramSender,
+    ) -> Result<usize, Error>;
+
+    fn name(&self) -> String {
+        return "datagram process".to_string();
+    }
+}
+
+impl Display for dyn DatagramProcess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.write_str(&self.name());
+    }
+}
@@ -1,9 +1,11 @@
-use std::{
-    io::{ErrorKind, Read, Write},
-    net::{SocketAddr, TcpStream},
-};
+use std::io::{ErrorKind, Read, Write};
 
-use crate::process::{Error, Process};
+use crate::process::{
+    Error, Process,
+    address::Address,
+    datagram::{DatagramProcess, DatagramSender},
+    stream::Stream,
+};
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -12,7 +14,11 @@ pub struct EchoProcess {
 }
 
 impl Process for EchoProcess {
-    fn process(&self, mut stream: TcpStream, client: &SocketAddr) -> Result<(usize, usize), Error> {
+    fn process(
+        &self,
+        mut stream: Box<dyn Stream>,
+        client: &Address,
+    ) -> Result<(usize, usize), Error> {
         let pid = nix::unistd::getpid();
         let mut all_readed = 0;
         let mut all_writed = 0;
@@ -62,7 +68,8 @@ impl Process for EchoProcess {
             all_writed += echo_result.unwrap();
         }
 
-        log::info!(target:"access log", "{pid} {client} {all_readed} {all_writed}");
+        let local = stream.local_addr();
+        log::info!(target:"access log", "{pid} {client} {local} {all_readed} {all_writed}");
 
         return Ok((all_readed, all_writed));
     }
@@ -72,16 +79,48 @@ impl Process for EchoProcess {
     }
 }
 
+/**
+ * UDP echo, each datagram is sent back to where it came from.
+ */
+impl DatagramProcess for EchoProcess {
+    fn process(
+        &self,
+        datagram: &[u8],
+        source: &Address,
+        sender: &dyn DatagramSender,
+    ) -> Result<usize, Error> {
+        let mut reply = vec![];
+        if let Some(prefix) = &self.prefix {
+            reply.extend(prefix.as_bytes());
+            reply.extend(b": ");
+        }
+        reply.extend(datagram);
+
+        let written = sender
+            .send_to(&reply, source)
+            .map_err(|e| Error::IoFail(format!("send to {source} failed: {e}")))?;
+
+        let pid = nix::unistd::getpid();
+        let local = sender.local_addr();
+        log::info!(target:"access log", "{pid} {source} {local} {} {written}", datagram.len());
+        return Ok(written);
+    }
+
+    fn name(&self) -> String {
+        return "EchoProcess".to_string();
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::{
         io::{Read, Write},
-        net::{TcpListener, TcpStream},
+        net::{TcpListener, TcpStream, UdpSocket},
         thread,
         time::Duration,
     };
 
-    use crate::process::{Process, echo::EchoProcess};
+    use crate::process::{Process, address::Address, echo::EchoProcess, pipe::pipe};
 
     #[test]
     fn success() {
@@ -94,7 +133,7 @@ mod test {
 
         let t = thread::spawn(move || {
             let (stream, remote_addr) = listener.accept().unwrap();
-            return process.process(stream, &remote_addr);
+            return process.process(Box::new(stream), &remote_addr.into());
         });
 
         let mut client = TcpStream::connect(local_addr).unwrap();
@@ -115,4 +154,55 @@ mod test {
         assert_eq!(readed, 4);
         assert_eq!(writed, 4);
     }
+
+    #[test]
+    fn success_over_pipe() {
+        let process = EchoProcess { prefix: None };
+        let (server, mut client) = pipe();
+
+        let t = thread::spawn(move || {
+            return process.process(Box::new(server), &Address::Unnamed);
+        });
+
+        client.write_all("echo".as_bytes()).unwrap();
+        let mut v = vec![0; 4];
+        client.read_exact(&mut v).unwrap();
+        assert_eq!("echo".as_bytes(), v);
+
+        drop(client);
+
+        let (readed, writed) = t.join().unwrap().unwrap();
+        assert_eq!(readed, 4);
+        assert_eq!(writed, 4);
+    }
+
+    #[test]
+    fn success_datagram() {
+        use crate::process::datagram::DatagramProcess;
+
+        let process = EchoProcess {
+            prefix: Some("test".to_string()),
+        };
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let written = DatagramProcess::process(
+            &process,
+            b"echo",
+            &client.local_addr().unwrap().into(),
+            &server,
+        )
+        .unwrap();
+        assert_eq!(written, 10);
+
+        let mut buf = [0; 64];
+        let (n, from) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"test: echo");
+        assert_eq!(from, server.local_addr().unwrap());
+
+        assert!(DatagramProcess::process(&process, b"echo", &Address::Unnamed, &server).is_err());
+    }
 }
@@ -0,0 +1,47 @@
+use std::time::Instant;
+
+use crate::process::{Error, address::Address};
+
+/**
+ * Buffers of one connection in the event worker. The worker fills `input` and sends `output`,
+ * the process moves complete messages from one to the other.
+ */
+#[derive(Debug)]
+pub struct Session {
+    pub client_addr: Address,
+    pub local_addr: Address,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    // close after `output` is sent
+    pub close: bool,
+    // first byte of the message still incomplete in `input`, for the process to enforce its deadline
+    pub started: Option<Instant>,
+}
+
+impl Session {
+    pub fn new(client_addr: Address, local_addr: Address) -> Self {
+        return Self {
+            client_addr,
+            local_addr,
+            input: vec![],
+            output: vec![],
+            close: false,
+            started: None,
+        };
+    }
+}
+
+/**
+ * Process driven by readiness of non-blocking sockets, so one worker keeps many connections.
+ * `receive` is called whenever bytes arrive and must not block, a partial message stays in `input`
+ * until the next call. `Err` closes the connection without sending the rest of `output`.
+ */
+pub trait EventProcess {
+    fn receive(&self, session: &mut Session) -> Result<(), Error>;
+
+    /**
+     * Called on every sweep of the worker for a connection with a message `started` and incomplete,
+     * so a deadline holds when no more bytes arrive. May answer into `output` and set `close`.
+     */
+    fn expire(&self, _session: &mut Session) {}
+}
@@ -1,9 +1,41 @@
 use std::{
     fmt::Display,
-    net::{SocketAddr, TcpStream},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
+use crate::process::{address::Address, event::EventProcess, stream::Stream};
+
+pub mod address;
+pub mod datagram;
 pub mod echo;
+pub mod event;
+pub mod pipe;
+pub mod proxy;
+pub mod sniff;
+pub mod stream;
+
+// set by a process of this worker that wants it replaced
+static RECYCLE: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Ask the worker to stop taking connections and exit, so the manager forks a fresh one in its place.
+ * A process calls it when its state can no longer be trusted, e.g. after too many handler panics.
+ */
+pub fn recycle() {
+    RECYCLE.store(true, Ordering::Relaxed);
+}
+
+pub fn recycling() -> bool {
+    return RECYCLE.load(Ordering::Relaxed);
+}
+
+/**
+ * Forget `recycle`, tests share the flag in one process.
+ */
+#[cfg(test)]
+pub fn cancel_recycle() {
+    RECYCLE.store(false, Ordering::Relaxed);
+}
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -13,13 +45,23 @@ pub enum Error {
 }
 
 #[allow(dead_code)]
-pub trait Process {
-    fn process(&self, stream: TcpStream, client_addr: &SocketAddr)
-    -> Result<(usize, usize), Error>;
+pub trait Process: Send + Sync {
+    fn process(
+        &self,
+        stream: Box<dyn Stream>,
+        client_addr: &Address,
+    ) -> Result<(usize, usize), Error>;
 
     fn name(&self) -> String {
         return "process".to_string();
     }
+
+    /**
+     * Same protocol for the event worker, `None` when it only works on blocking streams.
+     */
+    fn event(&self) -> Option<&dyn EventProcess> {
+        return None;
+    }
 }
 
 impl Display for dyn Process {
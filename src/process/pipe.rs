@@ -0,0 +1,211 @@
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read, Write},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::process::{address::Address, stream::Stream};
+
+#[derive(Default)]
+struct Buffer {
+    data: VecDeque<u8>,
+    // one end is dropped
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Channel {
+    buffer: Mutex<Buffer>,
+    readable: Condvar,
+}
+
+impl Channel {
+    fn close(&self) {
+        self.buffer.lock().unwrap().closed = true;
+        self.readable.notify_all();
+    }
+}
+
+/**
+ * One end of in-memory duplex stream made by `pipe`.
+ * Read blocks until the other end writes or drops, `WouldBlock` after read timeout like a socket.
+ * Write never blocks.
+ */
+pub struct PipeStream {
+    incoming: Arc<Channel>,
+    outgoing: Arc<Channel>,
+    read_timeout: Mutex<Option<Duration>>,
+    peer_addr: Address,
+    local_addr: Address,
+}
+
+/**
+ * Connected pair of in-memory streams, handy to run a `Process` without socket.
+ */
+#[allow(dead_code)]
+pub fn pipe() -> (PipeStream, PipeStream) {
+    let a = Arc::new(Channel::default());
+    let b = Arc::new(Channel::default());
+
+    return (
+        PipeStream {
+            incoming: a.clone(),
+            outgoing: b.clone(),
+            read_timeout: Mutex::new(None),
+            peer_addr: Address::Unnamed,
+            local_addr: Address::Unnamed,
+        },
+        PipeStream {
+            incoming: b,
+            outgoing: a,
+            read_timeout: Mutex::new(None),
+            peer_addr: Address::Unnamed,
+            local_addr: Address::Unnamed,
+        },
+    );
+}
+
+#[allow(dead_code)]
+impl PipeStream {
+    /**
+     * Addresses reported by `Stream`, pipe itself has none.
+     */
+    pub fn with_addr(mut self, local_addr: Address, peer_addr: Address) -> Self {
+        self.local_addr = local_addr;
+        self.peer_addr = peer_addr;
+        return self;
+    }
+}
+
+impl Read for PipeStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let deadline = self
+            .read_timeout
+            .lock()
+            .unwrap()
+            .map(|t| Instant::now() + t);
+        let mut buffer = self.incoming.buffer.lock().unwrap();
+
+        while buffer.data.is_empty() && !buffer.closed {
+            buffer = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(ErrorKind::WouldBlock.into());
+                    }
+                    self.incoming
+                        .readable
+                        .wait_timeout(buffer, deadline - now)
+                        .unwrap()
+                        .0
+                }
+                None => self.incoming.readable.wait(buffer).unwrap(),
+            };
+        }
+
+        let len = buf.len().min(buffer.data.len());
+        for (dst, src) in buf.iter_mut().zip(buffer.data.drain(..len)) {
+            *dst = src;
+        }
+        return Ok(len);
+    }
+}
+
+impl Write for PipeStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut buffer = self.outgoing.buffer.lock().unwrap();
+        if buffer.closed {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+
+        buffer.data.extend(buf);
+        self.outgoing.readable.notify_all();
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl Stream for PipeStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        *self.read_timeout.lock().unwrap() = timeout;
+        return Ok(());
+    }
+
+    fn set_write_timeout(&self, _: Option<Duration>) -> std::io::Result<()> {
+        return Ok(());
+    }
+
+    fn peer_addr(&self) -> Address {
+        return self.peer_addr.clone();
+    }
+
+    fn local_addr(&self) -> Address {
+        return self.local_addr.clone();
+    }
+}
+
+impl Drop for PipeStream {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{ErrorKind, Read, Write},
+        thread,
+        time::Duration,
+    };
+
+    use crate::process::{pipe::pipe, stream::Stream};
+
+    #[test]
+    fn test_pipe() {
+        let (mut a, mut b) = pipe();
+
+        a.write_all(b"hello").unwrap();
+        let mut buf = [0; 3];
+        b.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hel");
+
+        let t = thread::spawn(move || {
+            let mut rest = vec![];
+            b.read_to_end(&mut rest).unwrap();
+            return rest;
+        });
+
+        a.write_all(b" world").unwrap();
+        a.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        let mut buf = [0; 1];
+        assert_eq!(
+            a.read(&mut buf).map_err(|e| e.kind()),
+            Err(ErrorKind::WouldBlock)
+        );
+        drop(a);
+
+        assert_eq!(t.join().unwrap(), b"lo world");
+    }
+
+    #[test]
+    fn test_closed_pipe() {
+        let (mut a, b) = pipe();
+        drop(b);
+
+        let mut buf = [0; 1];
+        assert_eq!(a.read(&mut buf).unwrap(), 0);
+        assert_eq!(
+            a.write(b"x").map_err(|e| e.kind()),
+            Err(ErrorKind::BrokenPipe)
+        );
+    }
+}
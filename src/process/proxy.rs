@@ -0,0 +1,556 @@
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    process::{
+        Error, Process,
+        address::Address,
+        stream::{PrefixedStream, Stream},
+    },
+    tls::TlsInfo,
+};
+
+const V1_SIGNATURE: &[u8] = b"PROXY ";
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+// longest v1 header, "PROXY TCP6 <39> <39> <5> <5>\r\n"
+const V1_MAX_LENGTH: usize = 107;
+const V2_HEADER_LENGTH: usize = 16;
+
+#[allow(dead_code)]
+pub const PP2_TYPE_ALPN: u8 = 0x01;
+#[allow(dead_code)]
+pub const PP2_TYPE_AUTHORITY: u8 = 0x02;
+#[allow(dead_code)]
+pub const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+#[allow(dead_code)]
+pub const PP2_TYPE_SSL: u8 = 0x20;
+
+/**
+ * Connection information relayed by a load balancer in PROXY protocol header.
+ * Addresses are `None` for `LOCAL` / `UNKNOWN` connections, e.g. health checks.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProxyHeader {
+    pub version: u8,
+    pub source: Option<Address>,
+    pub destination: Option<Address>,
+    // type-length-value extensions of v2
+    pub tlvs: Vec<(u8, Vec<u8>)>,
+}
+
+#[allow(dead_code)]
+impl ProxyHeader {
+    pub fn tlv(&self, kind: u8) -> Option<&[u8]> {
+        return self
+            .tlvs
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, v)| v.as_slice());
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    return Error::ParseFail(format!("invalid PROXY header: {msg}"));
+}
+
+/**
+ * Parse PROXY header at the start of `data`.
+ * `Ok(None)` when more bytes are needed, otherwise the header and its length.
+ */
+pub fn parse_proxy_header(data: &[u8]) -> Result<Option<(ProxyHeader, usize)>, Error> {
+    let len = data.len().min(V2_SIGNATURE.len());
+    if data[..len] == V2_SIGNATURE[..len] {
+        return if len < V2_SIGNATURE.len() {
+            Ok(None)
+        } else {
+            parse_v2(data)
+        };
+    }
+
+    let len = data.len().min(V1_SIGNATURE.len());
+    if data[..len] == V1_SIGNATURE[..len] {
+        return if len < V1_SIGNATURE.len() {
+            Ok(None)
+        } else {
+            parse_v1(data)
+        };
+    }
+
+    return Err(invalid("no signature"));
+}
+
+fn parse_v1(data: &[u8]) -> Result<Option<(ProxyHeader, usize)>, Error> {
+    let end = match data.windows(2).position(|w| w == b"\r\n") {
+        Some(end) if end + 2 <= V1_MAX_LENGTH => end,
+        Some(_) => return Err(invalid("v1 header too long")),
+        None if data.len() >= V1_MAX_LENGTH => return Err(invalid("v1 header too long")),
+        None => return Ok(None),
+    };
+
+    let line = std::str::from_utf8(&data[..end]).map_err(|_| invalid("v1 not ascii"))?;
+    let fields: Vec<&str> = line.split(' ').collect();
+
+    let mut header = ProxyHeader {
+        version: 1,
+        ..Default::default()
+    };
+    match fields.get(1).copied() {
+        Some("UNKNOWN") => (),
+        Some(family @ ("TCP4" | "TCP6")) => {
+            if fields.len() != 6 {
+                return Err(invalid("v1 field count"));
+            }
+            let ip = |s: &str| -> Result<IpAddr, Error> {
+                let ip: IpAddr = s.parse().map_err(|_| invalid("v1 address"))?;
+                if ip.is_ipv4() != (family == "TCP4") {
+                    return Err(invalid("v1 address family"));
+                }
+                return Ok(ip);
+            };
+            let port = |s: &str| -> Result<u16, Error> {
+                // no leading zero
+                if s.len() > 1 && s.starts_with('0') {
+                    return Err(invalid("v1 port"));
+                }
+                return s.parse().map_err(|_| invalid("v1 port"));
+            };
+
+            header.source = Some(Address::Inet(SocketAddr::new(
+                ip(fields[2])?,
+                port(fields[4])?,
+            )));
+            header.destination = Some(Address::Inet(SocketAddr::new(
+                ip(fields[3])?,
+                port(fields[5])?,
+            )));
+        }
+        _ => return Err(invalid("v1 protocol")),
+    }
+
+    return Ok(Some((header, end + 2)));
+}
+
+fn parse_v2(data: &[u8]) -> Result<Option<(ProxyHeader, usize)>, Error> {
+    if data.len() < V2_HEADER_LENGTH {
+        return Ok(None);
+    }
+
+    let version = data[12] >> 4;
+    let command = data[12] & 0x0f;
+    let family = data[13] >> 4;
+    let length = u16::from_be_bytes([data[14], data[15]]) as usize;
+    if version != 2 {
+        return Err(invalid("v2 version"));
+    }
+    if data.len() < V2_HEADER_LENGTH + length {
+        return Ok(None);
+    }
+    let body = &data[V2_HEADER_LENGTH..V2_HEADER_LENGTH + length];
+
+    let mut header = ProxyHeader {
+        version: 2,
+        ..Default::default()
+    };
+
+    let address_length = match family {
+        0x0 => 0,
+        0x1 => 12,
+        0x2 => 36,
+        0x3 => 216,
+        _ => return Err(invalid("v2 address family")),
+    };
+    if body.len() < address_length {
+        return Err(invalid("v2 address length"));
+    }
+    let (addresses, mut tlvs) = body.split_at(address_length);
+
+    match command {
+        // LOCAL, connection of proxy itself, addresses are ignored
+        0x0 => (),
+        0x1 => match family {
+            0x1 => {
+                let ip = |b: &[u8]| IpAddr::V4(Ipv4Addr::new(b[0], b[1], b[2], b[3]));
+                let port = |b: &[u8]| u16::from_be_bytes([b[0], b[1]]);
+                header.source = Some(Address::Inet(SocketAddr::new(
+                    ip(&addresses[0..4]),
+                    port(&addresses[8..10]),
+                )));
+                header.destination = Some(Address::Inet(SocketAddr::new(
+                    ip(&addresses[4..8]),
+                    port(&addresses[10..12]),
+                )));
+            }
+            0x2 => {
+                let ip = |b: &[u8]| IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(b).unwrap()));
+                let port = |b: &[u8]| u16::from_be_bytes([b[0], b[1]]);
+                header.source = Some(Address::Inet(SocketAddr::new(
+                    ip(&addresses[0..16]),
+                    port(&addresses[32..34]),
+                )));
+                header.destination = Some(Address::Inet(SocketAddr::new(
+                    ip(&addresses[16..32]),
+                    port(&addresses[34..36]),
+                )));
+            }
+            0x3 => {
+                let path = |b: &[u8]| {
+                    let end = b.iter().position(|c| *c == 0).unwrap_or(b.len());
+                    let path = String::from_utf8_lossy(&b[..end]).into_owned();
+                    return Address::Unix((!path.is_empty()).then(|| PathBuf::from(path)));
+                };
+                header.source = Some(path(&addresses[0..108]));
+                header.destination = Some(path(&addresses[108..216]));
+            }
+            // UNSPEC, addresses are unknown
+            _ => (),
+        },
+        _ => return Err(invalid("v2 command")),
+    }
+
+    while !tlvs.is_empty() {
+        if tlvs.len() < 3 {
+            return Err(invalid("v2 TLV"));
+        }
+        let kind = tlvs[0];
+        let len = u16::from_be_bytes([tlvs[1], tlvs[2]]) as usize;
+        let value = tlvs
+            .get(3..3 + len)
+            .ok_or_else(|| invalid("v2 TLV length"))?;
+        header.tlvs.push((kind, value.to_vec()));
+        tlvs = &tlvs[3 + len..];
+    }
+
+    return Ok(Some((header, V2_HEADER_LENGTH + length)));
+}
+
+/**
+ * Read PROXY protocol v1/v2 header sent by a load balancer and run the inner process
+ * with the original client address. The header is mandatory, connection without it is dropped.
+ */
+pub struct ProxyProtocolProcess {
+    inner: Arc<dyn Process>,
+    timeout: Duration,
+}
+
+#[allow(dead_code)]
+impl ProxyProtocolProcess {
+    pub fn new(inner: Arc<dyn Process>) -> Self {
+        return Self {
+            inner,
+            timeout: Duration::from_secs(1),
+        };
+    }
+
+    /**
+     * How long to wait for the whole header.
+     */
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        return self;
+    }
+
+    fn read_header(
+        &self,
+        stream: &mut Box<dyn Stream>,
+        client_addr: &Address,
+    ) -> Result<(ProxyHeader, Vec<u8>), Error> {
+        // a v2 header may be 64 KiB, a client sending a few bytes now and then must not hold the worker
+        let deadline = Instant::now() + self.timeout;
+        let mut data = vec![];
+        let mut buf = [0; 512];
+        loop {
+            if !data.is_empty()
+                && let Some((header, len)) = parse_proxy_header(&data)?
+            {
+                return Ok((header, data.split_off(len)));
+            }
+
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(Error::IoFail(format!(
+                    "{client_addr} PROXY header timed out"
+                )));
+            }
+            let _ = stream.set_read_timeout(Some(left));
+            match stream.read(&mut buf) {
+                Ok(0) => {
+                    return Err(Error::IoFail(format!(
+                        "{client_addr} closed in PROXY header"
+                    )));
+                }
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(Error::IoFail(format!(
+                        "{client_addr} read PROXY header failed: {e}"
+                    )));
+                }
+            }
+        }
+    }
+}
+
+impl Process for ProxyProtocolProcess {
+    fn process(
+        &self,
+        mut stream: Box<dyn Stream>,
+        client_addr: &Address,
+    ) -> Result<(usize, usize), Error> {
+        let (header, rest) = self.read_header(&mut stream, client_addr)?;
+        log::debug!(
+            "{client_addr} PROXY v{} {:?} -> {:?}",
+            header.version,
+            header.source,
+            header.destination
+        );
+
+        let source = header.source.clone().unwrap_or(client_addr.clone());
+        let stream = ProxiedStream {
+            inner: PrefixedStream::new(rest, stream),
+            header,
+        };
+        return self.inner.process(Box::new(stream), &source);
+    }
+
+    fn name(&self) -> String {
+        return format!("proxy+{}", self.inner.name());
+    }
+}
+
+/**
+ * Stream reporting addresses of PROXY header instead of the load balancer connection.
+ */
+struct ProxiedStream {
+    inner: PrefixedStream,
+    header: ProxyHeader,
+}
+
+impl std::io::Read for ProxiedStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        return self.inner.read(buf);
+    }
+}
+
+impl std::io::Write for ProxiedStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return self.inner.write(buf);
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        return self.inner.write_vectored(bufs);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+impl Stream for ProxiedStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.set_read_timeout(timeout);
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.set_write_timeout(timeout);
+    }
+
+    fn peer_addr(&self) -> Address {
+        return self
+            .header
+            .source
+            .clone()
+            .unwrap_or_else(|| self.inner.peer_addr());
+    }
+
+    fn local_addr(&self) -> Address {
+        return self
+            .header
+            .destination
+            .clone()
+            .unwrap_or_else(|| self.inner.local_addr());
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        return self.inner.tls_info();
+    }
+
+    fn proxy_header(&self) -> Option<ProxyHeader> {
+        return Some(self.header.clone());
+    }
+
+    fn send_file(
+        &self,
+        file: &std::fs::File,
+        offset: u64,
+        count: usize,
+    ) -> Option<std::io::Result<usize>> {
+        return self.inner.send_file(file, offset, count);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        net::SocketAddr,
+        path::PathBuf,
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::process::{
+        Error, Process,
+        address::Address,
+        pipe::pipe,
+        proxy::{PP2_TYPE_AUTHORITY, ProxyHeader, ProxyProtocolProcess, parse_proxy_header},
+        stream::Stream,
+    };
+
+    fn inet(addr: &str) -> Option<Address> {
+        return Some(Address::Inet(addr.parse::<SocketAddr>().unwrap()));
+    }
+
+    fn v2(command: u8, family: u8, body: &[u8]) -> Vec<u8> {
+        let mut data = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+        data.push(0x20 | command);
+        data.push(family);
+        data.extend((body.len() as u16).to_be_bytes());
+        data.extend(body);
+        return data;
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let data = b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 443\r\nGET /";
+        let (header, len) = parse_proxy_header(data).unwrap().unwrap();
+        assert_eq!(len, data.len() - 5);
+        assert_eq!(header.source, inet("192.168.0.1:56324"));
+        assert_eq!(header.destination, inet("10.0.0.1:443"));
+
+        let (header, _) = parse_proxy_header(b"PROXY TCP6 ::1 2001:db8::1 1 2\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(header.source, inet("[::1]:1"));
+
+        let (header, len) = parse_proxy_header(b"PROXY UNKNOWN\r\n").unwrap().unwrap();
+        assert_eq!((header.source, len), (None, 15));
+
+        assert!(parse_proxy_header(b"PROX").unwrap().is_none());
+        assert!(parse_proxy_header(b"PROXY TCP4 1.1.1.1").unwrap().is_none());
+        assert!(parse_proxy_header(b"PROXY TCP4 ::1 ::1 1 2\r\n").is_err());
+        assert!(parse_proxy_header(b"PROXY TCP4 1.1.1.1 1.1.1.1 01 2\r\n").is_err());
+        assert!(parse_proxy_header(b"GET / HTTP/1.1\r\n").is_err());
+        assert!(parse_proxy_header(&[b'P'; 200]).is_err());
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let mut body = vec![192, 168, 0, 1, 10, 0, 0, 1, 0xdc, 0x04, 0x01, 0xbb];
+        body.extend([PP2_TYPE_AUTHORITY, 0, 11]);
+        body.extend(b"example.com");
+        let data = v2(1, 0x11, &body);
+
+        assert!(parse_proxy_header(&data[..20]).unwrap().is_none());
+        let (header, len) = parse_proxy_header(&data).unwrap().unwrap();
+        assert_eq!(len, data.len());
+        assert_eq!(
+            header,
+            ProxyHeader {
+                version: 2,
+                source: inet("192.168.0.1:56324"),
+                destination: inet("10.0.0.1:443"),
+                tlvs: vec![(PP2_TYPE_AUTHORITY, b"example.com".to_vec())],
+            }
+        );
+        assert_eq!(
+            header.tlv(PP2_TYPE_AUTHORITY),
+            Some(b"example.com".as_slice())
+        );
+
+        let mut unix = vec![0; 216];
+        unix[..9].copy_from_slice(b"/tmp/sock");
+        let (header, _) = parse_proxy_header(&v2(1, 0x31, &unix)).unwrap().unwrap();
+        assert_eq!(
+            header.source,
+            Some(Address::Unix(Some(PathBuf::from("/tmp/sock"))))
+        );
+        assert_eq!(header.destination, Some(Address::Unix(None)));
+
+        // LOCAL ignores addresses
+        let (header, _) = parse_proxy_header(&v2(0, 0x11, &[0; 12])).unwrap().unwrap();
+        assert_eq!(header.source, None);
+
+        assert!(parse_proxy_header(&v2(1, 0x11, &[0; 4])).is_err());
+        assert!(parse_proxy_header(&v2(2, 0x11, &[0; 12])).is_err());
+    }
+
+    // answers the address it is called with and the rest of stream
+    struct AddressProcess;
+
+    impl Process for AddressProcess {
+        fn process(
+            &self,
+            mut stream: Box<dyn Stream>,
+            client_addr: &Address,
+        ) -> Result<(usize, usize), Error> {
+            let mut rest = [0; 5];
+            stream.read_exact(&mut rest).unwrap();
+            let answer = format!(
+                "{client_addr} {} {}",
+                stream.local_addr(),
+                String::from_utf8_lossy(&rest)
+            );
+            stream.write_all(answer.as_bytes()).unwrap();
+            return Ok((rest.len(), answer.len()));
+        }
+    }
+
+    #[test]
+    fn test_process() {
+        let process = ProxyProtocolProcess::new(Arc::new(AddressProcess));
+        let (server, mut client) = pipe();
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 443\r\nhello")
+            .unwrap();
+
+        let balancer: SocketAddr = "172.16.0.1:40000".parse().unwrap();
+        process
+            .process(Box::new(server), &Address::Inet(balancer))
+            .unwrap();
+
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "192.168.0.1:56324 10.0.0.1:443 hello");
+    }
+
+    #[test]
+    fn test_header_deadline() {
+        let process = ProxyProtocolProcess::new(Arc::new(AddressProcess))
+            .with_timeout(Duration::from_millis(300));
+        let (server, mut client) = pipe();
+
+        // v2 header of a long TLV and a byte every 50ms, each read is well within the timeout
+        let writer = thread::spawn(move || {
+            for b in v2(0x1, 0x1, &[0; 1000]) {
+                if client.write_all(&[b]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let started = Instant::now();
+        let balancer: SocketAddr = "172.16.0.1:40000".parse().unwrap();
+        let result = process.process(Box::new(server), &Address::Inet(balancer));
+        let elapsed = started.elapsed();
+        assert!(result.is_err());
+        assert!(elapsed < Duration::from_millis(1000), "{elapsed:?}");
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+use std::{io::ErrorKind, sync::Arc, time::Duration};
+
+use crate::process::{
+    Error, Process,
+    address::Address,
+    stream::{PrefixedStream, Stream},
+};
+
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const PROXY_V1_SIGNATURE: &[u8] = b"PROXY ";
+const PROXY_V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+// longest method token accepted as HTTP/1
+const MAX_METHOD_LENGTH: usize = 16;
+// enough for every signature above
+const MAX_SNIFF_LENGTH: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Proxy,
+    Tls,
+    Http2,
+    Http1,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Matched {
+    Yes,
+    Maybe,
+    No,
+}
+
+impl Protocol {
+    fn matches(&self, prefix: &[u8]) -> Matched {
+        return match self {
+            Protocol::Proxy => match_signature(prefix, PROXY_V1_SIGNATURE)
+                .or(match_signature(prefix, PROXY_V2_SIGNATURE)),
+            Protocol::Tls => match prefix {
+                // handshake record of TLS 1.x (SSL 3 compatible record version)
+                [0x16] => Matched::Maybe,
+                [0x16, 0x03, ..] => Matched::Yes,
+                _ => Matched::No,
+            },
+            Protocol::Http2 => match_signature(prefix, HTTP2_PREFACE),
+            Protocol::Http1 => {
+                let token_len = prefix.iter().take_while(|b| b.is_ascii_uppercase()).count();
+                if token_len > MAX_METHOD_LENGTH {
+                    Matched::No
+                } else if token_len == prefix.len() {
+                    Matched::Maybe
+                } else if token_len > 0 && prefix[token_len] == b' ' {
+                    Matched::Yes
+                } else {
+                    Matched::No
+                }
+            }
+        };
+    }
+}
+
+impl Matched {
+    fn or(self, other: Matched) -> Matched {
+        return match (self, other) {
+            (Matched::Yes, _) | (_, Matched::Yes) => Matched::Yes,
+            (Matched::Maybe, _) | (_, Matched::Maybe) => Matched::Maybe,
+            _ => Matched::No,
+        };
+    }
+}
+
+fn match_signature(prefix: &[u8], signature: &[u8]) -> Matched {
+    let len = prefix.len().min(signature.len());
+    if prefix[..len] != signature[..len] {
+        return Matched::No;
+    }
+    return if len == signature.len() {
+        Matched::Yes
+    } else {
+        Matched::Maybe
+    };
+}
+
+/**
+ * Pick process by first bytes of connection so that several protocols share one port.
+ * Protocols are tried in order of PROXY, TLS, HTTP/2 preface and HTTP/1 method,
+ * only the configured ones are considered. Peeked bytes are replayed to the chosen process.
+ */
+pub struct SniffProcess {
+    routes: Vec<(Protocol, Arc<dyn Process>)>,
+    fallback: Option<Arc<dyn Process>>,
+    timeout: Duration,
+}
+
+impl Default for SniffProcess {
+    fn default() -> Self {
+        return Self {
+            routes: vec![],
+            fallback: None,
+            timeout: Duration::from_secs(1),
+        };
+    }
+}
+
+#[allow(dead_code)]
+impl SniffProcess {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn with_proxy(self, process: Arc<dyn Process>) -> Self {
+        return self.with_route(Protocol::Proxy, process);
+    }
+
+    pub fn with_tls(self, process: Arc<dyn Process>) -> Self {
+        return self.with_route(Protocol::Tls, process);
+    }
+
+    pub fn with_http2(self, process: Arc<dyn Process>) -> Self {
+        return self.with_route(Protocol::Http2, process);
+    }
+
+    pub fn with_http1(self, process: Arc<dyn Process>) -> Self {
+        return self.with_route(Protocol::Http1, process);
+    }
+
+    /**
+     * Process of a connection matching no protocol, or sending nothing within timeout.
+     */
+    pub fn with_fallback(mut self, process: Arc<dyn Process>) -> Self {
+        self.fallback = Some(process);
+        return self;
+    }
+
+    /**
+     * How long to wait for the first bytes.
+     */
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        return self;
+    }
+
+    fn with_route(mut self, protocol: Protocol, process: Arc<dyn Process>) -> Self {
+        self.routes.retain(|(p, _)| *p != protocol);
+        self.routes.push((protocol, process));
+        return self;
+    }
+
+    fn candidates(&self) -> Vec<Protocol> {
+        return [
+            Protocol::Proxy,
+            Protocol::Tls,
+            Protocol::Http2,
+            Protocol::Http1,
+        ]
+        .into_iter()
+        .filter(|p| self.routes.iter().any(|(r, _)| r == p))
+        .collect();
+    }
+
+    /**
+     * `None` when more bytes are needed. With `complete` the undecided ones are given up.
+     */
+    fn detect(&self, prefix: &[u8], complete: bool) -> Option<Option<Protocol>> {
+        for protocol in self.candidates() {
+            match protocol.matches(prefix) {
+                Matched::Yes => return Some(Some(protocol)),
+                Matched::Maybe if !complete => return None,
+                _ => continue,
+            }
+        }
+        return Some(None);
+    }
+
+    fn sniff(&self, stream: &mut Box<dyn Stream>) -> Result<(Vec<u8>, Option<Protocol>), Error> {
+        let _ = stream.set_read_timeout(Some(self.timeout));
+
+        let mut prefix = vec![];
+        let mut buf = [0; MAX_SNIFF_LENGTH];
+        loop {
+            let complete = prefix.len() >= MAX_SNIFF_LENGTH;
+            if !prefix.is_empty()
+                && let Some(protocol) = self.detect(&prefix, complete)
+            {
+                return Ok((prefix, protocol));
+            }
+
+            match stream.read(&mut buf[..MAX_SNIFF_LENGTH - prefix.len()]) {
+                Ok(0) => break,
+                Ok(n) => prefix.extend_from_slice(&buf[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::IoFail(format!("sniff failed: {e}"))),
+            }
+        }
+
+        if prefix.is_empty() {
+            return Ok((prefix, None));
+        }
+        let protocol = self.detect(&prefix, true).flatten();
+        return Ok((prefix, protocol));
+    }
+}
+
+impl Process for SniffProcess {
+    fn process(
+        &self,
+        mut stream: Box<dyn Stream>,
+        client_addr: &Address,
+    ) -> Result<(usize, usize), Error> {
+        let (prefix, protocol) = self.sniff(&mut stream)?;
+
+        let process = protocol
+            .and_then(|p| self.routes.iter().find(|(r, _)| *r == p))
+            .map(|(_, process)| process)
+            .or(self.fallback.as_ref())
+            .ok_or_else(|| {
+                Error::ParseFail(format!(
+                    "unknown protocol from {client_addr}: {prefix:02x?}"
+                ))
+            })?;
+
+        log::debug!("{client_addr} sniffed {:?} -> {}", protocol, process.name());
+        return process.process(Box::new(PrefixedStream::new(prefix, stream)), client_addr);
+    }
+
+    fn name(&self) -> String {
+        let mut names: Vec<String> = self.routes.iter().map(|(_, p)| p.name()).collect();
+        if let Some(fallback) = &self.fallback {
+            names.push(fallback.name());
+        }
+        return format!("sniff({})", names.join("|"));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use crate::process::{
+        Error, Process,
+        address::Address,
+        pipe::pipe,
+        sniff::{HTTP2_PREFACE, SniffProcess},
+        stream::Stream,
+    };
+
+    // answers its name and the bytes it read
+    struct NamedProcess(&'static str);
+
+    impl Process for NamedProcess {
+        fn process(
+            &self,
+            mut stream: Box<dyn Stream>,
+            _: &Address,
+        ) -> Result<(usize, usize), Error> {
+            let _ = stream.set_read_timeout(Some(Duration::from_millis(10)));
+            let mut received = vec![];
+            let _ = stream.read_to_end(&mut received);
+
+            let answer = format!("{}:{}", self.0, String::from_utf8_lossy(&received));
+            stream.write_all(answer.as_bytes()).unwrap();
+            return Ok((received.len(), answer.len()));
+        }
+
+        fn name(&self) -> String {
+            return self.0.to_string();
+        }
+    }
+
+    fn sniff_process() -> SniffProcess {
+        return SniffProcess::new()
+            .with_proxy(Arc::new(NamedProcess("proxy")))
+            .with_tls(Arc::new(NamedProcess("tls")))
+            .with_http2(Arc::new(NamedProcess("h2")))
+            .with_http1(Arc::new(NamedProcess("h1")))
+            .with_timeout(Duration::from_millis(10));
+    }
+
+    fn dispatch(process: &SniffProcess, data: &[u8]) -> String {
+        let (server, mut client) = pipe();
+        client.write_all(data).unwrap();
+
+        let _ = process.process(Box::new(server), &Address::Unnamed);
+
+        let mut received = vec![];
+        client.read_to_end(&mut received).unwrap();
+        return String::from_utf8_lossy(&received).into_owned();
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let process = sniff_process();
+
+        assert_eq!(
+            dispatch(&process, b"GET / HTTP/1.1\r\n\r\n"),
+            "h1:GET / HTTP/1.1\r\n\r\n"
+        );
+        assert_eq!(
+            dispatch(&process, HTTP2_PREFACE),
+            format!("h2:{}", String::from_utf8_lossy(HTTP2_PREFACE))
+        );
+        assert_eq!(
+            dispatch(&process, b"PROXY TCP4 1.1.1.1 2.2.2.2 1 2\r\n"),
+            "proxy:PROXY TCP4 1.1.1.1 2.2.2.2 1 2\r\n"
+        );
+        assert!(dispatch(&process, b"\x16\x03\x01\x00").starts_with("tls:"));
+        assert!(dispatch(&process, b"\r\n\r\n\0\r\nQUIT\n\x21").starts_with("proxy:"));
+    }
+
+    #[test]
+    fn test_short_or_unknown_input() {
+        let process = sniff_process();
+        // "PRI " is a method token too, without full preface it is HTTP/1
+        assert_eq!(dispatch(&process, b"PRI * HTTP/1.1"), "h1:PRI * HTTP/1.1");
+        // rest of preface never comes
+        assert_eq!(dispatch(&process, b"PRI * HTTP/2.0"), "h1:PRI * HTTP/2.0");
+        assert_eq!(dispatch(&process, b"hello"), "");
+
+        let process = process.with_fallback(Arc::new(NamedProcess("echo")));
+        assert_eq!(dispatch(&process, b"hello"), "echo:hello");
+        assert_eq!(dispatch(&process, b""), "echo:");
+        assert_eq!(dispatch(&process, b"\x16\x01"), "echo:\x16\x01");
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{IoSlice, Read, Stdin, Stdout, Write},
+    net::TcpStream,
+    os::{fd::AsFd, unix::net::UnixStream},
+    rc::Rc,
+    time::Duration,
+};
+
+use nix::{libc, sys::sendfile::sendfile};
+
+use crate::{
+    process::{address::Address, proxy::ProxyHeader},
+    tls::TlsInfo,
+};
+
+/**
+ * Byte stream of a connection handed to `Process`, with address and timeout control.
+ * Wrapper like TLS implements it by delegating to the wrapped stream.
+ * Streams without timeout support accept and ignore them.
+ */
+#[allow(dead_code)]
+pub trait Stream: Read + Write {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+
+    fn peer_addr(&self) -> Address {
+        return Address::Unnamed;
+    }
+
+    fn local_addr(&self) -> Address {
+        return Address::Unnamed;
+    }
+
+    /**
+     * Session of TLS terminated in front of the process, `None` for plain stream.
+     */
+    fn tls_info(&self) -> Option<TlsInfo> {
+        return None;
+    }
+
+    /**
+     * PROXY protocol header received in front of the stream.
+     */
+    fn proxy_header(&self) -> Option<ProxyHeader> {
+        return None;
+    }
+
+    /**
+     * Write `count` bytes of `file` from `offset` without copying them through the process, like sendfile(2).
+     * `None` when the stream can not, e.g. TLS or in-memory streams, the caller copies the file then.
+     */
+    fn send_file(
+        &self,
+        _file: &File,
+        _offset: u64,
+        _count: usize,
+    ) -> Option<std::io::Result<usize>> {
+        return None;
+    }
+}
+
+// sendfile(2) to a socket
+fn send_file_to(
+    socket: impl AsFd,
+    file: &File,
+    offset: u64,
+    count: usize,
+) -> std::io::Result<usize> {
+    let mut offset = offset as libc::off_t;
+    return sendfile(socket, file, Some(&mut offset), count).map_err(std::io::Error::from);
+}
+
+impl Stream for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return TcpStream::set_read_timeout(self, timeout);
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return TcpStream::set_write_timeout(self, timeout);
+    }
+
+    fn peer_addr(&self) -> Address {
+        return TcpStream::peer_addr(self)
+            .map(Address::Inet)
+            .unwrap_or(Address::Unnamed);
+    }
+
+    fn local_addr(&self) -> Address {
+        return TcpStream::local_addr(self)
+            .map(Address::Inet)
+            .unwrap_or(Address::Unnamed);
+    }
+
+    fn send_file(&self, file: &File, offset: u64, count: usize) -> Option<std::io::Result<usize>> {
+        return Some(send_file_to(self, file, offset, count));
+    }
+}
+
+impl Stream for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return UnixStream::set_read_timeout(self, timeout);
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return UnixStream::set_write_timeout(self, timeout);
+    }
+
+    fn peer_addr(&self) -> Address {
+        return UnixStream::peer_addr(self)
+            .map(|addr| addr.into())
+            .unwrap_or(Address::Unix(None));
+    }
+
+    fn local_addr(&self) -> Address {
+        return UnixStream::local_addr(self)
+            .map(|addr| addr.into())
+            .unwrap_or(Address::Unix(None));
+    }
+
+    fn send_file(&self, file: &File, offset: u64, count: usize) -> Option<std::io::Result<usize>> {
+        return Some(send_file_to(self, file, offset, count));
+    }
+}
+
+/**
+ * Stream which replays already read bytes before reading the wrapped stream.
+ * Used when first bytes are consumed to look at, e.g. protocol sniffing.
+ */
+pub struct PrefixedStream {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: Box<dyn Stream>,
+}
+
+impl PrefixedStream {
+    pub fn new(prefix: Vec<u8>, inner: Box<dyn Stream>) -> Self {
+        return Self {
+            prefix,
+            pos: 0,
+            inner,
+        };
+    }
+}
+
+impl Read for PrefixedStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos < self.prefix.len() {
+            let len = buf.len().min(self.prefix.len() - self.pos);
+            buf[..len].copy_from_slice(&self.prefix[self.pos..self.pos + len]);
+            self.pos += len;
+            return Ok(len);
+        }
+        return self.inner.read(buf);
+    }
+}
+
+impl Write for PrefixedStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return self.inner.write(buf);
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        return self.inner.write_vectored(bufs);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+impl Stream for PrefixedStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.set_read_timeout(timeout);
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.set_write_timeout(timeout);
+    }
+
+    fn peer_addr(&self) -> Address {
+        return self.inner.peer_addr();
+    }
+
+    fn local_addr(&self) -> Address {
+        return self.inner.local_addr();
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        return self.inner.tls_info();
+    }
+
+    fn proxy_header(&self) -> Option<ProxyHeader> {
+        return self.inner.proxy_header();
+    }
+
+    fn send_file(&self, file: &File, offset: u64, count: usize) -> Option<std::io::Result<usize>> {
+        return self.inner.send_file(file, offset, count);
+    }
+}
+
+/**
+ * Process standard input and output as a stream, e.g. for inetd style serving.
+ */
+#[allow(dead_code)]
+pub struct StdioStream {
+    stdin: Stdin,
+    stdout: Stdout,
+}
+
+#[allow(dead_code)]
+impl StdioStream {
+    pub fn new() -> Self {
+        return Self {
+            stdin: std::io::stdin(),
+            stdout: std::io::stdout(),
+        };
+    }
+}
+
+impl Read for StdioStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        return self.stdin.read(buf);
+    }
+}
+
+impl Write for StdioStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return self.stdout.write(buf);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.stdout.flush();
+    }
+}
+
+impl Stream for StdioStream {
+    fn set_read_timeout(&self, _: Option<Duration>) -> std::io::Result<()> {
+        return Ok(());
+    }
+
+    fn set_write_timeout(&self, _: Option<Duration>) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+/**
+ * Cloneable handle of a stream.
+ * Request reader and response writer of HTTP use the same stream at the same time,
+ * which `&TcpStream` allowed but an owned `Box<dyn Stream>` does not.
+ */
+#[derive(Clone)]
+pub struct SharedStream {
+    inner: Rc<RefCell<Box<dyn Stream>>>,
+}
+
+impl SharedStream {
+    pub fn new(stream: Box<dyn Stream>) -> Self {
+        return Self {
+            inner: Rc::new(RefCell::new(stream)),
+        };
+    }
+}
+
+impl Read for SharedStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        return self.inner.borrow_mut().read(buf);
+    }
+}
+
+impl Write for SharedStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return self.inner.borrow_mut().write(buf);
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        return self.inner.borrow_mut().write_vectored(bufs);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.borrow_mut().flush();
+    }
+}
+
+impl Stream for SharedStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.borrow().set_read_timeout(timeout);
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.borrow().set_write_timeout(timeout);
+    }
+
+    fn peer_addr(&self) -> Address {
+        return self.inner.borrow().peer_addr();
+    }
+
+    fn local_addr(&self) -> Address {
+        return self.inner.borrow().local_addr();
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        return self.inner.borrow().tls_info();
+    }
+
+    fn proxy_header(&self) -> Option<ProxyHeader> {
+        return self.inner.borrow().proxy_header();
+    }
+
+    fn send_file(&self, file: &File, offset: u64, count: usize) -> Option<std::io::Result<usize>> {
+        return self.inner.borrow().send_file(file, offset, count);
+    }
+}
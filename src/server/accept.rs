@@ -0,0 +1,160 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Error,
+    sync::{Mutex, MutexGuard},
+};
+
+use nix::{
+    fcntl::{FcntlArg, fcntl},
+    libc,
+};
+
+use crate::server::listener::Bind;
+
+/**
+ * How workers of a group wait for connections on the same address.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AcceptStrategy {
+    // every worker polls the shared listeners, a connection wakes all of them
+    #[default]
+    Shared,
+    // one worker at a time waits on the listeners, serialized with `AcceptLock`
+    Mutex,
+    // each worker waits in its own epoll with EPOLLEXCLUSIVE, the kernel wakes one of them
+    Exclusive,
+    // each worker binds its own SO_REUSEPORT socket and the kernel spreads connections by hash
+    ReusePort,
+}
+
+impl AcceptStrategy {
+    pub fn parse(str: &str) -> Option<Self> {
+        return match str {
+            "shared" => Some(AcceptStrategy::Shared),
+            "mutex" => Some(AcceptStrategy::Mutex),
+            "exclusive" => Some(AcceptStrategy::Exclusive),
+            "reuseport" => Some(AcceptStrategy::ReusePort),
+            _ => None,
+        };
+    }
+}
+
+/**
+ * Accept setup of one worker group.
+ */
+pub struct AcceptConfig {
+    pub strategy: AcceptStrategy,
+    // set for `AcceptStrategy::Mutex`
+    pub lock: Option<AcceptLock>,
+    // TCP binds each worker opens for itself with `AcceptStrategy::ReusePort`
+    pub reuse_port: Vec<Bind>,
+}
+
+/**
+ * Accept mutex of a worker group, like `AcceptMutex fcntl` of Apache.
+ * fcntl locks belong to a process, so the threads of a worker take `threads` first.
+ * The file is created before fork and unlinked at once, the inherited descriptor keeps it.
+ */
+pub struct AcceptLock {
+    file: File,
+    threads: Mutex<()>,
+}
+
+pub struct AcceptGuard<'a> {
+    lock: &'a AcceptLock,
+    _thread: MutexGuard<'a, ()>,
+}
+
+impl AcceptLock {
+    pub fn new() -> Result<Self, Error> {
+        let path = std::env::temp_dir().join(format!(
+            "server_rs.{}.{}.accept",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        fs::remove_file(&path)?;
+
+        return Ok(Self {
+            file,
+            threads: Mutex::new(()),
+        });
+    }
+
+    /**
+     * Block until this thread holds the lock. A signal interrupts the wait with `Interrupted`.
+     */
+    pub fn lock(&self) -> Result<AcceptGuard<'_>, Error> {
+        let thread = self.threads.lock().unwrap_or_else(|e| e.into_inner());
+        fcntl(&self.file, FcntlArg::F_SETLKW(&flock(libc::F_WRLCK)))?;
+        return Ok(AcceptGuard {
+            lock: self,
+            _thread: thread,
+        });
+    }
+}
+
+impl Drop for AcceptGuard<'_> {
+    fn drop(&mut self) {
+        let _ = fcntl(&self.lock.file, FcntlArg::F_SETLK(&flock(libc::F_UNLCK)));
+    }
+}
+
+// whole file
+fn flock(lock_type: i32) -> libc::flock {
+    let mut flock: libc::flock = unsafe { std::mem::zeroed() };
+    flock.l_type = lock_type as libc::c_short;
+    flock.l_whence = libc::SEEK_SET as libc::c_short;
+    return flock;
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::server::accept::{AcceptLock, AcceptStrategy};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            AcceptStrategy::parse("reuseport"),
+            Some(AcceptStrategy::ReusePort)
+        );
+        assert_eq!(AcceptStrategy::parse("futex"), None);
+    }
+
+    #[test]
+    fn test_lock_threads() {
+        let lock = Arc::new(AcceptLock::new().unwrap());
+        let inside = Arc::new(AtomicU32::new(0));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                let inside = inside.clone();
+                thread::spawn(move || {
+                    for _ in 0..5 {
+                        let _guard = lock.lock().unwrap();
+                        assert_eq!(inside.fetch_add(1, Ordering::SeqCst), 0);
+                        thread::sleep(Duration::from_millis(1));
+                        inside.fetch_sub(1, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    fmt::Display,
+    fs,
+    io::{Error, ErrorKind},
+    net::{IpAddr, SocketAddr, TcpListener, ToSocketAddrs, UdpSocket},
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
+        unix::{
+            fs::{FileTypeExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::PathBuf,
+};
+
+use nix::sys::socket::{
+    AddressFamily, Backlog, SockFlag, SockType, SockaddrStorage, bind, listen, setsockopt, socket,
+    sockopt::{Ipv6V6Only, ReuseAddr, ReusePort},
+};
+
+use crate::process::{address::Address, stream::Stream};
+
+const BACKLOG: i32 = 1024;
+
+/**
+ * One address a group of workers accepts connections on.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bind {
+    // `v6_only` is IPV6_V6ONLY of IPv6 address, `None` keeps the system default
+    Tcp {
+        host: String,
+        port: u16,
+        v6_only: Option<bool>,
+    },
+    Unix(UnixBind),
+    // datagram socket for `DatagramProcess`
+    Udp {
+        host: String,
+        port: u16,
+        v6_only: Option<bool>,
+    },
+}
+
+#[allow(dead_code)]
+impl Bind {
+    pub fn tcp(host: impl Into<String>, port: u16) -> Self {
+        return Bind::Tcp {
+            host: host.into(),
+            port,
+            v6_only: None,
+        };
+    }
+
+    pub fn udp(host: impl Into<String>, port: u16) -> Self {
+        return Bind::Udp {
+            host: host.into(),
+            port,
+            v6_only: None,
+        };
+    }
+
+    pub fn is_datagram(&self) -> bool {
+        return matches!(self, Bind::Udp { .. });
+    }
+
+    /**
+     * Open listening socket. Errors tell which address failed.
+     */
+    pub fn listen(&self) -> Result<Listener, Error> {
+        let listener = match self {
+            Bind::Tcp {
+                host,
+                port,
+                v6_only,
+            } => Listener::Tcp(TcpListener::from(bind_inet(
+                host,
+                *port,
+                *v6_only,
+                SockType::Stream,
+            )?)),
+            Bind::Unix(unix) => Listener::Unix(unix.bind()?),
+            Bind::Udp {
+                host,
+                port,
+                v6_only,
+            } => Listener::Udp(UdpSocket::from(bind_inet(
+                host,
+                *port,
+                *v6_only,
+                SockType::Datagram,
+            )?)),
+        };
+
+        // workers wait in poll and several of them may wake for one connection
+        listener.set_nonblocking(true)?;
+        return Ok(listener);
+    }
+}
+
+impl Display for Bind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Bind::Tcp { host, port, .. } if host.contains(':') => {
+                f.write_fmt(format_args!("[{host}]:{port}"))
+            }
+            Bind::Tcp { host, port, .. } => f.write_fmt(format_args!("{host}:{port}")),
+            Bind::Unix(unix) => f.write_fmt(format_args!("unix:{}", unix.path.display())),
+            Bind::Udp { host, port, .. } if host.contains(':') => {
+                f.write_fmt(format_args!("udp:[{host}]:{port}"))
+            }
+            Bind::Udp { host, port, .. } => f.write_fmt(format_args!("udp:{host}:{port}")),
+        };
+    }
+}
+
+fn resolve(host: &str, port: u16) -> Result<SocketAddr, Error> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port));
+    }
+
+    return (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::AddrNotAvailable,
+            format!("{host} has no address"),
+        )
+    });
+}
+
+// SO_REUSEPORT lets every worker and a restarted server share the address
+fn bind_inet(
+    host: &str,
+    port: u16,
+    v6_only: Option<bool>,
+    sock_type: SockType,
+) -> Result<OwnedFd, Error> {
+    let addr = resolve(host, port)?;
+    let family = if addr.is_ipv4() {
+        AddressFamily::Inet
+    } else {
+        AddressFamily::Inet6
+    };
+
+    let fd = socket(family, sock_type, SockFlag::SOCK_CLOEXEC, None)?;
+    setsockopt(&fd, ReuseAddr, &true)?;
+    setsockopt(&fd, ReusePort, &true)?;
+    if addr.is_ipv6()
+        && let Some(v6_only) = v6_only
+    {
+        setsockopt(&fd, Ipv6V6Only, &v6_only)?;
+    }
+
+    bind(fd.as_raw_fd(), &SockaddrStorage::from(addr))?;
+    if sock_type == SockType::Stream {
+        listen(&fd, Backlog::new(BACKLOG)?)?;
+    }
+    return Ok(fd);
+}
+
+/**
+ * Unix stream socket path, with permission and owner applied after bind.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixBind {
+    pub path: PathBuf,
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+#[allow(dead_code)]
+impl UnixBind {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        return Self {
+            path: path.into(),
+            mode: None,
+            uid: None,
+            gid: None,
+        };
+    }
+
+    /**
+     * Permission bits of the socket file like `0o660`, clients need write permission to connect.
+     */
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        return self;
+    }
+
+    pub fn with_owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        return self;
+    }
+
+    /**
+     * Bind the socket. A socket file left by a dead server is removed first,
+     * but a live one or a file of other type is an error.
+     */
+    pub fn bind(&self) -> Result<UnixListener, Error> {
+        self.remove_stale()?;
+
+        let listener = UnixListener::bind(&self.path)?;
+        if let Some(mode) = self.mode {
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(mode))?;
+        }
+        if self.uid.is_some() || self.gid.is_some() {
+            std::os::unix::fs::chown(&self.path, self.uid, self.gid)?;
+        }
+        return Ok(listener);
+    }
+
+    fn remove_stale(&self) -> Result<(), Error> {
+        let metadata = match fs::symlink_metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        if !metadata.file_type().is_socket() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", self.path.display()),
+            ));
+        }
+
+        return match UnixStream::connect(&self.path) {
+            Ok(_) => Err(Error::new(
+                ErrorKind::AddrInUse,
+                format!("{} is in use by another server", self.path.display()),
+            )),
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                log::info!("remove stale socket {}", self.path.display());
+                fs::remove_file(&self.path)
+            }
+            Err(e) => Err(e),
+        };
+    }
+}
+
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+    Udp(UdpSocket),
+}
+
+impl Listener {
+    pub fn accept(&self) -> Result<(Box<dyn Stream>, Address), Error> {
+        return match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                // IPv4 client of dual-stack socket comes as ::ffff:a.b.c.d
+                let addr = SocketAddr::new(addr.ip().to_canonical(), addr.port());
+                Ok((Box::new(stream), addr.into()))
+            }
+            Listener::Unix(listener) => {
+                let (stream, addr) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok((Box::new(stream), addr.into()))
+            }
+            Listener::Udp(_) => Err(Error::new(
+                ErrorKind::Unsupported,
+                "datagram socket has no connection",
+            )),
+        };
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), Error> {
+        return match self {
+            Listener::Tcp(listener) => listener.set_nonblocking(nonblocking),
+            Listener::Unix(listener) => listener.set_nonblocking(nonblocking),
+            Listener::Udp(socket) => socket.set_nonblocking(nonblocking),
+        };
+    }
+}
+
+impl AsFd for Listener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        return match self {
+            Listener::Tcp(listener) => listener.as_fd(),
+            Listener::Unix(listener) => listener.as_fd(),
+            Listener::Udp(socket) => socket.as_fd(),
+        };
+    }
+}
+
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        return self.as_fd().as_raw_fd();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::ErrorKind,
+        os::unix::{fs::PermissionsExt, net::UnixListener},
+    };
+
+    use crate::server::listener::{Bind, UnixBind};
+
+    #[test]
+    fn test_tcp_bind() {
+        let listener = Bind::tcp("127.0.0.1", 0).listen().unwrap();
+        assert!(listener.accept().is_err());
+
+        let v6 = Bind::Tcp {
+            host: "::1".to_string(),
+            port: 0,
+            v6_only: Some(true),
+        };
+        assert_eq!(v6.to_string(), "[::1]:0");
+        // no IPv6 in some sandboxes, only the message matters then
+        if let Err(e) = v6.listen() {
+            assert!(!e.to_string().is_empty());
+        }
+
+        let udp = Bind::udp("127.0.0.1", 0).listen().unwrap();
+        assert!(matches!(udp.accept(), Err(e) if e.kind() == ErrorKind::Unsupported));
+        assert_eq!(Bind::udp("::", 53).to_string(), "udp:[::]:53");
+
+        // TEST-NET address is not on any interface
+        let err = Bind::tcp("192.0.2.1", 80).listen().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AddrNotAvailable);
+    }
+
+    #[test]
+    fn test_unix_bind() {
+        let dir = std::env::temp_dir().join(format!("server_rs_bind_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let bind = UnixBind::new(dir.join("server.sock")).with_mode(0o600);
+
+        // stale socket of a dead server
+        drop(UnixListener::bind(&bind.path).unwrap());
+        let listener = bind.bind().unwrap();
+        let mode = fs::metadata(&bind.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // still listening
+        assert_eq!(bind.bind().unwrap_err().kind(), ErrorKind::AddrInUse);
+        drop(listener);
+
+        let file = UnixBind::new(dir.join("file"));
+        fs::write(&file.path, b"").unwrap();
+        assert_eq!(file.bind().unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
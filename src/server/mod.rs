@@ -1,30 +1,92 @@
-use std::{collections::HashMap, net::TcpListener, rc::Rc};
+use std::{collections::HashMap, fmt::Display, rc::Rc, sync::Arc, time::Duration};
 
-use nix::sys::{
-    socket::{
-        setsockopt,
-        sockopt::{ReceiveTimeout, ReuseAddr, ReusePort},
-    },
-    time::TimeVal,
-};
+pub use accept::AcceptStrategy;
+pub use listener::{Bind, UnixBind};
 
 use crate::{
-    process::Process,
-    worker::{group::WorkerGroup, manager::WorkerManager},
+    process::{Process, datagram::DatagramProcess, proxy::ProxyProtocolProcess},
+    server::{
+        accept::{AcceptConfig, AcceptLock},
+        listener::Listener,
+    },
+    tls::{TlsConfig, process::TlsProcess},
+    worker::{Worker, group::WorkerGroup, manager::WorkerManager},
 };
 
+mod accept;
+mod listener;
 mod worker;
 
+/**
+ * How a worker serves its connections.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorkerMode {
+    // one connection at a time, the process owns a blocking stream
+    #[default]
+    Blocking,
+    // many non-blocking connections in an epoll loop, needs `Process::event`
+    Event,
+    // `WorkerInfo::threads` blocking threads in each process
+    Threaded,
+}
+
+impl WorkerMode {
+    pub fn parse(str: &str) -> Option<Self> {
+        return match str {
+            "blocking" => Some(WorkerMode::Blocking),
+            "event" => Some(WorkerMode::Event),
+            "threaded" => Some(WorkerMode::Threaded),
+            _ => None,
+        };
+    }
+}
+
 pub struct WorkerInfo {
-    pub host: String,
-    pub port: u16,
+    // every bind is served by the same workers
+    pub binds: Vec<Bind>,
+    pub mode: WorkerMode,
+    pub worker: u32,
+    // threads of each worker in threaded mode
+    pub threads: u32,
+    pub process: Arc<dyn Process>,
+    // terminate TLS before process when set
+    pub tls: Option<TlsConfig>,
+    // read PROXY protocol header before anything else, for listeners behind load balancer
+    pub proxy_protocol: bool,
+}
+
+/**
+ * Worker group of UDP binds, every datagram goes to `process`.
+ */
+pub struct UdpWorkerInfo {
+    pub binds: Vec<Bind>,
     pub worker: u32,
-    pub process: Rc<dyn Process>,
+    pub process: Rc<dyn DatagramProcess>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    BindFail(String),
+    ConfigFail(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Error::BindFail(msg) => f.write_fmt(format_args!("BindFail({msg})")),
+            Error::ConfigFail(msg) => f.write_fmt(format_args!("ConfigFail({msg})")),
+        };
+    }
 }
 
 pub struct ServerArgs {
     pub worker_infos: Vec<WorkerInfo>,
+    pub udp_infos: Vec<UdpWorkerInfo>,
     pub timeout_ms: u64,
+    pub accept: AcceptStrategy,
+    // idle time before event worker drops a keep-alive connection
+    pub keep_alive_ms: u64,
 }
 
 pub struct Server {
@@ -36,60 +98,191 @@ impl Server {
         return Self { config };
     }
 
-    pub fn open_server(&mut self) {
+    /**
+     * Bind every address, then fork workers and supervise them until SIGINT.
+     * Nothing is forked when any bind fails.
+     */
+    pub fn open_server(&mut self) -> Result<(), Error> {
         let config = &self.config;
-        let listeners = Rc::new(
-            config
+        let binds: Vec<&Bind> = config
+            .worker_infos
+            .iter()
+            .flat_map(|i| &i.binds)
+            .chain(config.udp_infos.iter().flat_map(|i| &i.binds))
+            .collect();
+
+        let mut stream_binds = config.worker_infos.iter().flat_map(|i| &i.binds);
+        if let Some(bind) = stream_binds.find(|b| b.is_datagram()) {
+            return Err(Error::ConfigFail(format!(
+                "{bind} needs a datagram process"
+            )));
+        }
+        // TLS and PROXY protocol wrap blocking streams only
+        if let Some(i) = config.worker_infos.iter().find(|i| {
+            i.mode == WorkerMode::Event
+                && (i.tls.is_some() || i.proxy_protocol || i.process.event().is_none())
+        }) {
+            return Err(Error::ConfigFail(format!(
+                "{} can not run in event mode",
+                i.process.name()
+            )));
+        }
+        if config.accept == AcceptStrategy::Mutex
+            && let Some(i) = config
                 .worker_infos
                 .iter()
-                .flat_map(|i| {
-                    let ip = format!("{}:{}", i.host, i.port);
-                    let listener = TcpListener::bind(ip.clone()).unwrap();
+                .find(|i| i.mode == WorkerMode::Event)
+        {
+            return Err(Error::ConfigFail(format!(
+                "{} in event mode can not use accept mutex",
+                i.process.name()
+            )));
+        }
+        let mut datagram_binds = config.udp_infos.iter().flat_map(|i| &i.binds);
+        if let Some(bind) = datagram_binds.find(|b| !b.is_datagram()) {
+            return Err(Error::ConfigFail(format!("{bind} is not a UDP bind")));
+        }
 
-                    if let Err(e) = setsockopt(&listener, ReuseAddr, &true) {
-                        log::error!(target: "TcpWorker::run", "set ReuseAddr failed: [{e}]");
-                        return Err(e);
-                    }
+        let mut listeners = HashMap::new();
+        for bind in &binds {
+            let key = bind.to_string();
+            let result = if listeners.contains_key(&key) {
+                Err(Error::BindFail(format!("{key} is bound twice")))
+            } else {
+                bind.listen()
+                    .map_err(|e| Error::BindFail(format!("{key}: {e}")))
+            };
 
-                    if let Err(e) = setsockopt(&listener, ReusePort, &true) {
-                        log::error!(target: "TcpWorker::run", "set ReusePort failed: [{e}]");
-                        return Err(e);
-                    }
+            match result {
+                Ok(listener) => {
+                    log::info!(target: "Server::open_server", "listen {key}");
+                    listeners.insert(key, listener);
+                }
+                Err(e) => {
+                    remove_unix_sockets(&binds, &listeners);
+                    return Err(e);
+                }
+            }
+        }
+        // every worker binds for itself, the kernel would queue connections on these too
+        if config.accept == AcceptStrategy::ReusePort {
+            listeners.retain(|_, listener| !matches!(listener, Listener::Tcp(_)));
+        }
+        let listeners = Rc::new(listeners);
 
-                    // accept 과정에서 시그널 받으면 취소되도록 설정
-                    if let Err(e) = setsockopt(&listener, ReceiveTimeout, &TimeVal::new(2, 0)) {
-                        log::error!(target: "TcpWorker::run", "[] set ReceiveTimeout failed: [{e}]");
-                        return Err(e);
+        let mut group: Vec<WorkerGroup> = vec![];
+        for i in &config.worker_infos {
+            let keys = i.binds.iter().map(|b| b.to_string()).collect();
+            let accept = match accept_config(config.accept, &i.binds) {
+                Ok(accept) => accept,
+                Err(e) => {
+                    remove_unix_sockets(&binds, &listeners);
+                    return Err(e);
+                }
+            };
+            if i.mode == WorkerMode::Event {
+                group.push(WorkerGroup::new(
+                    i.worker,
+                    Rc::new(worker::EventWorker {
+                        listeners: listeners.clone(),
+                        binds: keys,
+                        accept,
+                        process: i.process.clone(),
+                        keep_alive: Duration::from_millis(config.keep_alive_ms),
+                    }),
+                ));
+                continue;
+            }
+
+            let process: Arc<dyn Process> = match &i.tls {
+                Some(tls) => match TlsProcess::new(i.process.clone(), tls) {
+                    Ok(p) => Arc::new(p),
+                    Err(e) => {
+                        remove_unix_sockets(&binds, &listeners);
+                        return Err(Error::ConfigFail(format!("{}: {e}", i.binds[0])));
                     }
+                },
+                None => i.process.clone(),
+            };
+            let process: Arc<dyn Process> = if i.proxy_protocol {
+                Arc::new(ProxyProtocolProcess::new(process))
+            } else {
+                process
+            };
 
-                    return Ok((ip, listener));
+            let worker: Rc<dyn Worker> = if i.mode == WorkerMode::Threaded {
+                Rc::new(worker::ThreadedWorker {
+                    timeout_ms: config.timeout_ms,
+                    threads: i.threads.max(1),
+                    listeners: listeners.clone(),
+                    binds: keys,
+                    accept,
+                    process,
                 })
-                .fold(HashMap::new(), |m, pair| {
-                    let mut new_m = HashMap::from(m);
-                    new_m.insert(pair.0, pair.1);
-                    return new_m;
-                }),
-        );
+            } else {
+                Rc::new(worker::SocketWorker {
+                    timeout_ms: config.timeout_ms,
+                    listeners: listeners.clone(),
+                    binds: keys,
+                    accept,
+                    process,
+                })
+            };
+            group.push(WorkerGroup::new(i.worker, worker));
+        }
 
-        let group: Vec<WorkerGroup> = config
-            .worker_infos
-            .iter()
-            .map(|i| {
-                return WorkerGroup::new(
-                    i.worker,
-                    Rc::new(worker::TcpWorker {
-                        timeout_ms: config.timeout_ms,
-                        listeners: listeners.clone(),
-                        host: format!("{}:{}", i.host, i.port),
-                        tcp_process: i.process.clone(),
-                    }),
-                );
-            })
-            .collect();
+        for i in &config.udp_infos {
+            group.push(WorkerGroup::new(
+                i.worker,
+                Rc::new(worker::UdpWorker {
+                    listeners: listeners.clone(),
+                    binds: i.binds.iter().map(|b| b.to_string()).collect(),
+                    process: i.process.clone(),
+                }),
+            ));
+        }
 
         let manager = WorkerManager::new(group);
         let mut group_list = manager.start();
 
         manager.run(&mut group_list);
+
+        remove_unix_sockets(&binds, &listeners);
+        return Ok(());
+    }
+}
+
+fn accept_config(strategy: AcceptStrategy, binds: &[Bind]) -> Result<AcceptConfig, Error> {
+    let lock = if strategy == AcceptStrategy::Mutex {
+        let lock = AcceptLock::new().map_err(|e| Error::ConfigFail(format!("accept lock: {e}")))?;
+        Some(lock)
+    } else {
+        None
+    };
+    let reuse_port = if strategy == AcceptStrategy::ReusePort {
+        binds
+            .iter()
+            .filter(|b| matches!(b, Bind::Tcp { .. }))
+            .cloned()
+            .collect()
+    } else {
+        vec![]
+    };
+
+    return Ok(AcceptConfig {
+        strategy,
+        lock,
+        reuse_port,
+    });
+}
+
+// socket files this server created
+fn remove_unix_sockets(binds: &[&Bind], listeners: &HashMap<String, Listener>) {
+    for bind in binds {
+        if let Bind::Unix(unix) = bind
+            && listeners.contains_key(&bind.to_string())
+        {
+            let _ = std::fs::remove_file(&unix.path);
+        }
     }
 }
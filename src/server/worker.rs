@@ -1,31 +1,71 @@
 use std::{
-    collections::HashMap, net::TcpListener, os::fd::AsRawFd, process::exit, rc::Rc, time::Duration
+    collections::HashMap,
+    fmt::Display,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, UdpSocket},
+    os::{
+        fd::{AsFd, AsRawFd},
+        unix::net::UnixStream,
+    },
+    process::exit,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use nix::{
+    errno::Errno,
     libc::{self, close, siginfo_t},
-    sys::{
-        signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction},
-        socket::{
-            setsockopt,
-            sockopt::{ReceiveTimeout, ReuseAddr, ReusePort},
-        },
-        time::TimeVal,
-    },
+    poll::{PollFd, PollFlags, PollTimeout, poll},
+    sys::epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags},
+    sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction},
     unistd::getpid,
 };
 
-use crate::process::Process;
+use crate::process::{
+    self, Process,
+    address::Address,
+    datagram::DatagramProcess,
+    event::{EventProcess, Session},
+    stream::Stream,
+};
+use crate::server::{
+    accept::{AcceptConfig, AcceptLock, AcceptStrategy},
+    listener::{Bind, Listener},
+};
 use crate::worker::Worker;
 
-static mut RUNNING: bool = true;
+// cleared by SIGINT, read by every thread of the worker
+static RUNNING: AtomicBool = AtomicBool::new(true);
+
+// wake up now and then to see RUNNING
+const POLL_TIMEOUT_MS: u16 = 1000;
+// events taken by one epoll_wait
+const MAX_EVENTS: usize = 256;
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+// largest UDP payload
+const MAX_DATAGRAM_SIZE: usize = 65535;
+// pause of accept when descriptors or memory ran out
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(50);
 
-extern "C" fn tcpworker_exit_signal_handler(sig_no: i32, si: *mut siginfo_t, _: *mut libc::c_void) {
-    unsafe { RUNNING = false };
+extern "C" fn worker_exit_signal_handler(sig_no: i32, si: *mut siginfo_t, _: *mut libc::c_void) {
+    RUNNING.store(false, Ordering::Relaxed);
 
     let pid = getpid();
     let si_code = (unsafe { *si }).si_code;
-    log::trace!(target:"tcpworker_exit_signal_handler", "{sig_no}/{si_code} received in TcpWorker[{pid}]");
+    log::trace!(target:"worker_exit_signal_handler", "{sig_no}/{si_code} received in SocketWorker[{pid}]");
+}
+
+// until SIGINT, or the process asked for the worker to be replaced
+fn running() -> bool {
+    if process::recycling() {
+        return false;
+    }
+    return RUNNING.load(Ordering::Relaxed);
 }
 
 fn register_signal() {
@@ -33,7 +73,7 @@ fn register_signal() {
         sigaction(
             Signal::SIGINT,
             &SigAction::new(
-                SigHandler::SigAction(tcpworker_exit_signal_handler),
+                SigHandler::SigAction(worker_exit_signal_handler),
                 SaFlags::SA_SIGINFO,
                 SigSet::empty(),
             ),
@@ -43,83 +83,870 @@ fn register_signal() {
     }
 }
 
-pub struct TcpWorker {
+/**
+ * Accepts connections on every bind of its group, TCP or Unix, and runs the process on them.
+ * Listeners are non-blocking and waited together as `AcceptStrategy` says, so a worker sleeping for one bind
+ * still serves the others.
+ */
+pub struct SocketWorker {
     pub timeout_ms: u64,
-    pub listeners: Rc<HashMap<String, TcpListener>>,
-    pub host: String,
-    pub tcp_process: Rc<dyn Process>,
+    pub listeners: Rc<HashMap<String, Listener>>,
+    pub binds: Vec<String>,
+    pub accept: AcceptConfig,
+    pub process: Arc<dyn Process>,
 }
 
-impl Worker for TcpWorker {
+impl Worker for SocketWorker {
     fn run(&self) {
-        let tcp_listener_opt = self.listeners.get(&self.host);
-        if tcp_listener_opt.is_none() {
+        let reuse_port = open_reuse_port(&self.accept.reuse_port);
+        let mut listeners = own_listeners(&self.listeners, &self.binds);
+        listeners.extend(reuse_port.iter());
+        if listeners.is_empty() {
             return;
         }
 
-        let tcp_listener = tcp_listener_opt.unwrap();
+        let mut acceptor = Acceptor::new(listeners, &self.accept);
+        while running() {
+            for (stream, client) in acceptor.accept() {
+                acceptor.dispatched();
+                serve(stream, client, self.process.as_ref(), self.timeout_ms);
+            }
+        }
+        acceptor.report("SocketWorker");
+    }
+
+    fn init(&self) {
+        let pid = nix::unistd::getpid();
+        let process = &self.process.name();
+        log::trace!(target: "SocketWorker.init", "SocketWorker start[{pid}:{process}]");
+        // close unnecessary sockets
+        close_others(&self.listeners, &self.binds);
+
+        register_signal();
+    }
+
+    fn cleanup(&self) {
+        let pid = nix::unistd::getpid();
+        let process = &self.process.name();
+        log::trace!(target: "SocketWorker.cleanup", "SocketWorker stop[{pid}:{process}]");
+        close_own(&self.listeners, &self.binds);
+    }
+}
+
+fn serve(stream: Box<dyn Stream>, client: Address, process: &dyn Process, timeout_ms: u64) {
+    let _ = stream.set_write_timeout(Some(Duration::from_millis(timeout_ms)));
+    let process_result = process.process(stream, &client);
+    match process_result {
+        Ok((r, w)) => {
+            log::trace!("{} r:{} o:{}", client, r, w)
+        }
+        Err(err) => log::warn!("process failed {:?}", err),
+    }
+}
+
+/**
+ * `SocketWorker` with a pool of threads in one process, like the worker MPM of Apache.
+ * Every thread waits on the same listeners and serves one connection at a time,
+ * so a process holds `threads` connections while sharing the process and its memory.
+ */
+pub struct ThreadedWorker {
+    pub timeout_ms: u64,
+    pub threads: u32,
+    pub listeners: Rc<HashMap<String, Listener>>,
+    pub binds: Vec<String>,
+    pub accept: AcceptConfig,
+    pub process: Arc<dyn Process>,
+}
+
+impl Worker for ThreadedWorker {
+    fn run(&self) {
+        let listeners = own_listeners(&self.listeners, &self.binds);
+        if listeners.is_empty() && self.accept.reuse_port.is_empty() {
+            return;
+        }
 
-        while unsafe { RUNNING } {
-            let stream_result = tcp_listener.accept();
+        let process: &dyn Process = self.process.as_ref();
+        let listeners = &listeners;
+        let accept = &self.accept;
+        let timeout_ms = self.timeout_ms;
+        thread::scope(|scope| {
+            for i in 0..self.threads {
+                let spawned = thread::Builder::new()
+                    .name(format!("worker-{i}"))
+                    .spawn_scoped(scope, move || {
+                        // SO_REUSEPORT socket of each thread
+                        let reuse_port = open_reuse_port(&accept.reuse_port);
+                        let mut listeners = listeners.clone();
+                        listeners.extend(reuse_port.iter());
 
-            match stream_result {
-                Ok((stream, client)) => {
-                    let _ = stream.set_write_timeout(Some(Duration::from_millis(self.timeout_ms)));
-                    let process_result = self.tcp_process.process(stream, &client);
-                    match process_result {
-                        Ok((r, w)) => {
-                            log::trace!("{} r:{} o:{}", client, r, w)
+                        let mut acceptor = Acceptor::new(listeners, accept);
+                        while running() {
+                            for (stream, client) in acceptor.accept() {
+                                acceptor.dispatched();
+                                serve(stream, client, process, timeout_ms);
+                            }
                         }
-                        Err(err) => log::warn!("process failed {:?}", err),
+                        acceptor.report("ThreadedWorker");
+                    });
+                if let Err(e) = spawned {
+                    log::error!(target: "ThreadedWorker::run", "spawn thread failed: {e}");
+                    break;
+                }
+            }
+        });
+    }
+
+    fn init(&self) {
+        let pid = nix::unistd::getpid();
+        let process = &self.process.name();
+        let threads = self.threads;
+        log::trace!(target: "ThreadedWorker.init", "ThreadedWorker start[{pid}:{process}] threads:{threads}");
+        close_others(&self.listeners, &self.binds);
+
+        register_signal();
+    }
+
+    fn cleanup(&self) {
+        let pid = nix::unistd::getpid();
+        let process = &self.process.name();
+        log::trace!(target: "ThreadedWorker.cleanup", "ThreadedWorker stop[{pid}:{process}]");
+        close_own(&self.listeners, &self.binds);
+    }
+}
+
+/**
+ * Waits for connections of one worker thread the way `AcceptStrategy` says.
+ * Counts accepted connections and wakeups which found nothing, and times each connection from the wakeup which
+ * found it to its dispatch, to compare the strategies.
+ */
+struct Acceptor<'a> {
+    listeners: Vec<&'a Listener>,
+    strategy: AcceptStrategy,
+    lock: Option<&'a AcceptLock>,
+    // `listeners` registered with EPOLLEXCLUSIVE, made again after a failure
+    epoll: Option<Epoll>,
+    accepted: u64,
+    missed: u64,
+    // end of the last wait
+    woke: Instant,
+    latency: Latency,
+}
+
+impl<'a> Acceptor<'a> {
+    fn new(listeners: Vec<&'a Listener>, config: &'a AcceptConfig) -> Self {
+        return Self {
+            listeners,
+            strategy: config.strategy,
+            lock: config.lock.as_ref(),
+            epoll: None,
+            accepted: 0,
+            missed: 0,
+            woke: Instant::now(),
+            latency: Latency::default(),
+        };
+    }
+
+    /**
+     * Wait up to POLL_TIMEOUT_MS, then accept a connection of every ready listener.
+     */
+    fn accept(&mut self) -> Vec<(Box<dyn Stream>, Address)> {
+        if self.strategy == AcceptStrategy::Exclusive && self.epoll.is_none() {
+            match exclusive_epoll(&self.listeners) {
+                Ok(epoll) => self.epoll = Some(epoll),
+                Err(e) => {
+                    accept_failed(&e.into());
+                    return vec![];
+                }
+            }
+        }
+
+        // held until accepted, released before the process runs
+        let _guard = match self.lock {
+            Some(lock) => match lock.lock() {
+                Ok(guard) => Some(guard),
+                Err(e) => {
+                    accept_failed(&e);
+                    return vec![];
+                }
+            },
+            None => None,
+        };
+        if !running() {
+            return vec![];
+        }
+
+        let ready = match &self.epoll {
+            Some(epoll) => wait_epoll(epoll, &self.listeners),
+            None => wait_ready(&self.listeners),
+        };
+        self.woke = Instant::now();
+        let mut res = vec![];
+        for listener in &ready {
+            match listener.accept() {
+                Ok(accepted) => res.push(accepted),
+                Err(err) => accept_failed(&err),
+            }
+        }
+
+        if !ready.is_empty() && res.is_empty() {
+            self.missed += 1;
+        }
+        self.accepted += res.len() as u64;
+        return res;
+    }
+
+    // a connection of the last `accept` goes to the process now
+    fn dispatched(&mut self) {
+        self.latency.record(self.woke.elapsed());
+    }
+
+    fn report(&self, worker: &str) {
+        let pid = nix::unistd::getpid();
+        log::info!(target: "Worker::run", "{worker}[{pid}] {:?} accepted:{} empty wakeups:{} latency:{}", self.strategy, self.accepted, self.missed, self.latency);
+    }
+}
+
+/**
+ * One policy for a failed accept in every worker. A connection another worker took or the client dropped is
+ * skipped. Running out of descriptors, buffers or memory backs off for `ACCEPT_BACKOFF` and tries again,
+ * so connections being served can finish and free them. Anything else means the listener is broken, the worker exits.
+ */
+fn accept_failed(err: &std::io::Error) {
+    match err.kind() {
+        std::io::ErrorKind::WouldBlock
+        | std::io::ErrorKind::Interrupted
+        | std::io::ErrorKind::ConnectionAborted => (),
+        _ if matches!(
+            err.raw_os_error(),
+            Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM)
+        ) =>
+        {
+            log::warn!(target: "Worker::run", "Accept failed, backing off: {err}");
+            thread::sleep(ACCEPT_BACKOFF);
+        }
+        _ => {
+            log::error!(target: "Worker::run", "Accept failed: {err}");
+            exit(1);
+        }
+    }
+}
+
+/**
+ * Time from the wakeup which found a connection to its dispatch, min / avg / max.
+ */
+#[derive(Debug, Default)]
+struct Latency {
+    count: u32,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl Latency {
+    fn record(&mut self, latency: Duration) {
+        self.min = if self.count == 0 {
+            latency
+        } else {
+            self.min.min(latency)
+        };
+        self.max = self.max.max(latency);
+        self.total += latency;
+        self.count += 1;
+    }
+}
+
+impl Display for Latency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.count == 0 {
+            return f.write_str("-");
+        }
+        let avg = self.total / self.count;
+        return write!(f, "{:?}/{:?}/{:?}", self.min, avg, self.max);
+    }
+}
+
+fn exclusive_epoll(listeners: &[&Listener]) -> Result<Epoll, Errno> {
+    let epoll = Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC)?;
+    for (token, listener) in listeners.iter().enumerate() {
+        epoll.add(
+            listener.as_fd(),
+            EpollEvent::new(
+                EpollFlags::EPOLLIN | EpollFlags::EPOLLEXCLUSIVE,
+                token as u64,
+            ),
+        )?;
+    }
+    return Ok(epoll);
+}
+
+// listeners reported by epoll within POLL_TIMEOUT_MS
+fn wait_epoll<'a>(epoll: &Epoll, listeners: &[&'a Listener]) -> Vec<&'a Listener> {
+    let mut events = vec![EpollEvent::empty(); listeners.len()];
+    let n = match epoll.wait(&mut events, PollTimeout::from(POLL_TIMEOUT_MS)) {
+        Ok(n) => n,
+        Err(Errno::EINTR) => 0,
+        Err(e) => {
+            log::error!(target: "Worker::run", "epoll_wait failed: {e}");
+            exit(1);
+        }
+    };
+    return events[..n]
+        .iter()
+        .filter_map(|event| listeners.get(event.data() as usize).copied())
+        .collect();
+}
+
+// sockets of this worker for `AcceptStrategy::ReusePort`
+fn open_reuse_port(binds: &[Bind]) -> Vec<Listener> {
+    let mut res = vec![];
+    for bind in binds {
+        match bind.listen() {
+            Ok(listener) => res.push(listener),
+            Err(e) => {
+                log::error!(target: "Worker::run", "{bind}: {e}");
+                exit(1);
+            }
+        }
+    }
+    return res;
+}
+
+/**
+ * Receives datagrams on shared UDP sockets of its group and hands each one to the process.
+ * Every worker of the group waits on the same sockets, the kernel gives a datagram to one of them.
+ */
+pub struct UdpWorker {
+    pub listeners: Rc<HashMap<String, Listener>>,
+    pub binds: Vec<String>,
+    pub process: Rc<dyn DatagramProcess>,
+}
+
+impl Worker for UdpWorker {
+    fn run(&self) {
+        let sockets = own_listeners(&self.listeners, &self.binds);
+        if sockets.is_empty() {
+            return;
+        }
+
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        while running() {
+            for socket in wait_ready(&sockets) {
+                if let Listener::Udp(socket) = socket {
+                    self.receive(socket, &mut buf);
+                }
+            }
+        }
+    }
+
+    fn init(&self) {
+        let pid = nix::unistd::getpid();
+        let process = &self.process.name();
+        log::trace!(target: "UdpWorker.init", "UdpWorker start[{pid}:{process}]");
+        close_others(&self.listeners, &self.binds);
+
+        register_signal();
+    }
+
+    fn cleanup(&self) {
+        let pid = nix::unistd::getpid();
+        let process = &self.process.name();
+        log::trace!(target: "UdpWorker.cleanup", "UdpWorker stop[{pid}:{process}]");
+        close_own(&self.listeners, &self.binds);
+    }
+}
+
+impl UdpWorker {
+    fn receive(&self, socket: &UdpSocket, buf: &mut [u8]) {
+        match socket.recv_from(buf) {
+            Ok((n, source)) => {
+                let source = SocketAddr::new(source.ip().to_canonical(), source.port()).into();
+                match self.process.process(&buf[..n], &source, socket) {
+                    Ok(w) => log::trace!("{} r:{} o:{}", source, n, w),
+                    Err(err) => log::warn!("process failed {:?}", err),
+                }
+            }
+            Err(err) => {
+                match err.kind() {
+                    // other worker took it
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted => (),
+                    // ICMP error of earlier reply, nothing to do with this datagram
+                    std::io::ErrorKind::ConnectionRefused => (),
+                    _ => {
+                        log::error!(target: "UdpWorker::run", "Receive failed: {err}");
+                        exit(1);
                     }
+                };
+            }
+        }
+    }
+}
+
+/**
+ * Event MPM: one epoll loop keeps many non-blocking connections and feeds them to `EventProcess`.
+ * Idle connections cost a buffer instead of a process, they are dropped after `keep_alive`.
+ * Every loop the process may expire a message still incomplete, e.g. a header past its deadline.
+ */
+pub struct EventWorker {
+    pub listeners: Rc<HashMap<String, Listener>>,
+    pub binds: Vec<String>,
+    pub accept: AcceptConfig,
+    pub process: Arc<dyn Process>,
+    pub keep_alive: Duration,
+}
+
+// accepted socket of event worker
+trait Socket: Read + Write + AsFd {}
+
+impl Socket for TcpStream {}
+impl Socket for UnixStream {}
+
+struct Connection {
+    token: u64,
+    socket: Box<dyn Socket>,
+    session: Session,
+    // sent bytes of session.output
+    sent: usize,
+    last_active: Instant,
+    interest: EpollFlags,
+}
+
+impl Worker for EventWorker {
+    fn run(&self) {
+        let process = match self.process.event() {
+            Some(process) => process,
+            None => {
+                log::error!(target: "EventWorker::run", "{} has no event mode", self.process.name());
+                exit(1);
+            }
+        };
+        let reuse_port = open_reuse_port(&self.accept.reuse_port);
+        let mut listeners = own_listeners(&self.listeners, &self.binds);
+        listeners.extend(reuse_port.iter());
+        if listeners.is_empty() {
+            return;
+        }
+
+        let epoll = match Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC) {
+            Ok(epoll) => epoll,
+            Err(e) => {
+                log::error!(target: "EventWorker::run", "epoll_create failed: {e}");
+                exit(1);
+            }
+        };
+        let listen_flags = if self.accept.strategy == AcceptStrategy::Exclusive {
+            EpollFlags::EPOLLIN | EpollFlags::EPOLLEXCLUSIVE
+        } else {
+            EpollFlags::EPOLLIN
+        };
+        for (token, listener) in listeners.iter().enumerate() {
+            if let Err(e) = epoll.add(
+                listener.as_fd(),
+                EpollEvent::new(listen_flags, token as u64),
+            ) {
+                log::error!(target: "EventWorker::run", "epoll_ctl failed: {e}");
+                exit(1);
+            }
+        }
+
+        let mut connections: HashMap<u64, Connection> = HashMap::new();
+        let mut next_token = listeners.len() as u64;
+        let (mut accepted, mut missed) = (0, 0);
+        let mut latency = Latency::default();
+        let mut events = vec![EpollEvent::empty(); MAX_EVENTS];
+        while running() {
+            let n = match epoll.wait(&mut events, PollTimeout::from(POLL_TIMEOUT_MS)) {
+                Ok(n) => n,
+                Err(Errno::EINTR) => 0,
+                Err(e) => {
+                    log::error!(target: "EventWorker::run", "epoll_wait failed: {e}");
+                    exit(1);
                 }
-                Err(err) => {
-                    match err.kind() {
-                        std::io::ErrorKind::WouldBlock => (),
-                        _ => {
-                            log::error!(target: "TcpWorker::run", "Accept failed: {err}");
-                            exit(1);
+            };
+            let woke = Instant::now();
+
+            for event in &events[..n] {
+                let token = event.data();
+                if let Some(listener) = listeners.get(token as usize) {
+                    let sockets = accept_nonblocking(listener);
+                    if sockets.is_empty() {
+                        missed += 1;
+                    }
+                    accepted += sockets.len();
+                    for (socket, session) in sockets {
+                        let interest = EpollFlags::EPOLLIN | EpollFlags::EPOLLRDHUP;
+                        if let Err(e) =
+                            epoll.add(socket.as_fd(), EpollEvent::new(interest, next_token))
+                        {
+                            log::warn!(target: "EventWorker::run", "epoll_ctl failed: {e}");
+                            continue;
                         }
-                    };
+                        let connection = Connection {
+                            token: next_token,
+                            socket,
+                            session,
+                            sent: 0,
+                            last_active: Instant::now(),
+                            interest,
+                        };
+                        connections.insert(next_token, connection);
+                        next_token += 1;
+                        latency.record(woke.elapsed());
+                    }
+                    continue;
+                }
+
+                let keep = match connections.get_mut(&token) {
+                    Some(connection) => connection.on_event(event.events(), process, &epoll),
+                    None => continue,
+                };
+                if !keep && let Some(connection) = connections.remove(&token) {
+                    let _ = epoll.delete(connection.socket.as_fd());
+                    log::trace!("{} closed", connection.session.client_addr);
                 }
             }
+
+            connections.retain(|_, connection| {
+                // deadline of a message which stopped arriving
+                if connection.session.started.is_some() {
+                    process.expire(&mut connection.session);
+                    if connection.session.close
+                        && !connection.on_event(EpollFlags::empty(), process, &epoll)
+                    {
+                        let _ = epoll.delete(connection.socket.as_fd());
+                        log::trace!("{} deadline expired", connection.session.client_addr);
+                        return false;
+                    }
+                }
+                let alive = connection.last_active.elapsed() < self.keep_alive;
+                if !alive {
+                    let _ = epoll.delete(connection.socket.as_fd());
+                    log::trace!("{} idle timeout", connection.session.client_addr);
+                }
+                return alive;
+            });
         }
+
+        let pid = nix::unistd::getpid();
+        let strategy = self.accept.strategy;
+        log::info!(target: "Worker::run", "EventWorker[{pid}] {strategy:?} accepted:{accepted} empty wakeups:{missed} latency:{latency}");
     }
 
     fn init(&self) {
         let pid = nix::unistd::getpid();
-        let process = &self.tcp_process.name();
-        log::trace!(target: "TcpWorker.init", "TcpWorker start[{pid}:{process}]");
-        // close unnecessary sockets
-        self.init_sockets();
+        let process = &self.process.name();
+        log::trace!(target: "EventWorker.init", "EventWorker start[{pid}:{process}]");
+        close_others(&self.listeners, &self.binds);
 
         register_signal();
     }
 
     fn cleanup(&self) {
         let pid = nix::unistd::getpid();
-        let process = &self.tcp_process.name();
-        log::trace!(target: "TcpWorker.cleanup", "TcpWorker stop[{pid}:{process}]");
-        if let Some(listener) = self.listeners.get(&self.host) {
-            unsafe { close(listener.as_raw_fd()) };
+        let process = &self.process.name();
+        log::trace!(target: "EventWorker.cleanup", "EventWorker stop[{pid}:{process}]");
+        close_own(&self.listeners, &self.binds);
+    }
+}
+
+impl Connection {
+    /**
+     * Read what arrived, let the process answer and send as much as the socket takes.
+     * `false` when the connection is done.
+     */
+    fn on_event(&mut self, events: EpollFlags, process: &dyn EventProcess, epoll: &Epoll) -> bool {
+        let readable = EpollFlags::EPOLLIN
+            | EpollFlags::EPOLLRDHUP
+            | EpollFlags::EPOLLHUP
+            | EpollFlags::EPOLLERR;
+        if events.intersects(readable) && !self.session.close {
+            let mut buf = [0; READ_BUFFER_SIZE];
+            let mut eof = false;
+            let received = self.session.input.len();
+            loop {
+                match self.socket.read(&mut buf) {
+                    Ok(0) => {
+                        eof = true;
+                        break;
+                    }
+                    Ok(n) => self.session.input.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(_) => return false,
+                }
+            }
+
+            if self.session.input.len() > received {
+                self.last_active = Instant::now();
+                if let Err(e) = process.receive(&mut self.session) {
+                    log::warn!("process failed {:?}", e);
+                    return false;
+                }
+            }
+            // answer what came before the half close
+            self.session.close |= eof;
+        }
+
+        while self.sent < self.session.output.len() {
+            match self.socket.write(&self.session.output[self.sent..]) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.sent += n;
+                    self.last_active = Instant::now();
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+
+        if self.sent == self.session.output.len() {
+            self.session.output.clear();
+            self.sent = 0;
+            if self.session.close {
+                return false;
+            }
+        }
+
+        // wait for the socket to take the rest, stop reading once closing
+        let mut interest = if self.session.close {
+            EpollFlags::empty()
+        } else {
+            EpollFlags::EPOLLIN | EpollFlags::EPOLLRDHUP
+        };
+        if !self.session.output.is_empty() {
+            interest |= EpollFlags::EPOLLOUT;
         }
+        if interest != self.interest {
+            let mut event = EpollEvent::new(interest, self.token);
+            if epoll.modify(self.socket.as_fd(), &mut event).is_err() {
+                return false;
+            }
+            self.interest = interest;
+        }
+        return true;
     }
 }
 
-impl TcpWorker {
-    fn init_sockets(&self) {
-        self.listeners
-            .iter()
-            .filter(|(host, _)| **host != self.host)
-            .for_each(|(_, listener)| {
-                let fd = listener.as_raw_fd();
-                unsafe { close(fd) };
+fn accept_nonblocking(listener: &Listener) -> Vec<(Box<dyn Socket>, Session)> {
+    let mut res: Vec<(Box<dyn Socket>, Session)> = vec![];
+    loop {
+        let accepted: std::io::Result<(Box<dyn Socket>, Session)> = match listener {
+            Listener::Tcp(listener) => listener.accept().and_then(|(stream, addr)| {
+                stream.set_nonblocking(true)?;
+                let addr = SocketAddr::new(addr.ip().to_canonical(), addr.port());
+                let local = stream
+                    .local_addr()
+                    .map(Address::Inet)
+                    .unwrap_or(Address::Unnamed);
+                return Ok((
+                    Box::new(stream) as Box<dyn Socket>,
+                    Session::new(addr.into(), local),
+                ));
+            }),
+            Listener::Unix(listener) => listener.accept().and_then(|(stream, addr)| {
+                stream.set_nonblocking(true)?;
+                let local = stream
+                    .local_addr()
+                    .map(Address::from)
+                    .unwrap_or(Address::Unix(None));
+                return Ok((
+                    Box::new(stream) as Box<dyn Socket>,
+                    Session::new(addr.into(), local),
+                ));
+            }),
+            Listener::Udp(_) => return res,
+        };
+
+        match accepted {
+            Ok(accepted) => res.push(accepted),
+            Err(err) => match err.kind() {
+                std::io::ErrorKind::WouldBlock => return res,
+                std::io::ErrorKind::Interrupted | std::io::ErrorKind::ConnectionAborted => continue,
+                // e.g. out of file descriptors, try again on next event
+                _ => {
+                    accept_failed(&err);
+                    return res;
+                }
+            },
+        }
+    }
+}
+
+fn own_listeners<'a>(
+    listeners: &'a HashMap<String, Listener>,
+    binds: &[String],
+) -> Vec<&'a Listener> {
+    return binds
+        .iter()
+        .filter_map(|bind| listeners.get(bind))
+        .collect();
+}
+
+// listeners readable within POLL_TIMEOUT_MS, none when interrupted by signal
+fn wait_ready<'a>(listeners: &[&'a Listener]) -> Vec<&'a Listener> {
+    let mut fds: Vec<PollFd> = listeners
+        .iter()
+        .map(|l| PollFd::new(l.as_fd(), PollFlags::POLLIN))
+        .collect();
+
+    match poll(&mut fds, PollTimeout::from(POLL_TIMEOUT_MS)) {
+        Ok(0) | Err(Errno::EINTR) => return vec![],
+        Ok(_) => (),
+        Err(e) => {
+            log::error!(target: "Worker::run", "poll failed: {e}");
+            exit(1);
+        }
+    }
+
+    let ready: Vec<bool> = fds.iter().map(|fd| fd.any().unwrap_or(false)).collect();
+    return listeners
+        .iter()
+        .zip(ready)
+        .filter(|(_, r)| *r)
+        .map(|(l, _)| *l)
+        .collect();
+}
+
+fn close_others(listeners: &HashMap<String, Listener>, binds: &[String]) {
+    listeners
+        .iter()
+        .filter(|(bind, _)| !binds.contains(bind))
+        .for_each(|(_, listener)| {
+            let fd = listener.as_raw_fd();
+            unsafe { close(fd) };
+        });
+}
+
+fn close_own(listeners: &HashMap<String, Listener>, binds: &[String]) {
+    for listener in own_listeners(listeners, binds) {
+        unsafe { close(listener.as_raw_fd()) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::TcpStream,
+        rc::Rc,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, AtomicU32, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        process::{self, Process, address::Address, stream::Stream},
+        server::{
+            accept::{AcceptConfig, AcceptStrategy},
+            listener::{Bind, Listener},
+            worker::{Latency, RUNNING, ThreadedWorker},
+        },
+        worker::Worker,
+    };
+
+    // echoes one byte after a while, counting connections served at once
+    #[derive(Default)]
+    struct SlowProcess {
+        active: AtomicU32,
+        max_active: AtomicU32,
+        served: AtomicU32,
+    }
+
+    impl Process for SlowProcess {
+        fn process(
+            &self,
+            mut stream: Box<dyn Stream>,
+            _: &Address,
+        ) -> Result<(usize, usize), process::Error> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_active.fetch_max(active, Ordering::SeqCst);
+
+            let mut buf = [0; 1];
+            let result = stream.read_exact(&mut buf).and_then(|_| {
+                thread::sleep(Duration::from_millis(200));
+                return stream.write_all(&buf);
             });
+            self.active.fetch_sub(1, Ordering::SeqCst);
 
-        if let Some(listener) = self.listeners.get(&self.host) {
-            let _ = setsockopt(listener, ReceiveTimeout, &TimeVal::new(1, 0));
-            let _ = setsockopt(listener, ReuseAddr, &true);
-            let _ = setsockopt(listener, ReusePort, &true);
+            result.map_err(|e| process::Error::IoFail(e.to_string()))?;
+            self.served.fetch_add(1, Ordering::SeqCst);
+            return Ok((1, 1));
         }
     }
+
+    #[test]
+    fn test_latency() {
+        let mut latency = Latency::default();
+        assert_eq!(latency.to_string(), "-");
+
+        latency.record(Duration::from_millis(3));
+        latency.record(Duration::from_millis(1));
+        latency.record(Duration::from_millis(8));
+        assert_eq!(latency.to_string(), "1ms/4ms/8ms");
+    }
+
+    #[test]
+    fn test_threaded_worker_concurrent_connections() {
+        let listener = Bind::tcp("127.0.0.1", 0).listen().unwrap();
+        let local_addr = match &listener {
+            Listener::Tcp(listener) => listener.local_addr().unwrap(),
+            _ => unreachable!(),
+        };
+        let process = Arc::new(SlowProcess::default());
+        let worker = ThreadedWorker {
+            timeout_ms: 1000,
+            threads: 4,
+            listeners: Rc::new(HashMap::from([("test".to_string(), listener)])),
+            binds: vec!["test".to_string()],
+            accept: AcceptConfig {
+                strategy: AcceptStrategy::Shared,
+                lock: None,
+                reuse_port: vec![],
+            },
+            process: process.clone(),
+        };
+
+        let done = Arc::new(AtomicBool::new(false));
+        let clients = thread::spawn({
+            let done = done.clone();
+            move || {
+                let handles: Vec<_> = (0..4u8)
+                    .map(|i| {
+                        thread::spawn(move || {
+                            let mut client = TcpStream::connect(local_addr).unwrap();
+                            client
+                                .set_read_timeout(Some(Duration::from_secs(5)))
+                                .unwrap();
+                            client.write_all(&[i]).unwrap();
+                            let mut buf = [0; 1];
+                            client.read_exact(&mut buf).unwrap();
+                            assert_eq!(buf[0], i);
+                        })
+                    })
+                    .collect();
+                let results: Vec<_> = handles.into_iter().map(|h| h.join()).collect();
+                done.store(true, Ordering::SeqCst);
+                RUNNING.store(false, Ordering::SeqCst);
+                return results.into_iter().all(|r| r.is_ok());
+            }
+        });
+
+        // run returns early while another test has the worker recycling
+        while !done.load(Ordering::SeqCst) {
+            worker.run();
+            if process::recycling() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        RUNNING.store(true, Ordering::SeqCst);
+
+        assert!(clients.join().unwrap());
+        assert_eq!(process.served.load(Ordering::SeqCst), 4);
+        // one process shared by the threads, serving the connections side by side
+        assert!(process.max_active.load(Ordering::SeqCst) > 1);
+    }
 }
@@ -0,0 +1,206 @@
+use std::{path::Path, sync::Arc};
+
+use rustls::{
+    RootCertStore,
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, pem::PemObject},
+    server::{WebPkiClientVerifier, danger::ClientCertVerifier},
+};
+
+use crate::tls::{
+    Error,
+    x509::{SubjectAltName, parse_certificate},
+};
+
+/**
+ * When client certificate is asked.
+ * A presented certificate must always chain to the CA bundle, otherwise handshake fails with alert.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientAuthMode {
+    // handshake succeeds without certificate
+    Optional,
+    // handshake fails without certificate
+    Required,
+    // requests under these path prefixes are answered 403 without certificate
+    Paths(Vec<String>),
+}
+
+impl ClientAuthMode {
+    /**
+     * Whether a request of `path` can be served with or without certificate.
+     */
+    pub fn permits(&self, path: &str, has_certificate: bool) -> bool {
+        if has_certificate {
+            return true;
+        }
+
+        return match self {
+            ClientAuthMode::Optional => true,
+            ClientAuthMode::Required => false,
+            ClientAuthMode::Paths(prefixes) => {
+                let path = normalize_path(path);
+                !prefixes.iter().any(|prefix| under_path(&path, prefix))
+            }
+        };
+    }
+}
+
+// "/api" covers "/api" and "/api/..." but not "/apis"
+fn under_path(path: &str, prefix: &str) -> bool {
+    let prefix = normalize_path(prefix);
+    let prefix = prefix.trim_end_matches('/');
+    return match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    };
+}
+
+/**
+ * Path as a rule sees it: unreserved characters percent-decoded, empty and `.` segments dropped, `..` resolved.
+ * Otherwise `//admin`, `/./admin`, `/public/../admin` or `/%61dmin` would slip past a rule on `/admin`
+ * while a handler, or a server behind us, still takes them for `/admin`.
+ */
+fn normalize_path(path: &str) -> String {
+    let decoded = decode_unreserved(path);
+    let mut segments = vec![];
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    return format!("/{}", segments.join("/"));
+}
+
+// %XX of ALPHA, DIGIT, "-", ".", "_" and "~" means the character itself (RFC 3986 section 2.3)
+fn decode_unreserved(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = match bytes.get(i..i + 3) {
+            Some([b'%', h, l]) => std::str::from_utf8(&[*h, *l])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .filter(|c| c.is_ascii_alphanumeric() || b"-._~".contains(c)),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                res.push(c);
+                i += 3;
+            }
+            None => {
+                res.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    // only ASCII triplets were replaced by ASCII, the rest is still UTF-8
+    return String::from_utf8_lossy(&res).into_owned();
+}
+
+/**
+ * Verified certificate of the client.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    pub subject: String,
+    pub common_name: Option<String>,
+    pub subject_alt_names: Vec<SubjectAltName>,
+    // lowercase hex SHA-256 of DER
+    pub fingerprint: String,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &CertificateDer<'_>) -> Self {
+        let info = parse_certificate(der).unwrap_or_default();
+        let fingerprint = ring::digest::digest(&ring::digest::SHA256, der)
+            .as_ref()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        return Self {
+            subject: info.subject,
+            common_name: info.common_name,
+            subject_alt_names: info.subject_alt_names,
+            fingerprint,
+        };
+    }
+}
+
+pub(crate) fn client_verifier(
+    ca_path: &Path,
+    mode: &ClientAuthMode,
+    provider: &Arc<CryptoProvider>,
+) -> Result<Arc<dyn ClientCertVerifier>, Error> {
+    let mut roots = RootCertStore::empty();
+    let certs = CertificateDer::pem_file_iter(ca_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| Error::LoadFail(format!("{}: {e}", ca_path.display())))?;
+    for cert in certs {
+        roots
+            .add(cert)
+            .map_err(|e| Error::LoadFail(format!("{}: {e}", ca_path.display())))?;
+    }
+    if roots.is_empty() {
+        return Err(Error::LoadFail(format!(
+            "{}: no certificate",
+            ca_path.display()
+        )));
+    }
+
+    let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone());
+    let builder = if *mode == ClientAuthMode::Required {
+        builder
+    } else {
+        builder.allow_unauthenticated()
+    };
+
+    return builder
+        .build()
+        .map_err(|e| Error::ConfigFail(format!("client verifier: {e}")));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tls::client_auth::{ClientAuthMode, normalize_path};
+
+    #[test]
+    fn test_permits() {
+        assert!(ClientAuthMode::Optional.permits("/", false));
+        assert!(!ClientAuthMode::Required.permits("/", false));
+        assert!(ClientAuthMode::Required.permits("/", true));
+
+        let paths = ClientAuthMode::Paths(vec!["/internal/".to_string(), "/admin".to_string()]);
+        assert!(!paths.permits("/internal", false));
+        assert!(!paths.permits("/internal/users", false));
+        assert!(!paths.permits("/admin", false));
+        assert!(paths.permits("/administrator", false));
+        assert!(paths.permits("/public", false));
+        assert!(paths.permits("/admin/x", true));
+    }
+
+    #[test]
+    fn test_permits_normalized_path() {
+        let paths = ClientAuthMode::Paths(vec!["/admin".to_string()]);
+        assert!(!paths.permits("//admin", false));
+        assert!(!paths.permits("/./admin", false));
+        assert!(!paths.permits("/public/../admin", false));
+        assert!(!paths.permits("/%61dmin", false));
+        assert!(!paths.permits("/%2e%2E/admin/users", false));
+        assert!(!paths.permits("/admin/", false));
+        assert!(paths.permits("/admin/../public", false));
+        assert!(paths.permits("/%61dministrator", false));
+
+        assert_eq!(normalize_path("/a//b/./c/../d"), "/a/b/d");
+        assert_eq!(normalize_path("/../../a"), "/a");
+        assert_eq!(normalize_path("/%7euser/%2Fx%zz"), "/~user/%2Fx%zz");
+        assert_eq!(normalize_path(""), "/");
+    }
+}
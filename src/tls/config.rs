@@ -0,0 +1,333 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use rustls::{
+    ServerConfig, SupportedProtocolVersion,
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+
+use crate::tls::{
+    Error,
+    client_auth::{ClientAuthMode, client_verifier},
+    x509::{SubjectAltName, parse_certificate},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    pub fn parse(str: &str) -> Option<Self> {
+        return match str.to_ascii_lowercase().trim_start_matches("tls") {
+            "1.2" | "v1.2" => Some(TlsVersion::Tls12),
+            "1.3" | "v1.3" => Some(TlsVersion::Tls13),
+            _ => None,
+        };
+    }
+
+    fn supported(&self) -> &'static SupportedProtocolVersion {
+        return match self {
+            TlsVersion::Tls12 => &rustls::version::TLS12,
+            TlsVersion::Tls13 => &rustls::version::TLS13,
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CertificateConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    // names selected by SNI, taken from the certificate when empty
+    pub server_names: Vec<String>,
+}
+
+/**
+ * TLS settings of a listener.
+ * The first certificate is used when SNI is missing or matches no certificate.
+ */
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    certificates: Vec<CertificateConfig>,
+    alpn: Vec<String>,
+    versions: Vec<TlsVersion>,
+    cipher_suites: Vec<String>,
+    handshake_timeout: Duration,
+    // CA bundle verifying client certificates
+    client_auth: Option<(PathBuf, ClientAuthMode)>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        return Self {
+            certificates: vec![],
+            alpn: vec![],
+            versions: vec![TlsVersion::Tls12, TlsVersion::Tls13],
+            cipher_suites: vec![],
+            handshake_timeout: Duration::from_secs(5),
+            client_auth: None,
+        };
+    }
+}
+
+#[allow(dead_code)]
+impl TlsConfig {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn with_certificate(
+        mut self,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Self {
+        self.certificates.push(CertificateConfig {
+            cert_path: cert_path.as_ref().to_path_buf(),
+            key_path: key_path.as_ref().to_path_buf(),
+            server_names: vec![],
+        });
+        return self;
+    }
+
+    pub fn with_named_certificate(
+        mut self,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+        server_names: Vec<String>,
+    ) -> Self {
+        self.certificates.push(CertificateConfig {
+            cert_path: cert_path.as_ref().to_path_buf(),
+            key_path: key_path.as_ref().to_path_buf(),
+            server_names,
+        });
+        return self;
+    }
+
+    /**
+     * Protocols offered in ALPN, in order of preference. e.g. `h2`, `http/1.1`
+     */
+    pub fn with_alpn(mut self, alpn: Vec<String>) -> Self {
+        self.alpn = alpn;
+        return self;
+    }
+
+    pub fn with_versions(mut self, versions: Vec<TlsVersion>) -> Self {
+        self.versions = versions;
+        return self;
+    }
+
+    /**
+     * Allowed cipher suites by IANA name, e.g. `TLS13_AES_256_GCM_SHA384`.
+     * Empty means every suite of the crypto provider.
+     */
+    pub fn with_cipher_suites(mut self, cipher_suites: Vec<String>) -> Self {
+        self.cipher_suites = cipher_suites;
+        return self;
+    }
+
+    pub fn with_handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        return self;
+    }
+
+    /**
+     * Ask client certificate signed by CA of `ca_path`.
+     */
+    pub fn with_client_auth(mut self, ca_path: impl AsRef<Path>, mode: ClientAuthMode) -> Self {
+        self.client_auth = Some((ca_path.as_ref().to_path_buf(), mode));
+        return self;
+    }
+
+    pub fn handshake_timeout(&self) -> Duration {
+        return self.handshake_timeout;
+    }
+
+    pub fn client_auth_mode(&self) -> Option<&ClientAuthMode> {
+        return self.client_auth.as_ref().map(|(_, mode)| mode);
+    }
+
+    pub(crate) fn provider(&self) -> Result<CryptoProvider, Error> {
+        let mut provider = rustls::crypto::ring::default_provider();
+
+        if !self.cipher_suites.is_empty() {
+            for name in &self.cipher_suites {
+                let known = provider
+                    .cipher_suites
+                    .iter()
+                    .any(|s| format!("{:?}", s.suite()).eq_ignore_ascii_case(name));
+                if !known {
+                    return Err(Error::ConfigFail(format!("unknown cipher suite {name}")));
+                }
+            }
+
+            provider.cipher_suites.retain(|s| {
+                let suite = format!("{:?}", s.suite());
+                self.cipher_suites
+                    .iter()
+                    .any(|n| n.eq_ignore_ascii_case(&suite))
+            });
+        }
+
+        return Ok(provider);
+    }
+
+    pub(crate) fn protocol_versions(
+        &self,
+    ) -> Result<Vec<&'static SupportedProtocolVersion>, Error> {
+        if self.versions.is_empty() {
+            return Err(Error::ConfigFail("no TLS version enabled".to_string()));
+        }
+        return Ok(self.versions.iter().map(|v| v.supported()).collect());
+    }
+
+    pub(crate) fn cert_resolver(
+        &self,
+        provider: &CryptoProvider,
+    ) -> Result<Arc<SniResolver>, Error> {
+        let mut resolver = SniResolver {
+            by_name: HashMap::new(),
+            default: None,
+        };
+
+        for certificate in &self.certificates {
+            let key = Arc::new(load_certified_key(certificate, provider)?);
+
+            let names = if certificate.server_names.is_empty() {
+                certificate_names(&key)
+            } else {
+                certificate.server_names.clone()
+            };
+
+            for name in names {
+                log::debug!(
+                    "TLS certificate {} for {name}",
+                    certificate.cert_path.display()
+                );
+                resolver
+                    .by_name
+                    .entry(name.to_ascii_lowercase())
+                    .or_insert_with(|| key.clone());
+            }
+
+            if resolver.default.is_none() {
+                resolver.default = Some(key);
+            }
+        }
+
+        if resolver.default.is_none() {
+            return Err(Error::ConfigFail("no certificate configured".to_string()));
+        }
+
+        return Ok(Arc::new(resolver));
+    }
+
+    pub fn build(&self) -> Result<Arc<ServerConfig>, Error> {
+        let provider = Arc::new(self.provider()?);
+        let resolver = self.cert_resolver(&provider)?;
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&self.protocol_versions()?)
+            .map_err(|e| Error::ConfigFail(e.to_string()))?;
+        let builder = match &self.client_auth {
+            Some((ca_path, mode)) => {
+                builder.with_client_cert_verifier(client_verifier(ca_path, mode, &provider)?)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_cert_resolver(resolver);
+
+        config.alpn_protocols = self.alpn_protocols();
+
+        return Ok(Arc::new(config));
+    }
+
+    pub(crate) fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        return self.alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+    }
+}
+
+fn load_certified_key(
+    certificate: &CertificateConfig,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, Error> {
+    let cert_path = &certificate.cert_path;
+    let key_path = &certificate.key_path;
+
+    let chain = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| Error::LoadFail(format!("{}: {e}", cert_path.display())))?;
+    if chain.is_empty() {
+        return Err(Error::LoadFail(format!(
+            "{}: no certificate",
+            cert_path.display()
+        )));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| Error::LoadFail(format!("{}: {e}", key_path.display())))?;
+
+    return CertifiedKey::from_der(chain, key, provider)
+        .map_err(|e| Error::LoadFail(format!("{}: {e}", cert_path.display())));
+}
+
+// DNS names of certificate SAN, common name when there is no SAN
+fn certificate_names(key: &CertifiedKey) -> Vec<String> {
+    let info = match key.cert.first().and_then(|c| parse_certificate(c)) {
+        Some(info) => info,
+        None => return vec![],
+    };
+
+    let names: Vec<String> = info
+        .subject_alt_names
+        .iter()
+        .filter_map(|n| match n {
+            SubjectAltName::Dns(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+
+    if names.is_empty() {
+        return info.common_name.into_iter().collect();
+    }
+    return names;
+}
+
+/**
+ * Pick certificate by SNI, exact name first and then wildcard (`*.example.com`).
+ */
+#[derive(Debug)]
+pub(crate) struct SniResolver {
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+    default: Option<Arc<CertifiedKey>>,
+}
+
+impl SniResolver {
+    fn find(&self, server_name: &str) -> Option<Arc<CertifiedKey>> {
+        let name = server_name.to_ascii_lowercase();
+        if let Some(key) = self.by_name.get(&name) {
+            return Some(key.clone());
+        }
+
+        let wildcard = name
+            .split_once('.')
+            .map(|(_, parent)| format!("*.{parent}"))?;
+        return self.by_name.get(&wildcard).cloned();
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        return client_hello
+            .server_name()
+            .and_then(|name| self.find(name))
+            .or_else(|| self.default.clone());
+    }
+}
@@ -0,0 +1,45 @@
+pub mod client_auth;
+mod config;
+pub mod process;
+pub mod x509;
+
+pub use client_auth::{ClientAuthMode, ClientCertificate};
+pub use config::{TlsConfig, TlsVersion};
+
+/**
+ * Negotiated session of a TLS connection, exposed to processes through `Stream`.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TlsInfo {
+    pub server_name: Option<String>,
+    pub alpn: Option<String>,
+    pub client_certificate: Option<ClientCertificate>,
+    pub client_auth: Option<ClientAuthMode>,
+}
+
+impl TlsInfo {
+    /**
+     * Whether request of `path` is allowed by client authentication of the listener.
+     */
+    pub fn permits(&self, path: &str) -> bool {
+        return match &self.client_auth {
+            Some(mode) => mode.permits(path, self.client_certificate.is_some()),
+            None => true,
+        };
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    LoadFail(String),
+    ConfigFail(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Error::LoadFail(msg) => f.write_fmt(format_args!("TlsError: [load fail] {msg}")),
+            Error::ConfigFail(msg) => f.write_fmt(format_args!("TlsError: [config fail] {msg}")),
+        };
+    }
+}
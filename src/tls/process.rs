@@ -0,0 +1,421 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use crate::{
+    process::{Error, Process, address::Address, proxy::ProxyHeader, stream::Stream},
+    tls::{self, ClientAuthMode, ClientCertificate, TlsConfig, TlsInfo},
+};
+
+/**
+ * Terminate TLS and hand decrypted stream to the wrapped process.
+ */
+pub struct TlsProcess {
+    inner: Arc<dyn Process>,
+    config: Arc<ServerConfig>,
+    handshake_timeout: Duration,
+    client_auth: Option<ClientAuthMode>,
+}
+
+impl TlsProcess {
+    pub fn new(inner: Arc<dyn Process>, config: &TlsConfig) -> Result<Self, tls::Error> {
+        return Ok(Self {
+            inner,
+            config: config.build()?,
+            handshake_timeout: config.handshake_timeout(),
+            client_auth: config.client_auth_mode().cloned(),
+        });
+    }
+}
+
+impl Process for TlsProcess {
+    fn process(
+        &self,
+        mut stream: Box<dyn Stream>,
+        client_addr: &Address,
+    ) -> Result<(usize, usize), Error> {
+        let mut conn = ServerConnection::new(self.config.clone())
+            .map_err(|e| Error::IoFail(format!("TLS init failed: {e}")))?;
+
+        // the whole handshake, a client sending a byte now and then must not hold the worker
+        let deadline = Instant::now() + self.handshake_timeout;
+        while conn.is_handshaking() || conn.wants_write() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(Error::IoFail(format!(
+                    "TLS handshake timed out: {client_addr}"
+                )));
+            }
+            let _ = stream.set_read_timeout(Some(left));
+            let _ = stream.set_write_timeout(Some(left));
+            handshake_io(&mut conn, stream.as_mut())
+                .map_err(|e| Error::IoFail(format!("TLS handshake failed: {client_addr} {e}")))?;
+        }
+
+        let info = TlsInfo {
+            server_name: conn.server_name().map(String::from),
+            alpn: conn
+                .alpn_protocol()
+                .map(|p| String::from_utf8_lossy(p).into_owned()),
+            client_certificate: conn
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(ClientCertificate::from_der),
+            client_auth: self.client_auth.clone(),
+        };
+
+        log::debug!(
+            "TLS established {client_addr} sni:{:?} alpn:{:?} version:{:?} suite:{:?} client:{:?}",
+            info.server_name,
+            info.alpn,
+            conn.protocol_version(),
+            conn.negotiated_cipher_suite().map(|s| s.suite()),
+            info.client_certificate.as_ref().map(|c| &c.subject),
+        );
+
+        let tls = TlsStream {
+            inner: StreamOwned::new(conn, stream),
+            info,
+        };
+        return self.inner.process(Box::new(tls), client_addr);
+    }
+
+    fn name(&self) -> String {
+        return format!("tls+{}", self.inner.name());
+    }
+}
+
+// one read or write of the handshake, unlike `complete_io` which reads until the handshake is done
+fn handshake_io(conn: &mut ServerConnection, stream: &mut dyn Stream) -> std::io::Result<()> {
+    if conn.wants_write() {
+        conn.write_tls(stream)?;
+        return stream.flush();
+    }
+
+    if conn.read_tls(stream)? == 0 {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    if let Err(e) = conn.process_new_packets() {
+        // alert telling the client why
+        let _ = conn.write_tls(stream);
+        return Err(std::io::Error::new(ErrorKind::InvalidData, e));
+    }
+    return Ok(());
+}
+
+pub struct TlsStream {
+    inner: StreamOwned<ServerConnection, Box<dyn Stream>>,
+    info: TlsInfo,
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        return self.inner.read(buf);
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return self.inner.write(buf);
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        return self.inner.write_vectored(bufs);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+impl Stream for TlsStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.sock.set_read_timeout(timeout);
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        return self.inner.sock.set_write_timeout(timeout);
+    }
+
+    fn peer_addr(&self) -> Address {
+        return self.inner.sock.peer_addr();
+    }
+
+    fn local_addr(&self) -> Address {
+        return self.inner.sock.local_addr();
+    }
+
+    fn proxy_header(&self) -> Option<ProxyHeader> {
+        return self.inner.sock.proxy_header();
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        return Some(self.info.clone());
+    }
+}
+
+impl Drop for TlsStream {
+    // process is done with the stream, say goodbye before socket is closed
+    fn drop(&mut self) {
+        self.inner.conn.send_close_notify();
+        while self.inner.conn.wants_write() {
+            if self.inner.conn.write_tls(&mut self.inner.sock).is_err() {
+                break;
+            }
+        }
+        let _ = self.inner.sock.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use rustls::{
+        ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject},
+    };
+
+    use crate::{
+        process::{self, Process, echo::EchoProcess, stream::Stream},
+        tls::{ClientAuthMode, TlsConfig, process::TlsProcess, x509::parse_certificate},
+    };
+
+    const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tls/testdata");
+
+    fn client_config(client_cert: Option<&str>) -> Arc<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(format!("{TESTDATA}/ca.pem")).unwrap())
+            .unwrap();
+
+        let builder =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots);
+        let mut config = match client_cert {
+            Some(name) => builder
+                .with_client_auth_cert(
+                    vec![CertificateDer::from_pem_file(format!("{TESTDATA}/{name}.pem")).unwrap()],
+                    PrivateKeyDer::from_pem_file(format!("{TESTDATA}/{name}.key")).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"echo".to_vec()];
+        return Arc::new(config);
+    }
+
+    // writes client certificate seen by the process
+    struct ClientIdentityProcess;
+
+    impl Process for ClientIdentityProcess {
+        fn process(
+            &self,
+            mut stream: Box<dyn Stream>,
+            _: &crate::process::address::Address,
+        ) -> Result<(usize, usize), process::Error> {
+            let mut buf = [0; 1];
+            stream
+                .read_exact(&mut buf)
+                .map_err(|e| process::Error::IoFail(e.to_string()))?;
+
+            let identity = match stream.tls_info().and_then(|i| i.client_certificate) {
+                Some(cert) => format!("{} {}", cert.subject, cert.fingerprint),
+                None => "anonymous".to_string(),
+            };
+            stream
+                .write_all(identity.as_bytes())
+                .map_err(|e| process::Error::IoFail(e.to_string()))?;
+            return Ok((1, identity.len()));
+        }
+
+        fn name(&self) -> String {
+            return "identity".to_string();
+        }
+    }
+
+    // returns whether server side succeeded and what client received
+    fn client_identity(mode: ClientAuthMode, client_cert: Option<&str>) -> (bool, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let t = thread::spawn(move || {
+            let config = TlsConfig::new()
+                .with_certificate(
+                    format!("{TESTDATA}/localhost.pem"),
+                    format!("{TESTDATA}/localhost.key"),
+                )
+                .with_client_auth(format!("{TESTDATA}/ca.pem"), mode);
+            let process = TlsProcess::new(Arc::new(ClientIdentityProcess), &config).unwrap();
+
+            let (stream, remote_addr) = listener.accept().unwrap();
+            return process
+                .process(Box::new(stream), &remote_addr.into())
+                .is_ok();
+        });
+
+        let tcp = TcpStream::connect(local_addr).unwrap();
+        tcp.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let conn = ClientConnection::new(
+            client_config(client_cert),
+            ServerName::try_from("localhost").unwrap(),
+        )
+        .unwrap();
+        let mut client = StreamOwned::new(conn, tcp);
+
+        let mut received = String::new();
+        if client.write_all(b"?").and_then(|_| client.flush()).is_ok() {
+            let _ = client.read_to_string(&mut received);
+        }
+        drop(client);
+
+        return (t.join().unwrap(), received);
+    }
+
+    // returns common name of server certificate and echoed data
+    fn echo_over_tls(server_name: &'static str) -> (String, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let t = thread::spawn(move || {
+            let config = TlsConfig::new()
+                .with_certificate(
+                    format!("{TESTDATA}/localhost.pem"),
+                    format!("{TESTDATA}/localhost.key"),
+                )
+                .with_certificate(
+                    format!("{TESTDATA}/other.pem"),
+                    format!("{TESTDATA}/other.key"),
+                )
+                .with_alpn(vec!["echo".to_string()]);
+            let process = TlsProcess::new(Arc::new(EchoProcess { prefix: None }), &config).unwrap();
+
+            let (stream, remote_addr) = listener.accept().unwrap();
+            return process.process(Box::new(stream), &remote_addr.into());
+        });
+
+        let tcp = TcpStream::connect(local_addr).unwrap();
+        tcp.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let conn = ClientConnection::new(
+            client_config(None),
+            ServerName::try_from(server_name).unwrap(),
+        )
+        .unwrap();
+        let mut client = StreamOwned::new(conn, tcp);
+
+        client.write_all(b"hello").unwrap();
+        client.flush().unwrap();
+        let mut received = [0; 5];
+        client.read_exact(&mut received).unwrap();
+
+        assert_eq!(client.conn.alpn_protocol(), Some(&b"echo"[..]));
+        let cert = client.conn.peer_certificates().unwrap()[0].clone();
+        let common_name = parse_certificate(&cert).unwrap().common_name.unwrap();
+
+        client.conn.send_close_notify();
+        let _ = client.flush();
+        drop(client);
+
+        let (readed, _) = t.join().unwrap().unwrap();
+        assert_eq!(readed, 5);
+
+        return (common_name, String::from_utf8(received.to_vec()).unwrap());
+    }
+
+    #[test]
+    fn test_echo_over_tls() {
+        assert_eq!(
+            echo_over_tls("localhost"),
+            ("localhost".to_string(), "hello".to_string())
+        );
+    }
+
+    #[test]
+    fn test_select_certificate_by_sni() {
+        assert_eq!(
+            echo_over_tls("www.other.test"),
+            ("other".to_string(), "hello".to_string())
+        );
+    }
+
+    #[test]
+    fn test_client_certificate_required() {
+        assert_eq!(
+            client_identity(ClientAuthMode::Required, Some("client")),
+            (
+                true,
+                "CN=client,O=server_rs test \
+                 96261e4676c98d0d4ddb6ae596397acd75cf8bd1011b4a5829470dc9f355522a"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            client_identity(ClientAuthMode::Required, None),
+            (false, String::new())
+        );
+        assert_eq!(
+            client_identity(ClientAuthMode::Required, Some("untrusted")),
+            (false, String::new())
+        );
+    }
+
+    #[test]
+    fn test_client_certificate_optional() {
+        assert_eq!(
+            client_identity(ClientAuthMode::Optional, None),
+            (true, "anonymous".to_string())
+        );
+        assert_eq!(
+            client_identity(ClientAuthMode::Optional, Some("untrusted")),
+            (false, String::new())
+        );
+    }
+
+    #[test]
+    fn test_handshake_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let t = thread::spawn(move || {
+            let config = TlsConfig::new()
+                .with_certificate(
+                    format!("{TESTDATA}/localhost.pem"),
+                    format!("{TESTDATA}/localhost.key"),
+                )
+                .with_handshake_timeout(Duration::from_millis(300));
+            let process = TlsProcess::new(Arc::new(EchoProcess { prefix: None }), &config).unwrap();
+
+            let (stream, remote_addr) = listener.accept().unwrap();
+            let started = Instant::now();
+            let result = process.process(Box::new(stream), &remote_addr.into());
+            return (result.is_err(), started.elapsed());
+        });
+
+        // ClientHello record header and a byte every 50ms, each read is well within the timeout
+        let mut tcp = TcpStream::connect(local_addr).unwrap();
+        let mut hello = vec![0x16, 0x03, 0x01, 0x01, 0x00];
+        hello.extend_from_slice(&[0x01; 30]);
+        for b in hello {
+            if tcp.write_all(&[b]).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        let (failed, elapsed) = t.join().unwrap();
+        assert!(failed);
+        assert!(elapsed < Duration::from_millis(1000), "{elapsed:?}");
+    }
+}
@@ -0,0 +1,279 @@
+use std::net::IpAddr;
+
+/**
+ * Minimal X.509 reader, only subject and subject alternative names are extracted.
+ * Certificate is already verified by rustls, malformed input just yields `None`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub common_name: Option<String>,
+    pub subject_alt_names: Vec<SubjectAltName>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    Dns(String),
+    Email(String),
+    Uri(String),
+    Ip(IpAddr),
+}
+
+impl std::fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            SubjectAltName::Dns(name) => f.write_fmt(format_args!("DNS:{name}")),
+            SubjectAltName::Email(email) => f.write_fmt(format_args!("email:{email}")),
+            SubjectAltName::Uri(uri) => f.write_fmt(format_args!("URI:{uri}")),
+            SubjectAltName::Ip(ip) => f.write_fmt(format_args!("IP:{ip}")),
+        };
+    }
+}
+
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_OID: u8 = 0x06;
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_VERSION: u8 = 0xa0;
+const TAG_EXTENSIONS: u8 = 0xa3;
+
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+struct Der<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        return Self { data };
+    }
+
+    fn is_empty(&self) -> bool {
+        return self.data.is_empty();
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        return self.data.first().copied();
+    }
+
+    // (tag, content)
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        let tag = *self.data.first()?;
+        let first_len = *self.data.get(1)? as usize;
+
+        let (len, header) = if first_len < 0x80 {
+            (first_len, 2)
+        } else {
+            let octets = first_len & 0x7f;
+            if octets == 0 || octets > 4 {
+                return None;
+            }
+            let len = self
+                .data
+                .get(2..2 + octets)?
+                .iter()
+                .fold(0usize, |l, b| (l << 8) | *b as usize);
+            (len, 2 + octets)
+        };
+
+        let content = self.data.get(header..header.checked_add(len)?)?;
+        self.data = &self.data[header + len..];
+        return Some((tag, content));
+    }
+
+    fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+        let (t, content) = self.next()?;
+        return if t == tag { Some(content) } else { None };
+    }
+}
+
+pub fn parse_certificate(der: &[u8]) -> Option<CertificateInfo> {
+    let certificate = Der::new(der).expect(TAG_SEQUENCE)?;
+    let mut tbs = Der::new(Der::new(certificate).expect(TAG_SEQUENCE)?);
+
+    if tbs.peek_tag() == Some(TAG_VERSION) {
+        tbs.next()?;
+    }
+    tbs.next()?; // serialNumber
+    tbs.expect(TAG_SEQUENCE)?; // signature
+    tbs.expect(TAG_SEQUENCE)?; // issuer
+    tbs.expect(TAG_SEQUENCE)?; // validity
+    let subject = tbs.expect(TAG_SEQUENCE)?;
+    tbs.expect(TAG_SEQUENCE)?; // subjectPublicKeyInfo
+
+    let mut info = CertificateInfo::default();
+    let (subject, common_name) = parse_name(subject)?;
+    info.subject = subject;
+    info.common_name = common_name;
+
+    while !tbs.is_empty() {
+        let (tag, content) = tbs.next()?;
+        if tag == TAG_EXTENSIONS {
+            info.subject_alt_names = parse_extensions(content)?;
+        }
+    }
+
+    return Some(info);
+}
+
+/**
+ * RFC 4514 string of Name, last RDN comes first.
+ */
+fn parse_name(name: &[u8]) -> Option<(String, Option<String>)> {
+    let mut rdns = Der::new(name);
+    let mut parts = vec![];
+    let mut common_name = None;
+
+    while !rdns.is_empty() {
+        let mut set = Der::new(rdns.expect(TAG_SET)?);
+        let mut attrs = vec![];
+        while !set.is_empty() {
+            let mut attr = Der::new(set.expect(TAG_SEQUENCE)?);
+            let oid = attr.expect(TAG_OID)?;
+            let (_, value) = attr.next()?;
+            let value = String::from_utf8_lossy(value).into_owned();
+
+            if oid == OID_COMMON_NAME {
+                common_name = Some(value.clone());
+            }
+            attrs.push(format!(
+                "{}={}",
+                attribute_name(oid),
+                escape_dn_value(&value)
+            ));
+        }
+        parts.push(attrs.join("+"));
+    }
+
+    parts.reverse();
+    return Some((parts.join(","), common_name));
+}
+
+fn attribute_name(oid: &[u8]) -> String {
+    return match oid {
+        [0x55, 0x04, 0x03] => "CN".to_string(),
+        [0x55, 0x04, 0x06] => "C".to_string(),
+        [0x55, 0x04, 0x07] => "L".to_string(),
+        [0x55, 0x04, 0x08] => "ST".to_string(),
+        [0x55, 0x04, 0x0a] => "O".to_string(),
+        [0x55, 0x04, 0x0b] => "OU".to_string(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress".to_string(),
+        _ => oid_to_string(oid),
+    };
+}
+
+fn oid_to_string(oid: &[u8]) -> String {
+    let mut parts = vec![];
+    let mut value: u64 = 0;
+    for b in oid {
+        value = (value << 7) | (b & 0x7f) as u64;
+        if b & 0x80 != 0 {
+            continue;
+        }
+
+        // first sub identifier holds two arcs
+        if parts.is_empty() {
+            let first = (value / 40).min(2);
+            parts.push(first);
+            parts.push(value - first * 40);
+        } else {
+            parts.push(value);
+        }
+        value = 0;
+    }
+
+    return parts
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<String>>()
+        .join(".");
+}
+
+fn escape_dn_value(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';' | '=') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    return res;
+}
+
+fn parse_extensions(extensions: &[u8]) -> Option<Vec<SubjectAltName>> {
+    let mut extensions = Der::new(Der::new(extensions).expect(TAG_SEQUENCE)?);
+
+    while !extensions.is_empty() {
+        let mut extension = Der::new(extensions.expect(TAG_SEQUENCE)?);
+        let oid = extension.expect(TAG_OID)?;
+        if extension.peek_tag() == Some(TAG_BOOLEAN) {
+            extension.next()?;
+        }
+        let value = extension.expect(TAG_OCTET_STRING)?;
+
+        if oid == OID_SUBJECT_ALT_NAME {
+            return parse_general_names(value);
+        }
+    }
+
+    return Some(vec![]);
+}
+
+fn parse_general_names(value: &[u8]) -> Option<Vec<SubjectAltName>> {
+    let mut names = Der::new(Der::new(value).expect(TAG_SEQUENCE)?);
+    let mut res = vec![];
+
+    while !names.is_empty() {
+        let (tag, content) = names.next()?;
+        let text = || String::from_utf8_lossy(content).into_owned();
+        match tag {
+            0x81 => res.push(SubjectAltName::Email(text())),
+            0x82 => res.push(SubjectAltName::Dns(text())),
+            0x86 => res.push(SubjectAltName::Uri(text())),
+            0x87 => {
+                if let Ok(v4) = <[u8; 4]>::try_from(content) {
+                    res.push(SubjectAltName::Ip(IpAddr::from(v4)));
+                } else if let Ok(v6) = <[u8; 16]>::try_from(content) {
+                    res.push(SubjectAltName::Ip(IpAddr::from(v6)));
+                }
+            }
+            // otherName, x400Address, directoryName, ediPartyName, registeredID
+            _ => (),
+        }
+    }
+
+    return Some(res);
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use rustls::pki_types::{CertificateDer, pem::PemObject};
+
+    use crate::tls::x509::{SubjectAltName, parse_certificate};
+
+    #[test]
+    fn test_parse_certificate() {
+        let der = CertificateDer::from_pem_slice(include_bytes!("testdata/localhost.pem")).unwrap();
+        let info = parse_certificate(&der).unwrap();
+
+        assert_eq!(info.subject, "CN=localhost,O=server_rs test");
+        assert_eq!(info.common_name, Some("localhost".to_string()));
+        assert_eq!(
+            info.subject_alt_names,
+            vec![
+                SubjectAltName::Dns("localhost".to_string()),
+                SubjectAltName::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(parse_certificate(&[0x30, 0x03, 0x02, 0x01]), None);
+    }
+}
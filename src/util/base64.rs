@@ -0,0 +1,90 @@
+/**
+ * Decode base64, both standard and url safe alphabet are accepted.
+ * Trailing padding is optional.
+ */
+pub fn decode(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim_end_matches('=');
+    let mut res = Vec::with_capacity(input.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut bits_len = 0;
+
+    for c in input.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+
+        bits = (bits << 6) | v as u32;
+        bits_len += 6;
+        if bits_len >= 8 {
+            bits_len -= 8;
+            res.push((bits >> bits_len) as u8);
+        }
+    }
+
+    // single dangling character can not be a valid encoding
+    if bits_len >= 6 {
+        return None;
+    }
+
+    return Some(res);
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/**
+ * Encode base64 with the standard alphabet and padding.
+ */
+pub fn encode(input: &[u8]) -> String {
+    let mut res = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |b, (i, c)| b | (*c as u32) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3f] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    return res;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::base64::{decode, encode};
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"h"), "aA==");
+        assert_eq!(encode(b"he"), "aGU=");
+        assert_eq!(encode(b"hello"), "aGVsbG8=");
+        assert_eq!(
+            decode(&encode(&[0xfb, 0xff, 0x00])),
+            Some(vec![0xfb, 0xff, 0x00])
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(decode("aGVsbG8"), Some(b"hello".to_vec()));
+        assert_eq!(
+            decode("AAMAAABkAARAAAAAAAIAAAAA"),
+            Some(vec![
+                0, 3, 0, 0, 0, 100, 0, 4, 64, 0, 0, 0, 0, 2, 0, 0, 0, 0
+            ])
+        );
+        assert_eq!(decode("-_"), Some(vec![0xfb]));
+        assert_eq!(decode("a"), None);
+        assert_eq!(decode("a b"), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub struct Date {
@@ -25,23 +25,94 @@ impl Date {
             .map(|d| d.as_secs())
             .unwrap_or(0);
 
-        let epoch_mins = epoch_secs / 60;
-        let epoch_hours = epoch_mins / 60;
-        let epoch_days = epoch_hours / 24;
+        let epoch_days = epoch_secs / 86400;
 
         let (years, months, days) = Date::year_and_date(epoch_days);
 
         Date {
             year: years as u32,
-            month: months as u8 % 12,
-            day: days as u8 % 30,
-            hour: epoch_hours as u8 % 24,
-            minute: epoch_mins as u8 % 60,
-            second: epoch_secs as u8 % 60,
+            month: months as u8,
+            day: days as u8,
+            hour: (epoch_secs / 3600 % 24) as u8,
+            minute: (epoch_secs / 60 % 60) as u8,
+            second: (epoch_secs % 60) as u8,
             epoch_days,
         }
     }
 
+    /**
+     * HTTP-date of RFC 9110: IMF-fixdate, or the obsolete RFC 850 and asctime forms recipients must still accept.
+     * The day of week is not checked.
+     */
+    pub fn parse(str: &str) -> Option<SystemTime> {
+        let str = str.trim();
+        let (day, month, year, time) = match str.split_once(", ") {
+            // Sunday, 06-Nov-94 08:49:37 GMT
+            Some((_, rest)) if rest.contains('-') => {
+                let (date, time) = rest.strip_suffix(" GMT")?.split_once(' ')?;
+                let mut date = date.split('-');
+                let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+                if year.len() != 2 {
+                    return None;
+                }
+                let year: u64 = year.parse().ok()?;
+                let year = if year < 70 { 2000 + year } else { 1900 + year };
+                (day, month, year, time)
+            }
+            // Sun, 06 Nov 1994 08:49:37 GMT
+            Some((_, rest)) => {
+                let mut parts = rest.strip_suffix(" GMT")?.split(' ');
+                let (day, month, year, time) =
+                    (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+                if parts.next().is_some() || year.len() != 4 {
+                    return None;
+                }
+                (day, month, year.parse().ok()?, time)
+            }
+            // Sun Nov  6 08:49:37 1994
+            None => {
+                let mut parts = str.split_ascii_whitespace().skip(1);
+                let (month, day, time, year) =
+                    (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+                if parts.next().is_some() || year.len() != 4 {
+                    return None;
+                }
+                (day, month, year.parse().ok()?, time)
+            }
+        };
+
+        let month = Date::MONTH.iter().position(|m| *m == month)?;
+        let day: u64 = day.parse().ok()?;
+        let mut time = time.split(':').map(|t| {
+            if t.len() != 2 {
+                return None;
+            }
+            return t.parse::<u64>().ok();
+        });
+        let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+        if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        let months = if Date::is_leap_year(year) {
+            Date::MONTH_DATE_LEAP
+        } else {
+            Date::MONTH_DATE
+        };
+        if year < 1970 || day == 0 || day > months[month] {
+            return None;
+        }
+
+        let days = (1970..year)
+            .map(|y| if Date::is_leap_year(y) { 366 } else { 365 })
+            .sum::<u64>()
+            + months[..month].iter().sum::<u64>()
+            + day
+            - 1;
+        let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+        return Some(UNIX_EPOCH + Duration::from_secs(secs));
+    }
+
     const MONTH: [&str; 12] = [
         "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
     ];
@@ -53,7 +124,7 @@ impl Date {
     const WEEK_DAY: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
 
     fn is_leap_year(year: u64) -> bool {
-        year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+        year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
     }
 
     fn year_and_date(dates: u64) -> (u64, u64, u64) {
@@ -97,9 +168,58 @@ impl Date {
             self.day,
             Date::MONTH[self.month as usize],
             self.year,
-            self.hour % 24,
-            self.minute % 60,
-            self.second % 60
+            self.hour,
+            self.minute,
+            self.second
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::util::date::Date;
+
+    #[test]
+    fn test_to_rfc1123() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_760_000_000);
+        assert_eq!(
+            Date::from(time).to_rfc1123(),
+            "Thu, 09 Oct 2025 08:53:20 GMT"
+        );
+        let time = UNIX_EPOCH + Duration::from_secs(951_868_799);
+        assert_eq!(
+            Date::from(time).to_rfc1123(),
+            "Tue, 29 Feb 2000 23:59:59 GMT"
+        );
+        let time = UNIX_EPOCH + Duration::from_secs(1_767_139_200);
+        assert_eq!(
+            Date::from(time).to_rfc1123(),
+            "Wed, 31 Dec 2025 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(Date::parse("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(Date::parse("Sunday, 06-Nov-94 08:49:37 GMT"), Some(time));
+        assert_eq!(Date::parse("Sun Nov  6 08:49:37 1994"), Some(time));
+
+        let now = UNIX_EPOCH
+            + Duration::from_secs(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            );
+        assert_eq!(Date::parse(&Date::from(now).to_rfc1123()), Some(now));
+
+        assert_eq!(Date::parse("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(Date::parse("Sun, 31 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(Date::parse("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(Date::parse("Sun, 06 Nov 1994 8:49:37 GMT"), None);
+        assert_eq!(Date::parse("1994-11-06"), None);
+    }
+}
@@ -1 +1,3 @@
+pub mod base64;
 pub mod date;
+pub mod panic;
@@ -0,0 +1,111 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe, PanicHookInfo},
+    sync::Once,
+};
+
+static HOOK: Once = Once::new();
+
+thread_local! {
+    // depth of `catch` calls running on this thread
+    static CATCHING: Cell<u32> = const { Cell::new(0) };
+    // panic the hook saw inside `catch`, taken when it unwinds there
+    static CAUGHT: RefCell<Option<Panic>> = const { RefCell::new(None) };
+}
+
+/**
+ * Panic caught by `catch`, with where it happened.
+ */
+#[derive(Debug)]
+pub struct Panic {
+    pub message: String,
+    pub location: String,
+    pub backtrace: Backtrace,
+}
+
+/**
+ * Run `f`, turning a panic into `Err` instead of unwinding further.
+ * The panic is not printed, its message, location and backtrace are returned to be logged.
+ * Whatever `f` borrows mutably may be left half changed, the caller must not trust it afterwards.
+ */
+pub fn catch<R>(f: impl FnOnce() -> R) -> Result<R, Panic> {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING.get() == 0 {
+                previous(info);
+                return;
+            }
+            CAUGHT.set(Some(Panic {
+                message: message(info.payload()),
+                location: location(info),
+                backtrace: Backtrace::force_capture(),
+            }));
+        }));
+    });
+
+    CATCHING.set(CATCHING.get() + 1);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.set(CATCHING.get() - 1);
+
+    return result.map_err(|payload| {
+        return CAUGHT.take().unwrap_or_else(|| Panic {
+            message: message(payload.as_ref()),
+            location: "unknown".to_string(),
+            backtrace: Backtrace::disabled(),
+        });
+    });
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    return "Box<dyn Any>".to_string();
+}
+
+fn location(info: &PanicHookInfo) -> String {
+    return match info.location() {
+        Some(location) => format!("{}:{}", location.file(), location.line()),
+        None => "unknown".to_string(),
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::panic::catch;
+
+    #[test]
+    fn test_catch() {
+        assert_eq!(catch(|| 1 + 1).unwrap(), 2);
+
+        let panic = catch(|| {
+            let v: Vec<u32> = vec![];
+            return v[3];
+        })
+        .unwrap_err();
+        assert!(
+            panic.message.contains("index out of bounds"),
+            "{}",
+            panic.message
+        );
+        assert!(
+            panic.location.starts_with("src/util/panic.rs:"),
+            "{}",
+            panic.location
+        );
+
+        // nested, the inner one is caught by itself
+        let outer = catch(|| {
+            let inner = catch(|| panic!("inner {}", 1));
+            assert_eq!(inner.unwrap_err().message, "inner 1");
+            panic!("outer");
+        });
+        assert_eq!(outer.unwrap_err().message, "outer");
+    }
+}
@@ -3,7 +3,7 @@ use std::process::exit;
 use nix::{
     errno::Errno,
     sys::{
-        signal::{Signal, kill},
+        signal::{SigHandler, SigSet, Signal, kill, signal},
         wait::{WaitStatus, wait},
     },
     unistd::{ForkResult, Pid, fork},
@@ -19,7 +19,8 @@ impl WorkerGenerator {
         let threshold = 5;
         let mut pids = vec![];
         for _ in 0..threshold {
-            for _ in 0..remains {
+            let target = remains;
+            for _ in 0..target {
                 if let Ok(pid) = self.fork_child(group) {
                     pids.push(pid);
                     remains -= 1;
@@ -33,15 +34,29 @@ impl WorkerGenerator {
     }
 
     pub fn fork_child(&self, group: &WorkerGroup) -> Result<Pid, Errno> {
+        // SIGINT during fork would hit the handler of the manager in the child and be lost,
+        // keep it pending until the worker has registered its own
+        let mut sigint = SigSet::empty();
+        sigint.add(Signal::SIGINT);
+        let _ = sigint.thread_block();
+
         return match unsafe { fork() } {
-            Ok(ForkResult::Parent { child }) => Ok(child),
+            Ok(ForkResult::Parent { child }) => {
+                let _ = sigint.thread_unblock();
+                Ok(child)
+            }
             Ok(ForkResult::Child) => {
+                let _ = unsafe { signal(Signal::SIGINT, SigHandler::SigDfl) };
                 group.worker.init();
+                let _ = sigint.thread_unblock();
                 group.worker.run();
                 group.worker.cleanup();
                 exit(0);
             }
-            Err(err) => Err(err),
+            Err(err) => {
+                let _ = sigint.thread_unblock();
+                Err(err)
+            }
         };
     }
 }
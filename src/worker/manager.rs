@@ -35,7 +35,7 @@ impl WorkerManager {
     pub fn start(&self) -> Vec<(&WorkerGroup, Vec<Pid>)> {
         let mut vec = vec![];
         for g in &self.groups {
-            let start_result = self.generator.start_group_workers(&g);
+            let start_result = self.generator.start_group_workers(g);
             match start_result {
                 Err(err) => {
                     log::error!("start failed: {err}");
@@ -60,6 +60,10 @@ impl WorkerManager {
         }
 
         pids.remove(idx.unwrap());
+        // stopped by the same SIGINT as the manager, do not replace it
+        if !unsafe { RUNNING } {
+            return Ok(None);
+        }
         return self.generator.fork_child(group).map(|p: Pid| Some(p));
     }
 